@@ -0,0 +1,69 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const SHADER_DIRECTORY: &str = "src/vulkan_engine/shaders";
+const SHADER_EXTENSIONS: [&str; 3] = ["vert", "frag", "comp"];
+
+/// Compiles every GLSL shader to SPIR-V and writes the table of
+/// `(file name, bytes)` the engine embeds
+fn main() {
+    println!("cargo:rerun-if-changed={}", SHADER_DIRECTORY);
+    println!("cargo:rerun-if-env-changed=VULKAN_SDK");
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let glslc = glslc_path();
+
+    let mut sources: Vec<PathBuf> = fs::read_dir(SHADER_DIRECTORY)
+        .expect("Unable to read the shader directory")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_shader_source(path))
+        .collect();
+    sources.sort();
+
+    let mut is_glslc_missing = false;
+    let mut table = String::from("&[\n");
+    for source in sources.iter() {
+        println!("cargo:rerun-if-changed={}", source.display());
+
+        let spv_name = format!("{}.spv", source.file_name().unwrap().to_string_lossy());
+        let spv_path = out_dir.join(&spv_name);
+
+        match Command::new(&glslc).arg(source).arg("-o").arg(&spv_path).output() {
+            Ok(output) if output.status.success() => {}
+            Ok(output) => panic!(
+                "{} failed to compile:\n{}",
+                source.display(),
+                String::from_utf8_lossy(&output.stderr)
+            ),
+            // still builds without the SDK, the engine reports the missing shader on startup
+            Err(_) => {
+                if !is_glslc_missing {
+                    println!("cargo:warning=glslc not found, shaders are not embedded. Install the Vulkan SDK or set VULKAN_SDK");
+                    is_glslc_missing = true;
+                }
+                fs::write(&spv_path, []).unwrap();
+            }
+        }
+
+        table.push_str(&format!("    ({:?}, include_bytes!({:?})),\n", spv_name, spv_path));
+    }
+    table.push_str("]\n");
+
+    fs::write(out_dir.join("embedded_shaders.rs"), table).unwrap();
+}
+
+fn is_shader_source(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => SHADER_EXTENSIONS.contains(&extension),
+        None => false
+    }
+}
+
+fn glslc_path() -> PathBuf {
+    match env::var_os("VULKAN_SDK") {
+        Some(sdk) => Path::new(&sdk).join("bin").join("glslc"),
+        None => PathBuf::from("glslc")
+    }
+}
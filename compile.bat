@@ -1,3 +0,0 @@
-C:\VulkanSDK\1.2.198.1\Bin\glslc.exe src\vulkan_engine\shaders\simple_shader.vert -o src\vulkan_engine\shaders\simple_shader.vert.spv
-C:\VulkanSDK\1.2.198.1\Bin\glslc.exe src\vulkan_engine\shaders\simple_shader.frag -o src\vulkan_engine\shaders\simple_shader.frag.spv
-pause
\ No newline at end of file
@@ -0,0 +1,221 @@
+use crate::item::inventory::Inventory;
+use crate::item::item_stack::ItemStack;
+use crate::item::tag::TagValue;
+use crate::world::block::Block;
+use crate::world::block_registry::StateId;
+use crate::world::weather::WeatherState;
+use crate::world::world::World;
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Weather(WeatherState, Option<f32>),
+    SetBlock(i32, i32, i32, Block),
+    // a state id or vanilla notation such as `oak_log[axis=x]`
+    BlockState(String),
+    BlockStateAt(i32, i32, i32),
+    Give(ItemStack)
+}
+
+impl Command {
+    /// Parses a console line such as `/weather thunder 120`. The leading slash is optional.
+    pub fn parse(line: &str) -> Result<Command, String> {
+        let line = line.trim();
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let mut arguments = line.split_whitespace();
+
+        match arguments.next() {
+            Some("weather") => {
+                let state = arguments
+                    .next()
+                    .ok_or("Usage: /weather <clear|rain|thunder> [seconds]")?;
+                let state = WeatherState::from_name(state)
+                    .ok_or(format!("Unknown weather state '{}'", state))?;
+                let duration = match arguments.next() {
+                    // a NaN or infinite timer would never run out
+                    Some(seconds) => Some(
+                        seconds.parse::<f32>()
+                            .ok()
+                            .filter(|seconds| seconds.is_finite() && *seconds > 0.0)
+                            .ok_or(format!("Invalid duration '{}'", seconds))?
+                    ),
+                    None => None
+                };
+                Ok(Command::Weather(state, duration))
+            }
+            Some("setblock") => {
+                const USAGE: &str = "Usage: /setblock <x> <y> <z> <block>";
+                let mut coordinates = [0; 3];
+                for coordinate in coordinates.iter_mut() {
+                    let argument = arguments.next().ok_or(USAGE)?;
+                    *coordinate = argument.parse::<i32>()
+                        .map_err(|_| format!("Invalid coordinate '{}'", argument))?;
+                }
+                let name = arguments.next().ok_or(USAGE)?;
+                let block = Block::from_name(name)
+                    .ok_or(format!("Unknown block '{}'", name))?;
+                Ok(Command::SetBlock(coordinates[0], coordinates[1], coordinates[2], block))
+            }
+            Some("blockstate") => {
+                const USAGE: &str = "Usage: /blockstate <id|block[property=value,...]|x y z>";
+                let first = arguments.next().ok_or(USAGE)?;
+                match (arguments.next(), arguments.next()) {
+                    (Some(y), Some(z)) => {
+                        let mut coordinates = [0; 3];
+                        for (coordinate, argument) in coordinates.iter_mut().zip([first, y, z].iter()) {
+                            *coordinate = argument.parse::<i32>()
+                                .map_err(|_| format!("Invalid coordinate '{}'", argument))?;
+                        }
+                        Ok(Command::BlockStateAt(coordinates[0], coordinates[1], coordinates[2]))
+                    }
+                    (None, None) => Ok(Command::BlockState(first.to_string())),
+                    _ => Err(USAGE.to_string())
+                }
+            }
+            Some("give") => {
+                const USAGE: &str = "Usage: /give <item>[{tag}] [count]";
+                // the tag may hold spaces, so the arguments aren't split on them
+                let rest = line["give".len()..].trim_start();
+                let end = rest
+                    .find(|character: char| character == '{' || character.is_whitespace())
+                    .unwrap_or(rest.len());
+                if end == 0 {
+                    return Err(USAGE.to_string());
+                }
+                let mut stack = ItemStack::new(&rest[..end], 1);
+
+                let mut rest = &rest[end..];
+                if rest.starts_with('{') {
+                    let (tag, after) = TagValue::parse(rest)?;
+                    if let TagValue::Compound(tag) = tag {
+                        stack.tag = tag;
+                    }
+                    // vanilla keeps the damage in the tag
+                    if let Some(TagValue::Int(damage)) = stack.tag.remove("Damage") {
+                        stack.damage = damage.max(0) as u32;
+                    }
+                    rest = after;
+                }
+
+                let mut arguments = rest.split_whitespace();
+                if let Some(count) = arguments.next() {
+                    stack.count = count.parse::<u32>()
+                        .ok()
+                        .filter(|&count| count > 0)
+                        .ok_or(format!("Invalid count '{}'", count))?;
+                }
+                if arguments.next().is_some() {
+                    return Err(USAGE.to_string());
+                }
+                Ok(Command::Give(stack))
+            }
+            Some(name) => Err(format!("Unknown command '{}'", name)),
+            None => Err("Empty command".to_string())
+        }
+    }
+
+    /// Runs the command and returns the feedback line for the console, `inventory` is the
+    /// player's when there is one
+    pub fn execute(&self, world: &mut World, inventory: Option<&mut Inventory>) -> String {
+        match self {
+            Command::Weather(state, duration) => {
+                world.weather.set_state(*state, *duration);
+                format!("Set the weather to {:?}", state)
+            }
+            Command::SetBlock(x, y, z, block) => {
+                world.set_block(*x, *y, *z, *block);
+                format!("Placed {:?} at {} {} {}", block, x, y, z)
+            }
+            Command::BlockState(text) => {
+                let state = match text.parse::<StateId>() {
+                    Ok(id) => world.blocks.block_of(id).map(|_| id),
+                    Err(_) => world.blocks.parse_state(text)
+                };
+                match state {
+                    Some(state) => describe_state(world, state),
+                    None => format!("Unknown block state '{}'", text)
+                }
+            }
+            Command::BlockStateAt(x, y, z) => match world.block_state(*x, *y, *z) {
+                Some(state) => describe_state(world, state),
+                None => format!("No block state at {} {} {}", x, y, z)
+            },
+            Command::Give(stack) => match inventory {
+                Some(inventory) => match inventory.add(stack.clone()) {
+                    Some(rest) => format!("Gave {} {}, {} did not fit", stack.count - rest.count, stack.item, rest.count),
+                    None => format!("Gave {} {}", stack.count, stack.item)
+                },
+                None => "There is no player to give items to".to_string()
+            }
+        }
+    }
+}
+
+fn describe_state(world: &World, state: StateId) -> String {
+    let name = world.blocks.state_name(state).unwrap_or_default();
+    match world.blocks.metadata(state) {
+        Some(metadata) => format!("{} is state {}: {:?}", name, state, metadata),
+        None => format!("{} is state {}", name, state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weather_takes_a_positive_finite_duration() {
+        assert_eq!(Command::parse("/weather rain 120").unwrap(), Command::Weather(WeatherState::Rain, Some(120.0)));
+        assert_eq!(Command::parse("weather clear").unwrap(), Command::Weather(WeatherState::Clear, None));
+
+        for duration in ["NaN", "inf", "-inf", "-5", "0", "soon"] {
+            assert!(Command::parse(&format!("/weather thunder {}", duration)).is_err(), "{}", duration);
+        }
+        assert!(Command::parse("/weather hail").is_err());
+        assert!(Command::parse("/weather").is_err());
+    }
+
+    #[test]
+    fn setblock_takes_three_coordinates_and_a_block() {
+        assert_eq!(
+            Command::parse("/setblock 1 -2 3 minecraft:oak_log").unwrap(),
+            Command::SetBlock(1, -2, 3, Block::OakLog)
+        );
+        assert!(Command::parse("/setblock 1 2 stone").is_err());
+        assert!(Command::parse("/setblock 1 2.5 3 stone").is_err());
+        assert!(Command::parse("/setblock 1 2 3 granite").is_err());
+    }
+
+    #[test]
+    fn blockstate_takes_a_state_or_a_position() {
+        assert_eq!(
+            Command::parse("/blockstate oak_log[axis=x]").unwrap(),
+            Command::BlockState("oak_log[axis=x]".to_string())
+        );
+        assert_eq!(Command::parse("/blockstate 4 5 -6").unwrap(), Command::BlockStateAt(4, 5, -6));
+        assert!(Command::parse("/blockstate 4 5").is_err());
+        assert!(Command::parse("/blockstate x 5 6").is_err());
+        assert!(Command::parse("/blockstate").is_err());
+    }
+
+    #[test]
+    fn give_reads_the_tag_and_count() {
+        let mut stack = ItemStack::new("iron_sword", 1);
+        stack.damage = 12;
+        stack.tag.insert("Name".to_string(), TagValue::String("Old sword".to_string()));
+        assert_eq!(
+            Command::parse("/give iron_sword{Damage: 12, Name: \"Old sword\"}").unwrap(),
+            Command::Give(stack)
+        );
+        assert_eq!(Command::parse("/give dirt 32").unwrap(), Command::Give(ItemStack::new("dirt", 32)));
+
+        assert!(Command::parse("/give").is_err());
+        assert!(Command::parse("/give dirt 0").is_err());
+        assert!(Command::parse("/give dirt 3 4").is_err());
+    }
+
+    #[test]
+    fn unknown_and_empty_lines_are_errors() {
+        assert!(Command::parse("/fly").is_err());
+        assert!(Command::parse("  ").is_err());
+    }
+}
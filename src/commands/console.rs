@@ -0,0 +1,41 @@
+use std::io::BufRead;
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+
+/// Reads debug commands from stdin on a background thread, so typing into the
+/// terminal never blocks the event loop.
+pub struct Console {
+    receiver: Receiver<String>
+}
+
+impl Console {
+    pub fn new() -> Console {
+        let (sender, receiver) = channel();
+
+        thread::Builder::new()
+            .name("console".to_string())
+            .spawn(move || {
+                let stdin = std::io::stdin();
+                for line in stdin.lock().lines() {
+                    let line = match line {
+                        Ok(line) => line,
+                        Err(_) => break
+                    };
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            })
+            .expect("Failed to spawn console thread");
+
+        Console { receiver }
+    }
+
+    /// Returns the next line typed into the console, if any
+    pub fn poll(&self) -> Option<String> {
+        match self.receiver.try_recv() {
+            Ok(line) => Some(line),
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => None
+        }
+    }
+}
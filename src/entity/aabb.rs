@@ -0,0 +1,152 @@
+use cgmath::{Point3, Vector3};
+
+use crate::world::block_view::BlockView;
+
+/// Axis aligned box in world coordinates
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    pub fn offset(&self, offset: Vector3<f32>) -> Aabb {
+        Aabb::new(self.min + offset, self.max + offset)
+    }
+
+    /// Grows the box in the direction of `offset`, covering everything it passes while moving
+    pub fn expand_towards(&self, offset: Vector3<f32>) -> Aabb {
+        let mut bounds = *self;
+        for axis in 0..3 {
+            if offset[axis] < 0.0 {
+                bounds.min[axis] += offset[axis];
+            } else {
+                bounds.max[axis] += offset[axis];
+            }
+        }
+        bounds
+    }
+
+    /// Boxes that only touch do not intersect
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        (0..3).all(|axis| self.min[axis] < other.max[axis] && self.max[axis] > other.min[axis])
+    }
+
+    /// Shortens a movement along `axis` so this box stops at `other` instead of entering it
+    pub fn clip(&self, other: &Aabb, axis: usize, offset: f32) -> f32 {
+        // boxes that don't overlap on the other two axes never meet
+        let overlaps = (0..3)
+            .filter(|&other_axis| other_axis != axis)
+            .all(|other_axis| self.min[other_axis] < other.max[other_axis] && self.max[other_axis] > other.min[other_axis]);
+        if !overlaps {
+            return offset;
+        }
+
+        if offset > 0.0 && self.max[axis] <= other.min[axis] {
+            offset.min(other.min[axis] - self.max[axis])
+        } else if offset < 0.0 && self.min[axis] >= other.max[axis] {
+            offset.max(other.max[axis] - self.min[axis])
+        } else {
+            offset
+        }
+    }
+
+    /// Moves the box through the obstacles one axis at a time, y first, and returns how far
+    /// it got along each axis
+    pub fn sweep(&self, offset: Vector3<f32>, obstacles: &[Aabb]) -> Vector3<f32> {
+        let mut bounds = *self;
+        let mut moved = Vector3::new(0.0, 0.0, 0.0);
+
+        for &axis in [1, 0, 2].iter() {
+            let mut distance = offset[axis];
+            for obstacle in obstacles.iter() {
+                distance = bounds.clip(obstacle, axis, distance);
+            }
+            moved[axis] = distance;
+
+            let mut step = Vector3::new(0.0, 0.0, 0.0);
+            step[axis] = distance;
+            bounds = bounds.offset(step);
+        }
+
+        moved
+    }
+}
+
+/// Collision boxes of the blocks intersecting `area`
+pub fn collision_boxes<W: BlockView>(world: &W, area: &Aabb) -> Vec<Aabb> {
+    let mut boxes = vec![];
+
+    for x in area.min.x.floor() as i32..area.max.x.ceil() as i32 {
+        for y in area.min.y.floor() as i32..area.max.y.ceil() as i32 {
+            for z in area.min.z.floor() as i32..area.max.z.ceil() as i32 {
+                let height = match world.get_block(x, y, z).collision_height() {
+                    Some(height) => height,
+                    None => continue
+                };
+
+                let block_box = Aabb::new(
+                    Point3::new(x as f32, y as f32, z as f32),
+                    Point3::new(x as f32 + 1.0, y as f32 + height, z as f32 + 1.0)
+                );
+                if block_box.intersects(area) {
+                    boxes.push(block_box);
+                }
+            }
+        }
+    }
+
+    boxes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+    use crate::world::block_view::BlockMap;
+
+    fn unit_box(x: f32, y: f32, z: f32) -> Aabb {
+        Aabb::new(Point3::new(x, y, z), Point3::new(x + 1.0, y + 1.0, z + 1.0))
+    }
+
+    #[test]
+    fn falling_box_lands_on_the_floor() {
+        let floor = BlockMap::filled((-1..=1).flat_map(|x| (-1..=1).map(move |z| (x, 0, z))), Block::Stone);
+        let falling = Aabb::new(Point3::new(-0.3, 1.5, -0.3), Point3::new(0.3, 3.3, 0.3));
+        let offset = Vector3::new(0.0, -2.0, 0.0);
+
+        let obstacles = collision_boxes(&floor, &falling.expand_towards(offset));
+        let moved = falling.sweep(offset, &obstacles);
+
+        assert_eq!(moved, Vector3::new(0.0, -0.5, 0.0));
+    }
+
+    #[test]
+    fn box_slides_along_a_wall() {
+        let wall = BlockMap::filled((-2..=3).flat_map(|z| (0..2).map(move |y| (1, y, z))), Block::Stone);
+        let walking = Aabb::new(Point3::new(0.2, 0.0, 0.2), Point3::new(0.8, 1.8, 0.8));
+        let offset = Vector3::new(0.5, 0.0, 1.0);
+
+        let obstacles = collision_boxes(&wall, &walking.expand_towards(offset));
+        let moved = walking.sweep(offset, &obstacles);
+
+        assert!((moved.x - 0.2).abs() < 1e-6);
+        assert_eq!(moved.z, 1.0);
+    }
+
+    #[test]
+    fn clip_ignores_boxes_beside_the_path() {
+        let moving = unit_box(0.0, 0.0, 0.0);
+
+        assert_eq!(moving.clip(&unit_box(3.0, 0.0, 0.0), 0, 5.0), 2.0);
+        assert_eq!(moving.clip(&unit_box(-3.0, 0.0, 0.0), 0, -5.0), -2.0);
+        // touching on the side is no overlap
+        assert_eq!(moving.clip(&unit_box(3.0, 1.0, 0.0), 0, 5.0), 5.0);
+        // moving away from a box it touches
+        assert_eq!(moving.clip(&unit_box(1.0, 0.0, 0.0), 0, -5.0), -5.0);
+    }
+}
@@ -0,0 +1,153 @@
+use cgmath::Point3;
+
+use crate::entity::aabb::Aabb;
+use crate::entity::player::Player;
+use crate::item::inventory::Inventory;
+use crate::item::item_stack::ItemStack;
+use crate::world::block::{Block, Face};
+use crate::world::raycast::RaycastHit;
+use crate::world::world::World;
+
+pub const CRACK_STAGES: u32 = 10;
+// breaking takes 1.5 seconds per point of hardness by hand, like vanilla
+const BREAK_TICKS_PER_HARDNESS: f32 = 30.0;
+// ticks between two blocks broken or placed while the button stays down
+const BREAK_DELAY: u32 = 5;
+const PLACE_DELAY: u32 = 4;
+
+/// Mouse buttons held during a tick
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InteractionInput {
+    pub attack: bool,
+    pub use_item: bool
+}
+
+struct Breaking {
+    position: Point3<i32>,
+    // 1 breaks the block
+    progress: f32
+}
+
+/// Breaking the targeted block over time and using the item in the main hand on it
+pub struct BlockInteraction {
+    breaking: Option<Breaking>,
+    break_delay: u32,
+    place_delay: u32
+}
+
+impl BlockInteraction {
+    pub fn new() -> BlockInteraction {
+        BlockInteraction {
+            breaking: None,
+            break_delay: 0,
+            place_delay: 0
+        }
+    }
+
+    pub fn tick(&mut self, world: &mut World, player: &mut Player, target: Option<&RaycastHit>, input: &InteractionInput) {
+        match target {
+            Some(target) if input.attack => self.tick_breaking(world, &mut player.inventory, target),
+            _ => {
+                self.breaking = None;
+                self.break_delay = 0;
+            }
+        }
+
+        if input.use_item {
+            if self.place_delay == 0 {
+                self.use_item(world, player, target);
+                self.place_delay = PLACE_DELAY;
+            } else {
+                self.place_delay -= 1;
+            }
+        } else {
+            self.place_delay = 0;
+        }
+    }
+
+    fn tick_breaking(&mut self, world: &mut World, inventory: &mut Inventory, target: &RaycastHit) {
+        if self.break_delay > 0 {
+            self.break_delay -= 1;
+            return;
+        }
+        let hardness = match target.block.hardness() {
+            Some(hardness) => hardness,
+            None => {
+                self.breaking = None;
+                return;
+            }
+        };
+
+        // looking at another block starts over
+        let progress = match &self.breaking {
+            Some(breaking) if breaking.position == target.block_position => breaking.progress,
+            _ => 0.0
+        };
+        let break_ticks = (hardness * BREAK_TICKS_PER_HARDNESS).ceil().max(1.0);
+        let progress = progress + 1.0 / break_ticks;
+
+        if progress >= 1.0 {
+            let position = target.block_position;
+            world.set_block(position.x, position.y, position.z, Block::Air);
+            // there are no item entities, drops go straight into the inventory and are lost when it is full
+            if let Some(dropped) = target.block.dropped_block() {
+                inventory.add(ItemStack::new(dropped.name(), 1));
+            }
+            self.breaking = None;
+            self.break_delay = BREAK_DELAY;
+        } else {
+            self.breaking = Some(Breaking {
+                position: target.block_position,
+                progress
+            });
+        }
+    }
+
+    /// Puts on armor held in the main hand, otherwise places the held block
+    fn use_item(&self, world: &mut World, player: &mut Player, target: Option<&RaycastHit>) {
+        if player.inventory.equip_selected() {
+            return;
+        }
+        if let Some(target) = target {
+            self.place(world, player, target);
+        }
+    }
+
+    /// Puts the held block in front of the clicked face, unless something is there or
+    /// it would end up inside the player
+    fn place(&self, world: &mut World, player: &mut Player, target: &RaycastHit) {
+        let block = match player.inventory.selected_stack().and_then(|stack| Block::from_name(&stack.item)) {
+            Some(block) if block != Block::Air => block,
+            _ => return
+        };
+
+        let (offset_x, offset_y, offset_z) = target.face.offset();
+        let position = target.block_position;
+        let (x, y, z) = (position.x + offset_x, position.y + offset_y, position.z + offset_z);
+        if !world.get_block(x, y, z).is_replaceable() {
+            return;
+        }
+
+        if let Some(height) = block.collision_height() {
+            let block_box = Aabb::new(
+                Point3::new(x as f32, y as f32, z as f32),
+                Point3::new(x as f32 + 1.0, y as f32 + height, z as f32 + 1.0)
+            );
+            if block_box.intersects(&player.bounds()) {
+                return;
+            }
+        }
+
+        let facing = block.placement_facing(target.face, Face::from_yaw(player.yaw));
+        world.place_block(x, y, z, block, facing);
+        player.inventory.take_selected(1);
+    }
+
+    /// The block being broken and how far along it is, in [0, CRACK_STAGES)
+    pub fn crack_stage(&self) -> Option<(Point3<i32>, u32)> {
+        self.breaking.as_ref().map(|breaking| {
+            let stage = (breaking.progress * CRACK_STAGES as f32) as u32;
+            (breaking.position, stage.min(CRACK_STAGES - 1))
+        })
+    }
+}
@@ -0,0 +1,3 @@
+pub mod aabb;
+pub mod block_interaction;
+pub mod player;
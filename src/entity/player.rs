@@ -0,0 +1,326 @@
+use cgmath::{InnerSpace, Point3, Vector2, Vector3};
+
+use crate::entity::aabb::{self, Aabb};
+use crate::item::inventory::Inventory;
+use crate::world::block_view::BlockView;
+use crate::world::chunk::ChunkPos;
+
+// sizes in blocks, speeds in blocks per tick like vanilla
+const WIDTH: f32 = 0.6;
+const HEIGHT: f32 = 1.8;
+const EYE_HEIGHT: f32 = 1.62;
+const SNEAK_EYE_OFFSET: f32 = 0.08;
+// highest ledge walked onto without jumping, enough for half blocks
+const STEP_HEIGHT: f32 = 0.6;
+
+const GRAVITY: f32 = 0.08;
+const AIR_DRAG: f32 = 0.98;
+const TERMINAL_VELOCITY: f32 = 3.92;
+const JUMP_VELOCITY: f32 = 0.42;
+// sprint jumps push forward
+const SPRINT_JUMP_BOOST: f32 = 0.2;
+
+const GROUND_ACCELERATION: f32 = 0.098;
+const AIR_ACCELERATION: f32 = 0.02;
+// horizontal speed kept per tick
+const GROUND_FRICTION: f32 = 0.546;
+const AIR_FRICTION: f32 = 0.91;
+const SPRINT_MULTIPLIER: f32 = 1.3;
+const SNEAK_MULTIPLIER: f32 = 0.3;
+
+const FLUID_ACCELERATION: f32 = 0.02;
+const FLUID_DRAG: f32 = 0.8;
+const FLUID_GRAVITY: f32 = 0.02;
+const SWIM_UP_ACCELERATION: f32 = 0.04;
+
+// blocks from the eyes that can be targeted
+const DEFAULT_REACH: f32 = 5.0;
+
+// how far sneaking backs off from a ledge per try
+const EDGE_STEP: f32 = 0.05;
+
+/// Movement wanted for the next tick, forward and strafe in [-1, 1] with strafe positive to the right
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayerInput {
+    pub forward: f32,
+    pub strafe: f32,
+    pub jump: bool,
+    pub sprint: bool,
+    pub sneak: bool
+}
+
+pub struct Player {
+    // centre of the feet
+    pub position: Point3<f32>,
+    // position before the last tick, rendering blends between the two
+    previous_position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    // degrees, matches the camera
+    pub yaw: f32,
+    pub on_ground: bool,
+    pub is_sneaking: bool,
+    pub is_sprinting: bool,
+    pub is_in_fluid: bool,
+    pub reach: f32,
+    pub inventory: Inventory
+}
+
+impl Player {
+    pub fn new(position: Point3<f32>) -> Player {
+        Player {
+            position,
+            previous_position: position,
+            velocity: Vector3::new(0.0, 0.0, 0.0),
+            yaw: 0.0,
+            on_ground: false,
+            is_sneaking: false,
+            is_sprinting: false,
+            is_in_fluid: false,
+            reach: DEFAULT_REACH,
+            inventory: Inventory::new()
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        Player::bounds_at(self.position)
+    }
+
+    fn bounds_at(position: Point3<f32>) -> Aabb {
+        Aabb::new(
+            Point3::new(position.x - WIDTH / 2.0, position.y, position.z - WIDTH / 2.0),
+            Point3::new(position.x + WIDTH / 2.0, position.y + HEIGHT, position.z + WIDTH / 2.0)
+        )
+    }
+
+    /// Eye position between the last two ticks
+    pub fn eye_position(&self, partial_tick: f32) -> Point3<f32> {
+        let position = self.previous_position + (self.position - self.previous_position) * partial_tick;
+        let eye_height = if self.is_sneaking { EYE_HEIGHT - SNEAK_EYE_OFFSET } else { EYE_HEIGHT };
+        position + Vector3::new(0.0, eye_height, 0.0)
+    }
+
+    pub fn tick<W: BlockView>(&mut self, world: &W, input: &PlayerInput) {
+        self.previous_position = self.position;
+
+        // the player waits in place until the chunk around it is loaded
+        let chunk = ChunkPos::from_block(self.position.x.floor() as i32, self.position.z.floor() as i32);
+        if world.chunk(chunk).is_none() {
+            return;
+        }
+
+        self.is_in_fluid = self.touches_fluid(world);
+        self.is_sneaking = input.sneak;
+        self.is_sprinting = input.sprint && input.forward > 0.0 && !input.sneak;
+
+        let (forward, right) = self.horizontal_axes();
+        if input.jump {
+            if self.is_in_fluid {
+                self.velocity.y += SWIM_UP_ACCELERATION;
+            } else if self.on_ground {
+                self.velocity.y = JUMP_VELOCITY;
+                if self.is_sprinting {
+                    self.velocity += forward * SPRINT_JUMP_BOOST;
+                }
+            }
+        }
+
+        let mut acceleration = if self.is_in_fluid {
+            FLUID_ACCELERATION
+        } else if self.on_ground {
+            GROUND_ACCELERATION
+        } else {
+            AIR_ACCELERATION
+        };
+        if self.is_sprinting {
+            acceleration *= SPRINT_MULTIPLIER;
+        } else if self.is_sneaking {
+            acceleration *= SNEAK_MULTIPLIER;
+        }
+
+        // diagonal input is no faster than straight input
+        let mut wish = Vector2::new(input.strafe, input.forward);
+        if wish.magnitude2() > 1.0 {
+            wish = wish.normalize();
+        }
+        self.velocity += (right * wish.x + forward * wish.y) * acceleration;
+
+        let velocity = self.velocity;
+        self.move_by(world, velocity);
+
+        if self.is_in_fluid {
+            self.velocity *= FLUID_DRAG;
+            self.velocity.y -= FLUID_GRAVITY;
+        } else {
+            let friction = if self.on_ground { GROUND_FRICTION } else { AIR_FRICTION };
+            self.velocity.x *= friction;
+            self.velocity.z *= friction;
+            self.velocity.y = (self.velocity.y - GRAVITY) * AIR_DRAG;
+        }
+        self.velocity.y = self.velocity.y.max(-TERMINAL_VELOCITY);
+    }
+
+    /// Unit vectors along the ground the player faces and to its right
+    fn horizontal_axes(&self) -> (Vector3<f32>, Vector3<f32>) {
+        let yaw = self.yaw.to_radians();
+        (
+            Vector3::new(yaw.sin(), 0.0, -yaw.cos()),
+            Vector3::new(yaw.cos(), 0.0, yaw.sin())
+        )
+    }
+
+    fn touches_fluid<W: BlockView>(&self, world: &W) -> bool {
+        let bounds = self.bounds();
+        for x in bounds.min.x.floor() as i32..bounds.max.x.ceil() as i32 {
+            for y in bounds.min.y.floor() as i32..bounds.max.y.ceil() as i32 {
+                for z in bounds.min.z.floor() as i32..bounds.max.z.ceil() as i32 {
+                    if world.get_block(x, y, z).is_fluid() {
+                        return true;
+                    }
+                }
+            }
+        }
+        false
+    }
+
+    fn has_ground_below<W: BlockView>(&self, world: &W, offset_x: f32, offset_z: f32) -> bool {
+        let area = self.bounds().offset(Vector3::new(offset_x, -STEP_HEIGHT, offset_z));
+        !aabb::collision_boxes(world, &area).is_empty()
+    }
+
+    /// Shortens a horizontal movement until the player still stands on something afterwards
+    fn stop_at_edge<W: BlockView>(&self, world: &W, offset: &mut Vector3<f32>) {
+        let towards_zero = |value: f32| {
+            if value.abs() < EDGE_STEP { 0.0 } else { value - EDGE_STEP * value.signum() }
+        };
+
+        while offset.x != 0.0 && !self.has_ground_below(world, offset.x, 0.0) {
+            offset.x = towards_zero(offset.x);
+        }
+        while offset.z != 0.0 && !self.has_ground_below(world, 0.0, offset.z) {
+            offset.z = towards_zero(offset.z);
+        }
+        while offset.x != 0.0 && offset.z != 0.0 && !self.has_ground_below(world, offset.x, offset.z) {
+            offset.x = towards_zero(offset.x);
+            offset.z = towards_zero(offset.z);
+        }
+    }
+
+    /// Moves through the world without entering solid blocks, stepping up low ledges.
+    /// Velocity along a blocked axis is dropped.
+    fn move_by<W: BlockView>(&mut self, world: &W, offset: Vector3<f32>) {
+        let mut offset = offset;
+        if self.is_sneaking && self.on_ground && !self.is_in_fluid {
+            self.stop_at_edge(world, &mut offset);
+        }
+
+        let bounds = self.bounds();
+        let area = bounds
+            .expand_towards(offset)
+            .expand_towards(Vector3::new(0.0, STEP_HEIGHT, 0.0));
+        let obstacles = aabb::collision_boxes(world, &area);
+        let mut moved = bounds.sweep(offset, &obstacles);
+
+        let is_blocked_horizontally = moved.x != offset.x || moved.z != offset.z;
+        let is_landing = offset.y < 0.0 && moved.y != offset.y;
+        if is_blocked_horizontally && (self.on_ground || is_landing) {
+            // lift, move across, then put down onto whatever is below
+            let lift = bounds.sweep(Vector3::new(0.0, STEP_HEIGHT, 0.0), &obstacles).y;
+            let raised = bounds.offset(Vector3::new(0.0, lift, 0.0));
+            let across = raised.sweep(Vector3::new(offset.x, 0.0, offset.z), &obstacles);
+            let lowered = raised.offset(across);
+            let drop = lowered.sweep(Vector3::new(0.0, -lift, 0.0), &obstacles).y;
+
+            let stepped = Vector3::new(across.x, lift + drop, across.z);
+            if stepped.x * stepped.x + stepped.z * stepped.z > moved.x * moved.x + moved.z * moved.z {
+                moved = stepped;
+            }
+        }
+
+        self.position += moved;
+        self.on_ground = offset.y < 0.0 && moved.y > offset.y;
+
+        if moved.x != offset.x {
+            self.velocity.x = 0.0;
+        }
+        if moved.y != offset.y {
+            self.velocity.y = 0.0;
+        }
+        if moved.z != offset.z {
+            self.velocity.z = 0.0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+    use crate::world::block_view::BlockMap;
+
+    // a floor of stone at y 0 from x 0 to 4, spanning z -2 to 2
+    fn floor() -> BlockMap {
+        BlockMap::filled((0..5).flat_map(|x| (-2..=2).map(move |z| (x, 0, z))), Block::Stone)
+    }
+
+    fn standing_player(position: Point3<f32>) -> Player {
+        let mut player = Player::new(position);
+        player.on_ground = true;
+        player
+    }
+
+    #[test]
+    fn falling_player_lands_on_the_floor() {
+        let mut player = Player::new(Point3::new(2.5, 1.5, 0.5));
+        player.velocity.y = -1.0;
+
+        player.move_by(&floor(), Vector3::new(0.0, -1.0, 0.0));
+
+        assert_eq!(player.position.y, 1.0);
+        assert!(player.on_ground);
+        assert_eq!(player.velocity.y, 0.0);
+    }
+
+    #[test]
+    fn player_steps_onto_a_half_block_ledge() {
+        // no block is half height, so the player starts half a block below a full ledge
+        let ledge = BlockMap::filled(vec![(1, 1, 0)], Block::Stone);
+        let mut player = standing_player(Point3::new(0.5, 1.5, 0.5));
+
+        player.move_by(&ledge, Vector3::new(0.5, 0.0, 0.0));
+
+        assert!((player.position.x - 1.0).abs() < 1e-5);
+        assert!((player.position.y - 2.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn player_does_not_step_onto_a_full_block() {
+        let mut world = floor();
+        world.0.insert((1, 1, 0), Block::Stone);
+        let mut player = standing_player(Point3::new(0.5, 1.0, 0.5));
+        player.velocity.x = 0.5;
+
+        player.move_by(&world, Vector3::new(0.5, 0.0, 0.0));
+
+        assert!((player.position.x - 0.7).abs() < 1e-5);
+        assert_eq!(player.position.y, 1.0);
+        assert_eq!(player.velocity.x, 0.0);
+    }
+
+    #[test]
+    fn sneaking_stops_at_a_ledge() {
+        let world = floor();
+        let mut player = standing_player(Point3::new(4.0, 1.0, 0.5));
+        player.is_sneaking = true;
+
+        for _ in 0..10 {
+            player.move_by(&world, Vector3::new(0.3, -0.1, 0.0));
+        }
+        // the player may lean out until only the edge of its box is above the floor
+        assert!(player.position.x > 5.0 && player.position.x < 5.0 + WIDTH / 2.0);
+        assert_eq!(player.position.y, 1.0);
+
+        player.is_sneaking = false;
+        player.move_by(&world, Vector3::new(0.3, -0.1, 0.0));
+        assert!(player.position.x > 5.0 + WIDTH / 2.0);
+    }
+}
@@ -0,0 +1,56 @@
+use std::thread;
+use std::time::Instant;
+
+use crate::commands::command::Command;
+use crate::commands::console::Console;
+use crate::game_loop::open_world;
+use crate::game_loop::tick_clock::{TickClock, TICKS_PER_SECOND};
+use crate::jobs::job_system::JobSystem;
+
+// chunks kept loaded around spawn without a camera to follow
+const SIMULATION_DISTANCE: i32 = 8;
+
+/// Runs the world simulation without a window, for servers and tests. Stops after
+/// `tick_limit` ticks, or when `stop` is typed into the console.
+pub fn run(tick_limit: Option<u64>) {
+    let jobs = JobSystem::new();
+    let mut world = open_world();
+    let [spawn_x, _, spawn_z] = world.level.spawn;
+    world.stream_around(&jobs, spawn_x, spawn_z, SIMULATION_DISTANCE);
+
+    let console = Console::new();
+    let mut clock = TickClock::new(Instant::now());
+    println!("Running headless at {} ticks per second", TICKS_PER_SECOND);
+
+    'simulation: loop {
+        while let Some(line) = console.poll() {
+            match line.trim() {
+                "stop" => break 'simulation,
+                "pause" => {
+                    clock.set_paused(!clock.is_paused());
+                    println!("{}", if clock.is_paused() { "Paused" } else { "Resumed" });
+                }
+                _ => match Command::parse(&line) {
+                    Ok(command) => println!("{}", command.execute(&mut world, None)),
+                    Err(message) => println!("{}", message)
+                }
+            }
+        }
+
+        for _ in 0..clock.advance(Instant::now()) {
+            world.tick();
+        }
+        world.receive_chunks();
+
+        if tick_limit.is_some_and(|limit| clock.tick_count >= limit) {
+            break;
+        }
+        thread::sleep(clock.until_next_tick());
+    }
+
+    if clock.skipped_ticks > 0 {
+        println!("Skipped {} ticks the simulation could not keep up with", clock.skipped_ticks);
+    }
+    world.save_and_flush();
+    println!("Stopped after {} ticks", clock.tick_count);
+}
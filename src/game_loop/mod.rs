@@ -0,0 +1,40 @@
+pub mod tick_clock;
+pub mod headless;
+
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::settings_loader::settings_directory;
+use crate::world::block_registry::{BlockRegistry, BLOCKSTATE_DIRECTORY};
+use crate::world::world::World;
+
+// seed of newly created worlds, saved worlds keep their own
+const WORLD_SEED: u64 = 20211218;
+const WORLD_NAME: &str = "world";
+// chunks generated around spawn on startup
+const SPAWN_RADIUS: i32 = 4;
+
+/// Opens the saved world and generates the chunks around its spawn. Exits when the
+/// blockstate files can't be read, the game can't name its blocks without them.
+pub fn open_world() -> World {
+    let blocks = match BlockRegistry::load(Path::new(BLOCKSTATE_DIRECTORY)) {
+        Ok(blocks) => blocks,
+        Err(error) => {
+            eprintln!("Unable to load the block states: {}", error);
+            std::process::exit(1);
+        }
+    };
+    println!("Registered {} blocks with {} states", blocks.blocks().len(), blocks.state_count());
+
+    let save_directory = settings_directory().join("saves").join(WORLD_NAME);
+    let mut world = match World::load(&save_directory, WORLD_SEED, Arc::new(blocks)) {
+        Ok(world) => world,
+        Err(error) => {
+            eprintln!("Unable to open the world in {:?}: {}", save_directory, error);
+            std::process::exit(1);
+        }
+    };
+    let [spawn_x, _, spawn_z] = world.level.spawn;
+    world.generate_around(spawn_x, spawn_z, SPAWN_RADIUS);
+    world
+}
@@ -0,0 +1,150 @@
+use std::time::{Duration, Instant};
+
+pub const TICKS_PER_SECOND: u32 = 20;
+pub const SECONDS_PER_TICK: f32 = 1.0 / TICKS_PER_SECOND as f32;
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+// a frame runs at most this many ticks, time beyond that is dropped instead of caught up
+const MAX_TICKS_PER_FRAME: u32 = 10;
+
+/// Turns real time into fixed length game ticks. Time accumulates until a whole tick has
+/// passed, the remainder tells rendering how far it is between the last tick and the next.
+pub struct TickClock {
+    last_time: Instant,
+    // real time not yet turned into ticks, less than one tick after `advance`
+    accumulator: Duration,
+    is_paused: bool,
+    // ticks run since the clock was created
+    pub tick_count: u64,
+    // ticks dropped because the simulation could not keep up
+    pub skipped_ticks: u64
+}
+
+impl TickClock {
+    pub fn new(now: Instant) -> TickClock {
+        TickClock {
+            last_time: now,
+            accumulator: Duration::ZERO,
+            is_paused: false,
+            tick_count: 0,
+            skipped_ticks: 0
+        }
+    }
+
+    /// Number of ticks to run for the time passed since the last call, none while paused
+    pub fn advance(&mut self, now: Instant) -> u32 {
+        let elapsed = now.saturating_duration_since(self.last_time);
+        self.last_time = now;
+        if self.is_paused {
+            return 0;
+        }
+
+        self.accumulator += elapsed;
+        let mut ticks = 0;
+        while self.accumulator >= TICK_DURATION {
+            self.accumulator -= TICK_DURATION;
+            ticks += 1;
+        }
+
+        // after a stall, catching up on every tick would make the next frame stall too
+        if ticks > MAX_TICKS_PER_FRAME {
+            self.skipped_ticks += (ticks - MAX_TICKS_PER_FRAME) as u64;
+            ticks = MAX_TICKS_PER_FRAME;
+        }
+        self.tick_count += ticks as u64;
+
+        ticks
+    }
+
+    /// How far rendering is from the last tick towards the next one, in [0, 1)
+    pub fn partial_tick(&self) -> f32 {
+        self.accumulator.as_secs_f32() / TICK_DURATION.as_secs_f32()
+    }
+
+    /// Seconds of game time, advancing smoothly between ticks and standing still while paused
+    pub fn game_time(&self) -> f32 {
+        (self.tick_count as f32 + self.partial_tick()) * SECONDS_PER_TICK
+    }
+
+    /// Time until the next tick is due
+    pub fn until_next_tick(&self) -> Duration {
+        TICK_DURATION - self.accumulator
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.is_paused
+    }
+
+    /// The partial tick is kept, so a paused frame looks the same as the frame before
+    pub fn set_paused(&mut self, is_paused: bool) {
+        self.is_paused = is_paused;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_simulated_second_runs_twenty_ticks() {
+        let start = Instant::now();
+        let mut clock = TickClock::new(start);
+
+        // 60 frames of a little under 17 ms, the remainder carries over between frames
+        let mut ticks = 0;
+        for frame in 1..=60 {
+            ticks += clock.advance(start + Duration::from_secs(frame) / 60);
+        }
+
+        assert_eq!(ticks, TICKS_PER_SECOND);
+        assert_eq!(clock.tick_count, TICKS_PER_SECOND as u64);
+        assert_eq!(clock.skipped_ticks, 0);
+    }
+
+    #[test]
+    fn ticks_beyond_the_frame_cap_are_skipped() {
+        let start = Instant::now();
+        let mut clock = TickClock::new(start);
+
+        let ticks = clock.advance(start + TICK_DURATION * (MAX_TICKS_PER_FRAME + 5));
+        assert_eq!(ticks, MAX_TICKS_PER_FRAME);
+        assert_eq!(clock.skipped_ticks, 5);
+        assert_eq!(clock.tick_count, MAX_TICKS_PER_FRAME as u64);
+
+        // the skipped time is gone rather than caught up in the next frame
+        let next = clock.advance(start + TICK_DURATION * (MAX_TICKS_PER_FRAME + 5) + TICK_DURATION / 2);
+        assert_eq!(next, 0);
+    }
+
+    #[test]
+    fn partial_tick_stays_below_one() {
+        let start = Instant::now();
+        let mut clock = TickClock::new(start);
+
+        for step in 1..=200 {
+            clock.advance(start + Duration::from_millis(7 * step));
+            let partial_tick = clock.partial_tick();
+            assert!((0.0..1.0).contains(&partial_tick), "partial tick {} at step {}", partial_tick, step);
+        }
+
+        clock.advance(start + Duration::from_millis(1400) + TICK_DURATION * 3 / 4);
+        assert!((clock.partial_tick() - 0.75).abs() < 1e-4);
+    }
+
+    #[test]
+    fn paused_clock_runs_no_ticks_and_builds_no_backlog() {
+        let start = Instant::now();
+        let mut clock = TickClock::new(start);
+        clock.advance(start + TICK_DURATION / 2);
+
+        clock.set_paused(true);
+        assert_eq!(clock.advance(start + Duration::from_secs(5)), 0);
+        assert_eq!(clock.advance(start + Duration::from_secs(10)), 0);
+        assert_eq!(clock.tick_count, 0);
+        assert!((clock.partial_tick() - 0.5).abs() < 1e-4);
+
+        clock.set_paused(false);
+        assert_eq!(clock.advance(start + Duration::from_secs(10) + TICK_DURATION / 4), 0);
+        assert_eq!(clock.advance(start + Duration::from_secs(10) + TICK_DURATION / 2), 1);
+        assert_eq!(clock.skipped_ticks, 0);
+    }
+}
@@ -0,0 +1,111 @@
+use crate::item::item_stack::ItemStack;
+
+pub const MAIN_SLOTS: usize = 36;
+// the first main slots, selectable without opening the inventory
+pub const HOTBAR_SLOTS: usize = 9;
+pub const ARMOR_SLOTS: usize = 4;
+
+/// The items a player carries, slots are numbered like vanilla's: the hotbar first,
+/// then the three rows above it
+pub struct Inventory {
+    pub main: [Option<ItemStack>; MAIN_SLOTS],
+    // feet, legs, chest, head, see `ItemStack::armor_slot`
+    pub armor: [Option<ItemStack>; ARMOR_SLOTS],
+    pub offhand: Option<ItemStack>,
+    // hotbar slot of the item in the main hand
+    selected: usize
+}
+
+impl Inventory {
+    pub fn new() -> Inventory {
+        Inventory {
+            main: std::array::from_fn(|_| None),
+            armor: std::array::from_fn(|_| None),
+            offhand: None,
+            selected: 0
+        }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn select(&mut self, slot: usize) {
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        }
+    }
+
+    /// Moves the selection by `steps` slots to the right, wrapping around the hotbar
+    pub fn scroll(&mut self, steps: i32) {
+        self.selected = (self.selected as i32 + steps).rem_euclid(HOTBAR_SLOTS as i32) as usize;
+    }
+
+    pub fn selected_stack(&self) -> Option<&ItemStack> {
+        self.main[self.selected].as_ref()
+    }
+
+    /// Takes up to `count` items off the stack in the main hand
+    pub fn take_selected(&mut self, count: u32) -> Option<ItemStack> {
+        let slot = &mut self.main[self.selected];
+        let taken = slot.as_mut()?.split(count);
+        if slot.as_ref().is_some_and(ItemStack::is_empty) {
+            *slot = None;
+        }
+
+        if taken.is_empty() { None } else { Some(taken) }
+    }
+
+    /// Puts a stack away, onto stacks of the same item first and then into empty slots,
+    /// the hotbar before the rows above it. Returns what did not fit.
+    pub fn add(&mut self, stack: ItemStack) -> Option<ItemStack> {
+        let mut rest = stack;
+        for slot in self.main.iter_mut().flatten() {
+            match slot.merge(rest) {
+                Some(left) => rest = left,
+                None => return None
+            }
+        }
+
+        for slot in self.main.iter_mut().filter(|slot| slot.is_none()) {
+            let max_stack_size = rest.max_stack_size();
+            if rest.count <= max_stack_size {
+                *slot = Some(rest);
+                return None;
+            }
+            *slot = Some(rest.split(max_stack_size));
+        }
+        Some(rest)
+    }
+
+    /// Picking a block: selects the hotbar slot holding `item`, or swaps it into the
+    /// selected slot from the rows above. False when there is none.
+    pub fn pick(&mut self, item: &str) -> bool {
+        let slot = match self.main.iter().position(|stack| stack.as_ref().is_some_and(|stack| stack.item == item)) {
+            Some(slot) => slot,
+            None => return false
+        };
+
+        if slot < HOTBAR_SLOTS {
+            self.selected = slot;
+        } else {
+            self.main.swap(slot, self.selected);
+        }
+        true
+    }
+
+    pub fn swap_offhand(&mut self) {
+        std::mem::swap(&mut self.main[self.selected], &mut self.offhand);
+    }
+
+    /// Puts on the armor in the main hand when nothing is worn in its slot yet
+    pub fn equip_selected(&mut self) -> bool {
+        let slot = match self.selected_stack().and_then(ItemStack::armor_slot) {
+            Some(slot) if self.armor[slot].is_none() => slot,
+            _ => return false
+        };
+
+        self.armor[slot] = self.main[self.selected].take();
+        true
+    }
+}
@@ -0,0 +1,74 @@
+use serde_json::Value;
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::world::block::Block;
+
+pub const ITEM_MODEL_DIRECTORY: &str = "TextureData/assets/minecraft/models/item";
+
+/// The texture drawn for each item of `model_directory` whose model leads to one, named
+/// relative to `texture_directory` without the extension, like `item/stick`
+pub fn icon_textures(model_directory: &Path, texture_directory: &Path) -> std::io::Result<HashMap<String, String>> {
+    let mut icons = HashMap::new();
+    for entry in fs::read_dir(model_directory)? {
+        let path = entry?.path();
+        if path.extension().map_or(true, |extension| extension != "json") {
+            continue;
+        }
+        let item = path.file_stem().unwrap().to_string_lossy().to_string();
+        if let Some(texture) = icon_texture(model_directory, texture_directory, &item)? {
+            icons.insert(item, texture);
+        }
+    }
+
+    Ok(icons)
+}
+
+/// The block texture an item of the block shows when no item model has one for it
+pub fn item_texture(block: Block) -> Option<&'static str> {
+    match block {
+        Block::Air => None,
+        Block::Grass => Some("grass_block_side"),
+        Block::Furnace => Some("furnace_front"),
+        Block::Water => Some("water_still"),
+        Block::Lava => Some("lava_still"),
+        Block::Snow => Some("snow"),
+        // the rest are named like their texture
+        _ => Some(block.name())
+    }
+}
+
+/// Follows the parents of a model to its first layer, or to the block it shows. The pack
+/// has no block models, so a block is shown by the texture of the same name.
+fn icon_texture(model_directory: &Path, texture_directory: &Path, model: &str) -> std::io::Result<Option<String>> {
+    let path = model_directory.join(format!("{}.json", model));
+    let content = match fs::read(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(error) => return Err(error)
+    };
+    let content: Value = serde_json::from_slice(&content)
+        .map_err(|error| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))?;
+
+    let texture = match (content["textures"]["layer0"].as_str(), content["parent"].as_str()) {
+        (Some(layer), _) => layer,
+        (None, Some(parent)) => {
+            let parent = parent.strip_prefix("minecraft:").unwrap_or(parent);
+            if let Some(parent) = parent.strip_prefix("item/") {
+                return icon_texture(model_directory, texture_directory, parent);
+            }
+            parent
+        }
+        (None, None) => return Ok(None)
+    };
+
+    let texture = texture.strip_prefix("minecraft:").unwrap_or(texture);
+    if texture_directory.join(format!("{}.png", texture)).exists() {
+        Ok(Some(texture.to_string()))
+    } else {
+        Ok(None)
+    }
+}
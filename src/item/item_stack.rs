@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+
+use crate::item::tag::TagValue;
+
+// vanilla stack sizes besides the usual 64
+const UNSTACKABLE_SUFFIXES: [&str; 13] = [
+    "_sword", "_pickaxe", "_axe", "_shovel", "_hoe", "_helmet", "_chestplate", "_leggings", "_boots",
+    "_boat", "_minecart", "_bucket", "_horse_armor"
+];
+const UNSTACKABLE_ITEMS: [&str; 14] = [
+    "bow", "crossbow", "trident", "shield", "elytra", "saddle", "fishing_rod", "flint_and_steel",
+    "shears", "potion", "splash_potion", "lingering_potion", "minecart", "totem_of_undying"
+];
+const SIXTEEN_SUFFIXES: [&str; 2] = ["_sign", "_banner"];
+const SIXTEEN_ITEMS: [&str; 5] = ["snowball", "egg", "ender_pearl", "bucket", "honey_bottle"];
+
+/// A number of one item. Stacks of the same item only merge when their damage and tag
+/// are equal too.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ItemStack {
+    // vanilla id without the namespace, also the name of the item's model
+    pub item: String,
+    pub count: u32,
+    // durability a tool or piece of armor has used up
+    pub damage: u32,
+    pub tag: BTreeMap<String, TagValue>
+}
+
+impl ItemStack {
+    pub fn new(item: &str, count: u32) -> ItemStack {
+        ItemStack {
+            item: item.strip_prefix("minecraft:").unwrap_or(item).to_string(),
+            count,
+            damage: 0,
+            tag: BTreeMap::new()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn max_stack_size(&self) -> u32 {
+        let item = self.item.as_str();
+        if UNSTACKABLE_ITEMS.contains(&item)
+            || UNSTACKABLE_SUFFIXES.iter().any(|suffix| item.ends_with(suffix))
+            || item.starts_with("music_disc_")
+        {
+            1
+        } else if SIXTEEN_ITEMS.contains(&item) || SIXTEEN_SUFFIXES.iter().any(|suffix| item.ends_with(suffix)) {
+            16
+        } else {
+            64
+        }
+    }
+
+    /// The armor slot the item is worn in, feet 0 to head 3
+    pub fn armor_slot(&self) -> Option<usize> {
+        let item = self.item.as_str();
+        if item.ends_with("_boots") {
+            Some(0)
+        } else if item.ends_with("_leggings") {
+            Some(1)
+        } else if item.ends_with("_chestplate") || item == "elytra" {
+            Some(2)
+        } else if item.ends_with("_helmet") {
+            Some(3)
+        } else {
+            None
+        }
+    }
+
+    pub fn can_merge(&self, other: &ItemStack) -> bool {
+        self.item == other.item && self.damage == other.damage && self.tag == other.tag
+    }
+
+    /// Moves as much of `other` onto this stack as fits, returns the rest of it
+    pub fn merge(&mut self, other: ItemStack) -> Option<ItemStack> {
+        if !self.can_merge(&other) {
+            return Some(other);
+        }
+
+        let mut other = other;
+        let moved = other.count.min(self.max_stack_size().saturating_sub(self.count));
+        self.count += moved;
+        other.count -= moved;
+
+        if other.is_empty() { None } else { Some(other) }
+    }
+
+    /// Takes up to `count` items off into a stack of their own, this one is left empty
+    /// when it had no more
+    pub fn split(&mut self, count: u32) -> ItemStack {
+        let count = count.min(self.count);
+        self.count -= count;
+
+        ItemStack {
+            count,
+            ..self.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merging_fills_up_to_the_stack_size() {
+        let mut stack = ItemStack::new("dirt", 50);
+
+        assert_eq!(stack.merge(ItemStack::new("minecraft:dirt", 10)), None);
+        assert_eq!(stack.count, 60);
+
+        let rest = stack.merge(ItemStack::new("dirt", 10)).unwrap();
+        assert_eq!(stack.count, 64);
+        assert_eq!(rest.count, 6);
+    }
+
+    #[test]
+    fn different_items_damage_or_tags_do_not_merge() {
+        let mut stack = ItemStack::new("dirt", 1);
+        assert_eq!(stack.merge(ItemStack::new("stone", 1)).map(|rest| rest.count), Some(1));
+
+        let mut sword = ItemStack::new("iron_sword", 1);
+        let mut damaged = ItemStack::new("iron_sword", 1);
+        damaged.damage = 10;
+        assert!(!sword.can_merge(&damaged));
+
+        let mut named = ItemStack::new("dirt", 1);
+        named.tag.insert("Name".to_string(), TagValue::String("Soil".to_string()));
+        assert!(!stack.can_merge(&named));
+        assert_eq!(stack.count, 1);
+
+        // unstackable items stay single even when they are equal
+        assert_eq!(sword.merge(ItemStack::new("iron_sword", 1)).map(|rest| rest.count), Some(1));
+    }
+
+    #[test]
+    fn splitting_keeps_the_item_and_its_data() {
+        let mut stack = ItemStack::new("snowball", 16);
+        stack.tag.insert("Charged".to_string(), TagValue::Int(1));
+
+        let half = stack.split(5);
+        assert_eq!(half.count, 5);
+        assert_eq!(stack.count, 11);
+        assert!(half.can_merge(&stack));
+
+        let rest = stack.split(100);
+        assert_eq!(rest.count, 11);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn split_stacks_merge_back() {
+        let mut stack = ItemStack::new("bucket", 16);
+        let taken = stack.split(7);
+
+        assert_eq!(stack.merge(taken), None);
+        assert_eq!(stack.count, 16);
+        assert_eq!(stack.max_stack_size(), 16);
+    }
+}
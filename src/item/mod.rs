@@ -0,0 +1,4 @@
+pub mod tag;
+pub mod item_stack;
+pub mod inventory;
+pub mod item_model;
@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+/// Extra data an item carries, shaped like vanilla NBT without its many number widths
+#[derive(Clone, Debug, PartialEq)]
+pub enum TagValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+    List(Vec<TagValue>),
+    Compound(BTreeMap<String, TagValue>)
+}
+
+impl TagValue {
+    /// Parses the value at the start of `text` in vanilla's text notation (SNBT), such as
+    /// `{display:{Name:"Sword"},Unbreakable:1b}`, and returns it with the text after it
+    pub fn parse(text: &str) -> Result<(TagValue, &str), String> {
+        let text = text.trim_start();
+        match text.chars().next() {
+            Some('{') => {
+                let mut compound = BTreeMap::new();
+                let mut rest = &text[1..];
+                if let Some(after) = rest.trim_start().strip_prefix('}') {
+                    return Ok((TagValue::Compound(compound), after));
+                }
+                loop {
+                    let (key, after) = parse_word(rest)?;
+                    let after = after.trim_start().strip_prefix(':')
+                        .ok_or(format!("Expected ':' after '{}'", key))?;
+                    let (value, after) = TagValue::parse(after)?;
+                    compound.insert(key, value);
+                    rest = after.trim_start();
+
+                    match rest.chars().next() {
+                        Some(',') => rest = &rest[1..],
+                        Some('}') => return Ok((TagValue::Compound(compound), &rest[1..])),
+                        _ => return Err("Expected ',' or '}' in compound".to_string())
+                    }
+                }
+            }
+            Some('[') => {
+                let mut list = vec![];
+                let mut rest = &text[1..];
+                if let Some(after) = rest.trim_start().strip_prefix(']') {
+                    return Ok((TagValue::List(list), after));
+                }
+                loop {
+                    let (value, after) = TagValue::parse(rest)?;
+                    list.push(value);
+                    rest = after.trim_start();
+
+                    match rest.chars().next() {
+                        Some(',') => rest = &rest[1..],
+                        Some(']') => return Ok((TagValue::List(list), &rest[1..])),
+                        _ => return Err("Expected ',' or ']' in list".to_string())
+                    }
+                }
+            }
+            Some(_) => {
+                let is_quoted = text.starts_with('"');
+                let (word, rest) = parse_word(text)?;
+                let value = if is_quoted { TagValue::String(word) } else { parse_number(word) };
+                Ok((value, rest))
+            }
+            None => Err("Expected a value".to_string())
+        }
+    }
+}
+
+/// A quoted string or a run of characters allowed in unquoted keys and values
+fn parse_word(text: &str) -> Result<(String, &str), String> {
+    let text = text.trim_start();
+    if let Some(quoted) = text.strip_prefix('"') {
+        let mut word = String::new();
+        let mut characters = quoted.char_indices();
+        while let Some((i, character)) = characters.next() {
+            match character {
+                '"' => return Ok((word, &quoted[i + 1..])),
+                '\\' => word.extend(characters.next().map(|(_, escaped)| escaped)),
+                _ => word.push(character)
+            }
+        }
+        return Err("Unterminated string".to_string());
+    }
+
+    let end = text
+        .find(|character: char| !(character.is_ascii_alphanumeric() || "_-.+".contains(character)))
+        .unwrap_or(text.len());
+    if end == 0 {
+        return Err(format!("Unexpected '{}'", text.chars().next().unwrap_or(' ')));
+    }
+    Ok((text[..end].to_string(), &text[end..]))
+}
+
+/// Numbers may end in a type suffix like `1b` or `0.5f`, anything else unquoted is a string
+fn parse_number(word: String) -> TagValue {
+    let digits = word.trim_end_matches(|character: char| "bBsSlLfFdD".contains(character));
+    let is_float = word.contains('.') || word.ends_with(|character: char| "fFdD".contains(character));
+    // keeps words like `inf` strings
+    if !word.starts_with(|character: char| character.is_ascii_digit() || "-+.".contains(character)) {
+        return TagValue::String(word);
+    }
+
+    match (is_float, digits.parse::<i64>(), digits.parse::<f64>()) {
+        (false, Ok(value), _) => TagValue::Int(value),
+        (_, _, Ok(value)) => TagValue::Float(value),
+        _ => TagValue::String(word)
+    }
+}
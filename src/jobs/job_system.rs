@@ -0,0 +1,148 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::world::chunk::ChunkPos;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    Generate,
+    Mesh
+}
+
+struct Job {
+    kind: JobKind,
+    position: ChunkPos,
+    priority: u32,
+    run: Box<dyn FnOnce() + Send>
+}
+
+struct JobQueue {
+    jobs: Vec<Job>,
+    is_shutdown: bool
+}
+
+struct Shared {
+    queue: Mutex<JobQueue>,
+    job_available: Condvar
+}
+
+/// Worker threads for chunk work that would otherwise stall a frame. Jobs hand their
+/// result back through a channel of whoever submitted them. At most one job per kind
+/// and chunk is queued, jobs with the lowest priority value run first.
+pub struct JobSystem {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>
+}
+
+impl JobSystem {
+    /// One worker per core, leaving one for the event loop
+    pub fn new() -> JobSystem {
+        let worker_count = thread::available_parallelism()
+            .map(|count| count.get().saturating_sub(1))
+            .unwrap_or(1)
+            .max(1);
+
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(JobQueue { jobs: vec![], is_shutdown: false }),
+            job_available: Condvar::new()
+        });
+
+        let workers = (0..worker_count)
+            .map(|i| {
+                let shared = Arc::clone(&shared);
+                thread::Builder::new()
+                    .name(format!("chunk worker {}", i))
+                    .spawn(move || JobSystem::work(&shared))
+                    .expect("Failed to spawn chunk worker thread")
+            })
+            .collect();
+
+        JobSystem { shared, workers }
+    }
+
+    fn work(shared: &Shared) {
+        loop {
+            let job = {
+                let mut queue = shared.queue.lock().unwrap();
+                while queue.jobs.is_empty() && !queue.is_shutdown {
+                    queue = shared.job_available.wait(queue).unwrap();
+                }
+                if queue.is_shutdown {
+                    return;
+                }
+
+                let next = queue.jobs
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, job)| job.priority)
+                    .map(|(i, _)| i)
+                    .unwrap();
+                queue.jobs.swap_remove(next)
+            };
+
+            (job.run)();
+        }
+    }
+
+    /// Queues a job, replacing the queued job of the same kind for the same chunk
+    pub fn submit<F>(&self, kind: JobKind, position: ChunkPos, priority: u32, run: F)
+    where F: FnOnce() + Send + 'static {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.retain(|job| job.kind != kind || job.position != position);
+        queue.jobs.push(Job {
+            kind,
+            position,
+            priority,
+            run: Box::new(run)
+        });
+        drop(queue);
+
+        self.shared.job_available.notify_one();
+    }
+
+    /// Drops the queued job of this kind for the chunk, a job that already runs still
+    /// delivers its result
+    pub fn cancel(&self, kind: JobKind, position: ChunkPos) {
+        let mut queue = self.shared.queue.lock().unwrap();
+        queue.jobs.retain(|job| job.kind != kind || job.position != position);
+    }
+
+    /// Gives every queued job of a kind a new priority, jobs given None are dropped.
+    /// Returns the chunks of the dropped jobs.
+    pub fn reprioritise<F>(&self, kind: JobKind, priority_of: F) -> Vec<ChunkPos>
+    where F: Fn(ChunkPos) -> Option<u32> {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let mut cancelled = vec![];
+
+        queue.jobs.retain_mut(|job| {
+            if job.kind != kind {
+                return true;
+            }
+            match priority_of(job.position) {
+                Some(priority) => {
+                    job.priority = priority;
+                    true
+                }
+                None => {
+                    cancelled.push(job.position);
+                    false
+                }
+            }
+        });
+
+        cancelled
+    }
+}
+
+impl Drop for JobSystem {
+    /// Queued jobs are dropped, running ones are finished
+    fn drop(&mut self) {
+        self.shared.queue.lock().unwrap().is_shutdown = true;
+        self.shared.job_available.notify_all();
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
@@ -6,16 +6,48 @@ extern crate winapi;
 extern crate serde;
 extern crate bincode;
 extern crate cgmath;
+extern crate flate2;
+extern crate serde_json;
 
 mod vulkan_engine;
 mod settings_loader;
+mod world;
+mod commands;
+mod jobs;
+mod game_loop;
+mod entity;
+mod item;
 
 use winit::event_loop::EventLoop;
 use settings_loader::key_mappings::KeyMappings;
+use settings_loader::graphics_settings::GraphicsSettings;
 use vulkan_engine::vulkan_engine::VulkanEngine;
 
 
 fn main(){
+    if std::env::args().any(|arg| arg == "--list-gpus") {
+        if let Err(error) = VulkanEngine::list_gpus() {
+            eprintln!("Unable to list GPUs: {}", error);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // --headless [ticks] simulates the world without a window
+    let arguments: Vec<String> = std::env::args().collect();
+    if let Some(i) = arguments.iter().position(|arg| arg == "--headless") {
+        let tick_limit = match arguments.get(i + 1).map(|ticks| ticks.parse::<u64>()) {
+            None => None,
+            Some(Ok(ticks)) => Some(ticks),
+            Some(Err(_)) => {
+                eprintln!("Usage: --headless [ticks]");
+                std::process::exit(1);
+            }
+        };
+        game_loop::headless::run(tick_limit);
+        return;
+    }
+
     let keymappings = match KeyMappings::read_from_file(){
         None => {
             let default = KeyMappings::default();
@@ -25,8 +57,16 @@ fn main(){
         Some(keymappings) => keymappings
     };
 
+    let graphics_settings = GraphicsSettings::load();
+
 
     let event_loop = EventLoop::new();
 
-    VulkanEngine::new(&event_loop).run(event_loop, keymappings);
+    match VulkanEngine::new(&event_loop, &graphics_settings) {
+        Ok(engine) => engine.run(event_loop, keymappings),
+        Err(error) => {
+            eprintln!("Unable to start: {}", error);
+            std::process::exit(1);
+        }
+    }
 }
\ No newline at end of file
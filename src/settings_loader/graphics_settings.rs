@@ -0,0 +1,91 @@
+use bincode;
+use serde::{Serialize, Deserialize};
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use crate::settings_loader::settings_directory;
+
+const FILE_NAME: &str = "graphics.json";
+// written with bincode before the settings were JSON
+const OLD_FILE_NAME: &str = "graphics.dat";
+
+/// Stored as JSON so it can be edited by hand. Fields missing from the file, like ones
+/// added after it was written, keep their defaults.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings{
+    // radius in blocks that grass and foliage colours are averaged over, 0 disables blending
+    pub biome_blend_radius : u32,
+    // averaged corner light with ambient occlusion, flat per face lighting when off
+    pub smooth_lighting : bool,
+    // GPU index or part of its name as shown by --list-gpus, None picks the best one
+    pub gpu : Option<String>,
+    // chunks drawn at full detail around the camera
+    pub render_distance : u32,
+    // outer chunk distance of each level of detail band, band i merges 2^(i + 1) blocks.
+    // Chunks are loaded up to the last band
+    pub lod_distances : Vec<u32>
+}
+
+impl Default for GraphicsSettings{
+    fn default() -> GraphicsSettings{
+        GraphicsSettings{
+            biome_blend_radius: 2,
+            smooth_lighting: true,
+            gpu: None,
+            render_distance: 8,
+            lod_distances: vec![12, 16, 24]
+        }
+    }
+}
+
+impl GraphicsSettings{
+    fn path() -> PathBuf {
+        settings_directory().join(FILE_NAME)
+    }
+
+    /// The settings in the file, which is created from the old `graphics.dat` or with the
+    /// defaults when there is none.
+    /// A file that can't be read is reported and left alone for the user to fix.
+    pub fn load() -> GraphicsSettings {
+        let path = GraphicsSettings::path();
+
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => {
+                let settings = GraphicsSettings::read_old_file().unwrap_or_default();
+                if let Err(error) = settings.write_to_file() {
+                    println!("Unable to write {:?}: {}", path, error);
+                }
+                return settings;
+            }
+            Err(error) => {
+                println!("Unable to read {:?}, using the defaults: {}", path, error);
+                return GraphicsSettings::default();
+            }
+        };
+
+        match serde_json::from_slice(&content) {
+            Ok(settings) => settings,
+            Err(error) => {
+                println!("Unable to read {:?}, using the defaults: {}", path, error);
+                GraphicsSettings::default()
+            }
+        }
+    }
+
+    /// The binary file older versions wrote, None when it is missing or from a version
+    /// with other fields
+    fn read_old_file() -> Option<GraphicsSettings> {
+        let content = fs::read(settings_directory().join(OLD_FILE_NAME)).ok()?;
+        let settings = bincode::deserialize(&content[..]).ok()?;
+        println!("Converted the graphics settings in {} to {}", OLD_FILE_NAME, FILE_NAME);
+        Some(settings)
+    }
+
+    pub fn write_to_file(&self) -> std::io::Result<()> {
+        let content = serde_json::to_string_pretty(&self)?;
+        fs::write(GraphicsSettings::path(), content)
+    }
+}
@@ -6,7 +6,8 @@ use std::{fs};
 use std::path::{Path};
 use std::fs::{File, OpenOptions};
 use std::io::Write;
-use std::env;
+use crate::settings_loader::settings_directory;
+use crate::item::inventory::HOTBAR_SLOTS;
 
 #[derive(Serialize, Deserialize)]
 pub struct KeyMappings{
@@ -14,7 +15,14 @@ pub struct KeyMappings{
     pub left : VirtualKeyCode,
     pub backward : VirtualKeyCode,
     pub right : VirtualKeyCode,
-    pub menu : VirtualKeyCode
+    pub menu : VirtualKeyCode,
+    pub pause : VirtualKeyCode,
+    pub jump : VirtualKeyCode,
+    pub sprint : VirtualKeyCode,
+    pub sneak : VirtualKeyCode,
+    pub swap_offhand : VirtualKeyCode,
+    // selects the hotbar slot of the same index
+    pub hotbar : [VirtualKeyCode; HOTBAR_SLOTS]
 }
 
 impl KeyMappings{
@@ -25,21 +33,22 @@ impl KeyMappings{
             backward: VirtualKeyCode::S,
             right: VirtualKeyCode::D,
             menu: VirtualKeyCode::Escape,
+            pause: VirtualKeyCode::P,
+            jump: VirtualKeyCode::Space,
+            sprint: VirtualKeyCode::LControl,
+            sneak: VirtualKeyCode::LShift,
+            swap_offhand: VirtualKeyCode::F,
+            hotbar: [
+                VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3,
+                VirtualKeyCode::Key4, VirtualKeyCode::Key5, VirtualKeyCode::Key6,
+                VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9
+            ],
         }
     }
-    //TODO: implement for other os' than windows
     pub fn write_to_file(&self){
         let mut file : File;
 
-        let home = env::var("USERPROFILE")
-            .expect("Home environment variable not found");
-
-        let dir = home + "/rustcraft";
-        if !Path::new(&dir).exists(){
-            fs::create_dir(&dir)
-                .expect("Unable to create directory");
-        }
-        let path = dir + "/settings.dat";
+        let path = settings_directory().join("settings.dat");
 
         if !Path::new(&path).exists(){
             file = File::create(path)
@@ -55,11 +64,9 @@ impl KeyMappings{
             .expect("Could not write to file")
     }
 
+    /// None when there is no file yet or it was written for other key mappings
     pub fn read_from_file() -> Option<KeyMappings> {
-        let home = env::var("USERPROFILE")
-            .expect("Home environment variable not found");
-
-        let path = home + "/rustcraft/settings.dat";
+        let path = settings_directory().join("settings.dat");
 
         if !Path::new(&path).exists(){
             return None
@@ -68,6 +75,6 @@ impl KeyMappings{
         let content = fs::read(path)
             .expect("unable to read keymappings");
 
-        Some(bincode::deserialize(&content[..]).unwrap())
+        bincode::deserialize(&content[..]).ok()
     }
 }
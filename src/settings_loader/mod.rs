@@ -1 +1,20 @@
-pub mod key_mappings;
\ No newline at end of file
+pub mod key_mappings;
+pub mod graphics_settings;
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory that holds every settings file, created on first use
+pub fn settings_directory() -> PathBuf {
+    let home = env::var("USERPROFILE")
+        .or_else(|_| env::var("HOME"))
+        .expect("Home environment variable not found");
+
+    let dir = Path::new(&home).join("rustcraft");
+    if !dir.exists(){
+        fs::create_dir(&dir)
+            .expect("Unable to create directory");
+    }
+    dir
+}
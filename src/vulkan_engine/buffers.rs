@@ -1,11 +1,16 @@
 use ash::vk;
 use std::ptr;
 use ash::version::DeviceV1_0;
+use crate::vulkan_engine::utilities::constants::MAX_FRAMES_IN_FLIGHT;
 use crate::vulkan_engine::utilities::structures::QueueFamilyIndices;
+use crate::vulkan_engine::draw_list::DrawList;
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage, ResourceLayout};
 
 pub struct Buffers {
     pub framebuffers: Vec<vk::Framebuffer>,
-    pub command_pool: vk::CommandPool,
+    // one pool and command buffer per frame in flight, reset before recording
+    pub command_pools: Vec<vk::CommandPool>,
     pub command_buffers: Vec<vk::CommandBuffer>,
     _device: ash::Device
 }
@@ -15,34 +20,39 @@ impl Buffers{
         device: &ash::Device,
         render_pass: vk::RenderPass,
         image_views: &Vec<vk::ImageView>,
+        depth_image_view: vk::ImageView,
         swapchain_extent: vk::Extent2D,
-        queue_families: &QueueFamilyIndices,
-        graphics_pipeline: vk::Pipeline
-    ) -> Buffers{
-        let framebuffers = Buffers::create_frame_buffers(device, render_pass, image_views, &swapchain_extent);
-
-        let command_pool = Buffers::create_command_pool(device, queue_families);
+        queue_families: &QueueFamilyIndices
+    ) -> EngineResult<Buffers>{
+        let framebuffers = Buffers::create_frame_buffers(device, render_pass, image_views, depth_image_view, &swapchain_extent)?;
 
-        let command_buffers = Buffers::create_command_buffers(device, command_pool, graphics_pipeline, &framebuffers, render_pass, swapchain_extent);
+        let mut command_pools = vec![];
+        let mut command_buffers = vec![];
+        for _ in 0..MAX_FRAMES_IN_FLIGHT {
+            let command_pool = Buffers::create_command_pool(device, queue_families)?;
+            command_buffers.push(Buffers::allocate_command_buffer(device, command_pool)?);
+            command_pools.push(command_pool);
+        }
 
-        Buffers{
+        Ok(Buffers{
             framebuffers,
+            command_pools,
             command_buffers,
-            command_pool,
             _device: device.clone()
-        }
+        })
     }
 
     pub fn create_frame_buffers(
         device: &ash::Device,
         render_pass: vk::RenderPass,
         image_views: &Vec<vk::ImageView>,
+        depth_image_view: vk::ImageView,
         swapchain_extent: &vk::Extent2D
-    ) -> Vec<vk::Framebuffer>{
+    ) -> EngineResult<Vec<vk::Framebuffer>>{
         let mut framebuffers = vec![];
 
         for &image_view in image_views.iter(){
-            let attachments = [image_view];
+            let attachments = [image_view, depth_image_view];
 
             let framebuffer_create_info = vk::FramebufferCreateInfo{
                 s_type: vk::StructureType::FRAMEBUFFER_CREATE_INFO,
@@ -57,111 +67,200 @@ impl Buffers{
             };
 
             let framebuffer = unsafe{
-                device
-                    .create_framebuffer(&framebuffer_create_info, None)
-                    .expect("Unable to create framebuffer!")
+                device.create_framebuffer(&framebuffer_create_info, None)?
             };
 
             framebuffers.push(framebuffer)
         }
 
-        framebuffers
+        Ok(framebuffers)
     }
 
-    pub fn create_command_buffers(
+    /// Resets the frame's command pool and records the draw list into a render pass
+    /// on the given framebuffer
+    pub fn record_command_buffer(
         device: &ash::Device,
         command_pool: vk::CommandPool,
-        graphics_pipeline: vk::Pipeline,
-        framebuffers: &Vec<vk::Framebuffer>,
+        command_buffer: vk::CommandBuffer,
+        framebuffer: vk::Framebuffer,
         render_pass: vk::RenderPass,
-        surface_extent: vk::Extent2D
-    ) -> Vec<vk::CommandBuffer> {
-        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
-            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+        surface_extent: vk::Extent2D,
+        draw_list: &DrawList
+    ) -> EngineResult<()> {
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
             p_next: ptr::null(),
-            command_buffer_count: framebuffers.len() as u32,
-            command_pool,
-            level: vk::CommandBufferLevel::PRIMARY,
-        };
-
-        let command_buffers = unsafe {
-            device
-                .allocate_command_buffers(&command_buffer_allocate_info)
-                .expect("Failed to allocate Command Buffers!")
+            p_inheritance_info: ptr::null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
         };
 
-        for (i, &command_buffer) in command_buffers.iter().enumerate() {
-            let command_buffer_begin_info = vk::CommandBufferBeginInfo {
-                s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
-                p_next: ptr::null(),
-                p_inheritance_info: ptr::null(),
-                flags: vk::CommandBufferUsageFlags::SIMULTANEOUS_USE,
-            };
-
-            unsafe {
-                device
-                    .begin_command_buffer(command_buffer, &command_buffer_begin_info)
-                    .expect("Failed to begin recording Command Buffer at beginning!");
-            }
+        unsafe {
+            device.reset_command_pool(command_pool, vk::CommandPoolResetFlags::empty())?;
+            device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
 
-            let clear_values = [vk::ClearValue {
+        let clear_values = [
+            vk::ClearValue {
                 color: vk::ClearColorValue {
                     float32: [0.0, 0.0, 0.0, 1.0],
                 },
-            }];
-
-            let render_pass_begin_info = vk::RenderPassBeginInfo {
-                s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
-                p_next: ptr::null(),
-                render_pass,
-                framebuffer: framebuffers[i],
-                render_area: vk::Rect2D {
-                    offset: vk::Offset2D { x: 0, y: 0 },
-                    extent: surface_extent,
+            },
+            vk::ClearValue {
+                depth_stencil: vk::ClearDepthStencilValue {
+                    depth: 1.0,
+                    stencil: 0,
                 },
-                clear_value_count: clear_values.len() as u32,
-                p_clear_values: clear_values.as_ptr(),
-            };
+            },
+        ];
+
+        let render_pass_begin_info = vk::RenderPassBeginInfo {
+            s_type: vk::StructureType::RENDER_PASS_BEGIN_INFO,
+            p_next: ptr::null(),
+            render_pass,
+            framebuffer,
+            render_area: vk::Rect2D {
+                offset: vk::Offset2D { x: 0, y: 0 },
+                extent: surface_extent,
+            },
+            clear_value_count: clear_values.len() as u32,
+            p_clear_values: clear_values.as_ptr(),
+        };
+
+        let viewports = [vk::Viewport {
+            x: 0.0,
+            y: 0.0,
+            width: surface_extent.width as f32,
+            height: surface_extent.height as f32,
+            min_depth: 0.0,
+            max_depth: 1.0,
+        }];
+        let scissors = [vk::Rect2D {
+            offset: vk::Offset2D { x: 0, y: 0 },
+            extent: surface_extent,
+        }];
 
-            unsafe {
-                device.cmd_begin_render_pass(
-                    command_buffer,
-                    &render_pass_begin_info,
-                    vk::SubpassContents::INLINE,
-                );
-                device.cmd_bind_pipeline(
-                    command_buffer,
-                    vk::PipelineBindPoint::GRAPHICS,
-                    graphics_pipeline,
-                );
-                device.cmd_draw(command_buffer, 3, 1, 0, 0);
-
-                device.cmd_end_render_pass(command_buffer);
-
-                device
-                    .end_command_buffer(command_buffer)
-                    .expect("Failed to record Command Buffer at Ending!");
-            }
+        unsafe {
+            device.cmd_begin_render_pass(
+                command_buffer,
+                &render_pass_begin_info,
+                vk::SubpassContents::INLINE,
+            );
+            // every pipeline takes its viewport and scissor from here
+            device.cmd_set_viewport(command_buffer, 0, &viewports);
+            device.cmd_set_scissor(command_buffer, 0, &scissors);
         }
 
-        command_buffers
+        draw_list.record(device, command_buffer);
+
+        unsafe {
+            device.cmd_end_render_pass(command_buffer);
+
+            device.end_command_buffer(command_buffer)?;
+        }
+
+        Ok(())
+    }
+
+    fn allocate_command_buffer(device: &ash::Device, command_pool: vk::CommandPool) -> EngineResult<vk::CommandBuffer> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            command_buffer_count: 1,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+        };
+
+        let command_buffers = unsafe {
+            device.allocate_command_buffers(&command_buffer_allocate_info)?
+        };
+
+        Ok(command_buffers[0])
     }
 
     fn create_command_pool(
         device: &ash::Device,
         queue_families: &QueueFamilyIndices
-    ) -> vk::CommandPool{
+    ) -> EngineResult<vk::CommandPool>{
         let command_pool_create_info = vk::CommandPoolCreateInfo{
             s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
             p_next: ptr::null(),
-            flags: vk::CommandPoolCreateFlags::empty(),
+            flags: vk::CommandPoolCreateFlags::TRANSIENT,
             queue_family_index: queue_families.graphics_family.unwrap()
         };
 
-        unsafe{
-            device
-                .create_command_pool(&command_pool_create_info, None)
-                .expect("Failed to create command pool")
+        let command_pool = unsafe{
+            device.create_command_pool(&command_pool_create_info, None)?
+        };
+
+        Ok(command_pool)
+    }
+
+    pub fn create_buffer(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        size: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        memory_usage: MemoryUsage
+    ) -> EngineResult<(vk::Buffer, Allocation)> {
+        let buffer_create_info = vk::BufferCreateInfo {
+            s_type: vk::StructureType::BUFFER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::BufferCreateFlags::empty(),
+            size,
+            usage,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+        };
+
+        let buffer = unsafe {
+            device.create_buffer(&buffer_create_info, None)?
+        };
+
+        let memory_requirements = unsafe { device.get_buffer_memory_requirements(buffer) };
+        let allocation = allocator.allocate(memory_requirements, memory_usage, ResourceLayout::Linear)?;
+
+        unsafe {
+            device.bind_buffer_memory(buffer, allocation.memory, allocation.offset)?;
+        }
+
+        Ok((buffer, allocation))
+    }
+
+    pub fn destroy_buffer(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        buffer: vk::Buffer,
+        allocation: &Allocation
+    ) {
+        unsafe {
+            device.destroy_buffer(buffer, None);
         }
+        allocator.free(allocation);
     }
-}
\ No newline at end of file
+
+    pub fn create_uniform_buffers<T>(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        count: usize
+    ) -> EngineResult<(Vec<vk::Buffer>, Vec<Allocation>)> {
+        let buffer_size = std::mem::size_of::<T>();
+
+        let mut uniform_buffers = vec![];
+        let mut uniform_buffer_allocations = vec![];
+
+        for _ in 0..count {
+            let (uniform_buffer, uniform_buffer_allocation) = Buffers::create_buffer(
+                device,
+                allocator,
+                buffer_size as vk::DeviceSize,
+                vk::BufferUsageFlags::UNIFORM_BUFFER,
+                MemoryUsage::CpuToGpu
+            )?;
+            uniform_buffers.push(uniform_buffer);
+            uniform_buffer_allocations.push(uniform_buffer_allocation);
+        }
+
+        Ok((uniform_buffers, uniform_buffer_allocations))
+    }
+}
@@ -0,0 +1,38 @@
+use cgmath::{Deg, Matrix4, Point3, Vector3, InnerSpace};
+
+pub struct Camera {
+    pub position: Point3<f32>,
+    // degrees, yaw 0 looks down -z
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov: f32,
+    pub aspect: f32
+}
+
+impl Camera {
+    pub fn new(position: Point3<f32>, aspect: f32) -> Camera {
+        Camera {
+            position,
+            yaw: 0.0,
+            pitch: -15.0,
+            fov: 70.0,
+            aspect
+        }
+    }
+
+    pub fn forward(&self) -> Vector3<f32> {
+        let (yaw, pitch) = (self.yaw.to_radians(), self.pitch.to_radians());
+        Vector3::new(yaw.sin() * pitch.cos(), pitch.sin(), -yaw.cos() * pitch.cos()).normalize()
+    }
+
+    pub fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_dir(self.position, self.forward(), Vector3::unit_y())
+    }
+
+    pub fn projection_matrix(&self) -> Matrix4<f32> {
+        let mut projection = cgmath::perspective(Deg(self.fov), self.aspect, 0.1, 1000.0);
+        // vulkan clip space has y pointing down
+        projection[1][1] *= -1.0;
+        projection
+    }
+}
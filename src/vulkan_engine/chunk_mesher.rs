@@ -0,0 +1,777 @@
+use cgmath::{MetricSpace, Point3};
+
+use crate::vulkan_engine::texture_atlas::TextureAtlas;
+use crate::vulkan_engine::utilities::structures::ChunkVertex;
+use crate::world::biome::Biome;
+use crate::world::block::{Block, Face, RenderLayer};
+use crate::world::chunk::{Chunk, CHUNK_HEIGHT, CHUNK_SIZE, SECTION_HEIGHT, SECTIONS_PER_CHUNK};
+use crate::world::colormap::{BiomeColors, Tint};
+use crate::world::fluid::{self, FALLING};
+use crate::world::lighting::{LightChannel, MAX_LIGHT};
+use crate::world::block_view::BlockView;
+
+// opaque, cutout and translucent, see `RenderLayer`
+pub const LAYER_COUNT: usize = 3;
+
+/// Every texture the mesher can reference, packed into the block atlas
+pub const BLOCK_TEXTURES: [&str; 24] = [
+    "stone",
+    "dirt",
+    "grass_block_top",
+    "grass_block_side",
+    "grass_block_side_overlay",
+    "grass_block_snow",
+    "sand",
+    "gravel",
+    "snow",
+    "water_still",
+    "water_flow",
+    "lava_still",
+    "lava_flow",
+    "bedrock",
+    "oak_log",
+    "oak_log_top",
+    "oak_leaves",
+    "vine",
+    "torch",
+    "furnace_front",
+    "furnace_side",
+    "furnace_top",
+    "cobblestone",
+    "obsidian"
+];
+
+// fluid surfaces sloping less than this are drawn still
+const FLAT_SLOPE: f32 = 0.01;
+
+/// Blocks drawn as side panels pulled into the block instead of a full cube: vines hug
+/// the side of their block, torches are a thin stick in the middle
+fn side_inset(block: Block) -> Option<f32> {
+    match block {
+        Block::Vine => Some(1.0 / 16.0),
+        Block::Torch => Some(7.0 / 16.0),
+        _ => None
+    }
+}
+
+/// Fixed per face darkening so the sides of a block stand apart even in flat light
+fn face_shade(face: Face) -> f32 {
+    match face {
+        Face::Up => 1.0,
+        Face::Down => 0.5,
+        Face::North | Face::South => 0.8,
+        Face::East | Face::West => 0.6
+    }
+}
+
+/// Corners of a unit cube face, counter clockwise seen from outside, starting bottom left
+fn face_corners(face: Face) -> [[f32; 3]; 4] {
+    match face {
+        Face::Up => [[0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0]],
+        Face::Down => [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]],
+        Face::North => [[1.0, 0.0, 0.0], [0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0]],
+        Face::South => [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]],
+        Face::East => [[1.0, 0.0, 1.0], [1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0]],
+        Face::West => [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0]]
+    }
+}
+
+// corner brightness by how open it is, 0 when enclosed by two solid sides
+const AMBIENT_OCCLUSION: [f32; 4] = [0.5, 0.7, 0.85, 1.0];
+
+/// The two blocks beside a corner within the layer in front of a face, as offsets from
+/// the block in front
+fn corner_sides(face: Face, corner: [f32; 3]) -> [(i32, i32, i32); 2] {
+    let normal = face.offset();
+    let normal = [normal.0, normal.1, normal.2];
+
+    let mut sides = [(0, 0, 0); 2];
+    let mut side = 0;
+    for axis in 0..3 {
+        if normal[axis] != 0 {
+            continue;
+        }
+        let mut offset = [0; 3];
+        offset[axis] = if corner[axis] > 0.5 { 1 } else { -1 };
+        sides[side] = (offset[0], offset[1], offset[2]);
+        side += 1;
+    }
+    sides
+}
+
+/// Texture layers drawn on a face, later layers are drawn over earlier ones. Logs lie
+/// along their facing, upright without one, furnaces face north without one.
+fn face_textures(block: Block, face: Face, facing: Option<Face>, above: Block) -> Vec<(&'static str, Tint)> {
+    match (block, face) {
+        (Block::Grass, Face::Up) => vec![("grass_block_top", Tint::Grass)],
+        (Block::Grass, Face::Down) => vec![("dirt", Tint::None)],
+        (Block::Grass, _) if above == Block::Snow => vec![("grass_block_snow", Tint::None)],
+        // the side texture is the dirt part, the overlay carries the tinted grass fringe
+        (Block::Grass, _) => vec![
+            ("grass_block_side", Tint::None),
+            ("grass_block_side_overlay", Tint::Grass)
+        ],
+        (Block::OakLog, _) if face.is_same_axis(facing.unwrap_or(Face::Up)) => vec![("oak_log_top", Tint::None)],
+        (Block::OakLog, _) => vec![("oak_log", Tint::None)],
+        (Block::Furnace, _) if face == facing.unwrap_or(Face::North) => vec![("furnace_front", Tint::None)],
+        (Block::Furnace, Face::Up) | (Block::Furnace, Face::Down) => vec![("furnace_top", Tint::None)],
+        (Block::Furnace, _) => vec![("furnace_side", Tint::None)],
+        (Block::OakLeaves, _) => vec![("oak_leaves", Tint::Foliage)],
+        (Block::Vine, _) => vec![("vine", Tint::Foliage)],
+        (Block::Torch, _) => vec![("torch", Tint::None)],
+        (Block::Water, _) => vec![("water_still", Tint::Water)],
+        (Block::Lava, _) => vec![("lava_still", Tint::None)],
+        (Block::Cobblestone, _) => vec![("cobblestone", Tint::None)],
+        (Block::Obsidian, _) => vec![("obsidian", Tint::None)],
+        (Block::Stone, _) => vec![("stone", Tint::None)],
+        (Block::Dirt, _) => vec![("dirt", Tint::None)],
+        (Block::Sand, _) => vec![("sand", Tint::None)],
+        (Block::Gravel, _) => vec![("gravel", Tint::None)],
+        (Block::Snow, _) => vec![("snow", Tint::None)],
+        (Block::Bedrock, _) => vec![("bedrock", Tint::None)],
+        (Block::Air, _) => vec![]
+    }
+}
+
+/// The top of a water or lava block, sloping between the heights at its four corners
+struct FluidSurface {
+    // by corner x, then corner z
+    heights: [[f32; 2]; 2]
+}
+
+impl FluidSurface {
+    fn height(&self, corner: [f32; 3]) -> f32 {
+        self.heights[corner[0] as usize][corner[2] as usize]
+    }
+
+    /// Which way the surface runs downhill along x and z, zero when flat
+    fn flow(&self) -> (f32, f32) {
+        let heights = &self.heights;
+        (
+            heights[0][0] + heights[0][1] - heights[1][0] - heights[1][1],
+            heights[0][0] + heights[1][0] - heights[0][1] - heights[1][1]
+        )
+    }
+
+    fn is_flat(&self) -> bool {
+        let (flow_x, flow_z) = self.flow();
+        flow_x.abs() < FLAT_SLOPE && flow_z.abs() < FLAT_SLOPE
+    }
+}
+
+/// Still textures on flat tops and on bottoms, flowing ones on slopes and sides
+fn fluid_texture(block: Block, face: Face, surface: &FluidSurface) -> (&'static str, Tint) {
+    let is_still = face == Face::Down || (face == Face::Up && surface.is_flat());
+    match (block, is_still) {
+        (Block::Lava, true) => ("lava_still", Tint::None),
+        (Block::Lava, false) => ("lava_flow", Tint::None),
+        (_, true) => ("water_still", Tint::Water),
+        (_, false) => ("water_flow", Tint::Water)
+    }
+}
+
+/// Lowers the upper corners of a fluid face to the surface. The flowing texture covers two
+/// blocks, so slopes show its middle turned downhill and sides its upper left quarter cut
+/// off at the surface.
+fn shape_fluid_quad(quad: &mut [ChunkVertex; 4], face: Face, surface: &FluidSurface, uv: [f32; 4]) {
+    let [u0, v0, u1, v1] = uv;
+    let (width, height) = (u1 - u0, v1 - v0);
+    let (flow_x, flow_z) = surface.flow();
+    let flow_length = (flow_x * flow_x + flow_z * flow_z).sqrt();
+
+    let corners = face_corners(face);
+    for (vertex, corner) in quad.iter_mut().zip(corners.iter()) {
+        let is_upper = corner[1] > 0.5;
+        let surface_height = if is_upper { surface.height(*corner) } else { 0.0 };
+        if is_upper {
+            vertex.pos[1] += surface_height - 1.0;
+        }
+
+        match face {
+            Face::Up if !surface.is_flat() => {
+                let (direction_x, direction_z) = (flow_x / flow_length, flow_z / flow_length);
+                let (offset_x, offset_z) = (corner[0] - 0.5, corner[2] - 0.5);
+                let across = (offset_z * direction_x - offset_x * direction_z) * 0.5;
+                let along = (offset_x * direction_x + offset_z * direction_z) * 0.5;
+                vertex.tex_coord = [u0 + (0.5 + across) * width, v0 + (0.5 + along) * height];
+            }
+            Face::Up | Face::Down => {}
+            _ => {
+                let u = u0 + (vertex.tex_coord[0] - u0) * 0.5;
+                let v = if is_upper { v0 + (1.0 - surface_height) * height * 0.5 } else { v0 + height * 0.5 };
+                vertex.tex_coord = [u, v];
+            }
+        }
+    }
+}
+
+/// Biome colours of the columns around a chunk, so corners can be averaged without
+/// resampling the colormaps for every vertex
+struct TintGrid {
+    // (CHUNK_SIZE + 2)^2 columns starting one block outside the chunk
+    grass: Vec<[f32; 3]>,
+    foliage: Vec<[f32; 3]>
+}
+
+const GRID_SIZE: usize = CHUNK_SIZE + 2;
+
+impl TintGrid {
+    fn new<W: BlockView>(world: &W, colors: &BiomeColors, origin_x: i32, origin_z: i32) -> TintGrid {
+        let mut grass = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+        let mut foliage = Vec::with_capacity(GRID_SIZE * GRID_SIZE);
+        for z in 0..GRID_SIZE as i32 {
+            for x in 0..GRID_SIZE as i32 {
+                let world_x = origin_x + x - 1;
+                let world_z = origin_z + z - 1;
+                grass.push(colors.blended_color(world, world_x, world_z, Tint::Grass));
+                foliage.push(colors.blended_color(world, world_x, world_z, Tint::Foliage));
+            }
+        }
+
+        TintGrid { grass, foliage }
+    }
+
+    /// Average of the four columns sharing the corner at chunk local (x, z)
+    fn corner_color(&self, colors: &BiomeColors, x: usize, z: usize, tint: Tint) -> [f32; 3] {
+        let grid = match tint {
+            Tint::Grass => &self.grass,
+            Tint::Foliage => &self.foliage,
+            _ => return colors.biome_color(Biome::Plains, tint)
+        };
+
+        let mut total = [0.0; 3];
+        for &(offset_x, offset_z) in [(0, 0), (1, 0), (0, 1), (1, 1)].iter() {
+            let color = grid[(z + offset_z) * GRID_SIZE + x + offset_x];
+            for i in 0..3 {
+                total[i] += color[i] * 0.25;
+            }
+        }
+        total
+    }
+}
+
+/// Blended quads of a chunk, kept so they can be sorted again whenever the camera moves
+pub struct TranslucentQuads {
+    // six per quad, ordered by section
+    pub indices: Vec<u32>,
+    pub centers: Vec<Point3<f32>>
+}
+
+impl TranslucentQuads {
+    /// The indices with the quads of every section ordered back to front as seen from `eye`.
+    /// `section_starts` are index offsets like those of `ChunkMeshData`.
+    pub fn sorted_indices(&self, section_starts: &[u32; SECTIONS_PER_CHUNK + 1], eye: Point3<f32>) -> Vec<u32> {
+        let distances: Vec<f32> = self.centers.iter().map(|center| center.distance2(eye)).collect();
+
+        let mut indices = Vec::with_capacity(self.indices.len());
+        for section in 0..SECTIONS_PER_CHUNK {
+            let first_quad = section_starts[section] as usize / 6;
+            let last_quad = section_starts[section + 1] as usize / 6;
+            let mut quads: Vec<usize> = (first_quad..last_quad).collect();
+            quads.sort_by(|&a, &b| distances[b].total_cmp(&distances[a]));
+            for quad in quads {
+                indices.extend_from_slice(&self.indices[quad * 6..quad * 6 + 6]);
+            }
+        }
+        indices
+    }
+}
+
+/// Indexed quads of a chunk split by render layer and ordered by section. `indices` holds
+/// the opaque then the cutout quads, the translucent ones have their own list. The indices
+/// of section `i` of a layer span `section_starts[layer][i]..section_starts[layer][i + 1]`
+/// of the layer's list.
+pub struct ChunkMeshData {
+    pub vertices: Vec<ChunkVertex>,
+    pub indices: Vec<u32>,
+    pub section_starts: [[u32; SECTIONS_PER_CHUNK + 1]; LAYER_COUNT],
+    pub translucent: TranslucentQuads
+}
+
+/// Collects the quads of a chunk into one list per render layer
+struct MeshBuilder {
+    vertices: Vec<ChunkVertex>,
+    layers: [Vec<u32>; LAYER_COUNT],
+    section_starts: [[u32; SECTIONS_PER_CHUNK + 1]; LAYER_COUNT],
+    translucent_centers: Vec<Point3<f32>>
+}
+
+impl MeshBuilder {
+    fn new() -> MeshBuilder {
+        MeshBuilder {
+            vertices: vec![],
+            layers: [vec![], vec![], vec![]],
+            section_starts: [[0; SECTIONS_PER_CHUNK + 1]; LAYER_COUNT],
+            translucent_centers: vec![]
+        }
+    }
+
+    fn start_section(&mut self, section: usize) {
+        for layer in 0..LAYER_COUNT {
+            self.section_starts[layer][section] = self.layers[layer].len() as u32;
+        }
+    }
+
+    /// `triangles` indexes the four corners, double sided quads are seen from behind as well
+    fn push_quad(&mut self, layer: RenderLayer, quad: &[ChunkVertex; 4], triangles: &[u32; 6], is_double_sided: bool) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(quad);
+
+        let indices = &mut self.layers[layer as usize];
+        indices.extend(triangles.iter().map(|&i| base + i));
+        if is_double_sided {
+            indices.extend(triangles.iter().rev().map(|&i| base + i));
+        }
+
+        if layer == RenderLayer::Translucent {
+            let center = quad.iter().fold([0.0; 3], |total, vertex| {
+                [total[0] + vertex.pos[0] / 4.0, total[1] + vertex.pos[1] / 4.0, total[2] + vertex.pos[2] / 4.0]
+            });
+            // a double sided quad is sorted as two quads at the same place
+            for _ in 0..if is_double_sided { 2 } else { 1 } {
+                self.translucent_centers.push(Point3::new(center[0], center[1], center[2]));
+            }
+        }
+    }
+
+    fn finish(self) -> ChunkMeshData {
+        let [opaque, cutout, translucent] = self.layers;
+        let mut section_starts = self.section_starts;
+        section_starts[RenderLayer::Opaque as usize][SECTIONS_PER_CHUNK] = opaque.len() as u32;
+        section_starts[RenderLayer::Cutout as usize][SECTIONS_PER_CHUNK] = cutout.len() as u32;
+        section_starts[RenderLayer::Translucent as usize][SECTIONS_PER_CHUNK] = translucent.len() as u32;
+        // the cutout quads follow the opaque ones in the shared list
+        for start in section_starts[RenderLayer::Cutout as usize].iter_mut() {
+            *start += opaque.len() as u32;
+        }
+
+        let mut indices = opaque;
+        indices.extend(cutout);
+
+        ChunkMeshData {
+            vertices: self.vertices,
+            indices,
+            section_starts,
+            translucent: TranslucentQuads {
+                indices: translucent,
+                centers: self.translucent_centers
+            }
+        }
+    }
+}
+
+/// Overlays such as the grass fringe have holes, whatever block they lie on
+fn quad_layer(block: Block, texture_layer: usize) -> RenderLayer {
+    if texture_layer > 0 { RenderLayer::Cutout } else { block.render_layer() }
+}
+
+/// Builds the triangle list of one chunk in world coordinates
+/// With `smooth_lighting` every corner averages the light around it and is darkened by
+/// ambient occlusion, otherwise a face takes the light of the block in front of it.
+pub fn mesh_chunk<W: BlockView>(
+    world: &W,
+    chunk: &Chunk,
+    atlas: &TextureAtlas,
+    colors: &BiomeColors,
+    smooth_lighting: bool
+) -> ChunkMeshData {
+    let origin_x = chunk.position.x * CHUNK_SIZE as i32;
+    let origin_z = chunk.position.z * CHUNK_SIZE as i32;
+    let tints = TintGrid::new(world, colors, origin_x, origin_z);
+
+    // neighbours inside the chunk skip the world lookup
+    let block_at = |x: i32, y: i32, z: i32| -> Block {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            Block::Air
+        } else if x >= 0 && x < CHUNK_SIZE as i32 && z >= 0 && z < CHUNK_SIZE as i32 {
+            chunk.get_block(x as usize, y as usize, z as usize)
+        } else {
+            world.get_block(origin_x + x, y, origin_z + z)
+        }
+    };
+    let light_at = |x: i32, y: i32, z: i32| -> [f32; 2] {
+        let (sky, block) = if y >= 0 && y < CHUNK_HEIGHT as i32
+            && x >= 0 && x < CHUNK_SIZE as i32 && z >= 0 && z < CHUNK_SIZE as i32
+        {
+            let (x, y, z) = (x as usize, y as usize, z as usize);
+            (chunk.get_sky_light(x, y, z), chunk.get_block_light(x, y, z))
+        } else {
+            (
+                world.get_light(LightChannel::Sky, origin_x + x, y, origin_z + z),
+                world.get_light(LightChannel::Block, origin_x + x, y, origin_z + z)
+            )
+        };
+        [sky as f32 / MAX_LIGHT as f32, block as f32 / MAX_LIGHT as f32]
+    };
+    let fluid_level_at = |x: i32, y: i32, z: i32| -> u8 {
+        if y >= 0 && y < CHUNK_HEIGHT as i32 && x >= 0 && x < CHUNK_SIZE as i32 && z >= 0 && z < CHUNK_SIZE as i32 {
+            chunk.get_fluid_level(x as usize, y as usize, z as usize)
+        } else {
+            world.get_fluid_level(origin_x + x, y, origin_z + z)
+        }
+    };
+
+    // height of a fluid's surface at a grid corner, averaged over the four columns around it.
+    // Sources and falling fluid weigh the most, open blocks pull the surface down and fluid
+    // above any of the columns fills the corner up.
+    let fluid_corner = |corner_x: i32, y: i32, corner_z: i32, fluid: Block| -> f32 {
+        let mut total = 0.0;
+        let mut weight = 0.0;
+        for column_x in corner_x - 1..=corner_x {
+            for column_z in corner_z - 1..=corner_z {
+                if block_at(column_x, y + 1, column_z) == fluid {
+                    return 1.0;
+                }
+
+                let block = block_at(column_x, y, column_z);
+                if block == fluid {
+                    let level = fluid_level_at(column_x, y, column_z);
+                    let column_weight = if level == 0 || level >= FALLING { 10.0 } else { 1.0 };
+                    total += fluid::surface_height(level) * column_weight;
+                    weight += column_weight;
+                } else if block.collision_height().is_none() {
+                    weight += 1.0;
+                }
+            }
+        }
+        total / weight
+    };
+
+    // light and occlusion of a face corner, `front` is the block the face looks into
+    let smooth_corner = |front: (i32, i32, i32), face: Face, corner: [f32; 3]| -> ([f32; 2], f32) {
+        let [side_a, side_b] = corner_sides(face, corner);
+        let side_a = (front.0 + side_a.0, front.1 + side_a.1, front.2 + side_a.2);
+        let side_b = (front.0 + side_b.0, front.1 + side_b.1, front.2 + side_b.2);
+        let diagonal = (side_a.0 + side_b.0 - front.0, side_a.1 + side_b.1 - front.1, side_a.2 + side_b.2 - front.2);
+
+        let solid_a = block_at(side_a.0, side_a.1, side_a.2).is_opaque();
+        let solid_b = block_at(side_b.0, side_b.1, side_b.2).is_opaque();
+        // light can't reach the diagonal through two solid sides
+        let solid_diagonal = (solid_a && solid_b) || block_at(diagonal.0, diagonal.1, diagonal.2).is_opaque();
+
+        let occlusion = if solid_a && solid_b {
+            0
+        } else {
+            3 - solid_a as usize - solid_b as usize - solid_diagonal as usize
+        };
+
+        let mut light = light_at(front.0, front.1, front.2);
+        let mut count = 1.0;
+        for &(position, solid) in [(side_a, solid_a), (side_b, solid_b), (diagonal, solid_diagonal)].iter() {
+            if !solid {
+                let sample = light_at(position.0, position.1, position.2);
+                light[0] += sample[0];
+                light[1] += sample[1];
+                count += 1.0;
+            }
+        }
+
+        ([light[0] / count, light[1] / count], AMBIENT_OCCLUSION[occlusion])
+    };
+
+    let mut mesh = MeshBuilder::new();
+    for y in 0..CHUNK_HEIGHT {
+        if y % SECTION_HEIGHT == 0 {
+            mesh.start_section(y / SECTION_HEIGHT);
+        }
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let block = chunk.get_block(x, y, z);
+                if block == Block::Air {
+                    continue;
+                }
+
+                let (local_x, local_y, local_z) = (x as i32, y as i32, z as i32);
+                let above = block_at(local_x, local_y + 1, local_z);
+                let surface = if block.is_fluid() {
+                    Some(FluidSurface {
+                        heights: [
+                            [fluid_corner(local_x, local_y, local_z, block), fluid_corner(local_x, local_y, local_z + 1, block)],
+                            [fluid_corner(local_x + 1, local_y, local_z, block), fluid_corner(local_x + 1, local_y, local_z + 1, block)]
+                        ]
+                    })
+                } else {
+                    None
+                };
+
+                for &face in Face::ALL.iter() {
+                    let (offset_x, offset_y, offset_z) = face.offset();
+                    let neighbour = block_at(local_x + offset_x, local_y + offset_y, local_z + offset_z);
+
+                    let front = (local_x + offset_x, local_y + offset_y, local_z + offset_z);
+
+                    // a face is lit by the block it looks into, panels by their own block
+                    let inset = match side_inset(block) {
+                        Some(inset) => {
+                            // only sides, and vines are not drawn between two vines
+                            if face == Face::Up || face == Face::Down || (block == Block::Vine && neighbour == block) {
+                                continue;
+                            }
+                            inset
+                        }
+                        None => {
+                            // a fluid's surface may lie below the block on top of it
+                            let is_hidden = if block.is_fluid() {
+                                neighbour == block || (face != Face::Up && neighbour.is_opaque())
+                            } else {
+                                neighbour.is_opaque()
+                            };
+                            if is_hidden {
+                                continue;
+                            }
+                            0.0
+                        }
+                    };
+
+                    let corners = face_corners(face);
+                    let corner_lights = if smooth_lighting && inset == 0.0 {
+                        [
+                            smooth_corner(front, face, corners[0]),
+                            smooth_corner(front, face, corners[1]),
+                            smooth_corner(front, face, corners[2]),
+                            smooth_corner(front, face, corners[3])
+                        ]
+                    } else {
+                        let light = if inset == 0.0 {
+                            light_at(front.0, front.1, front.2)
+                        } else {
+                            light_at(local_x, local_y, local_z)
+                        };
+                        [(light, 1.0); 4]
+                    };
+
+                    // split the quad along its brighter diagonal, otherwise the occlusion
+                    // gradient depends on which way the triangles happen to run
+                    let brightness = |i: usize| {
+                        let (light, occlusion) = corner_lights[i];
+                        light[0].max(light[1]) * occlusion
+                    };
+                    let triangles: [u32; 6] = if brightness(0) + brightness(2) < brightness(1) + brightness(3) {
+                        [1, 2, 3, 3, 0, 1]
+                    } else {
+                        [0, 1, 2, 2, 3, 0]
+                    };
+
+                    let shade = face_shade(face);
+
+                    let textures = match &surface {
+                        Some(surface) => vec![fluid_texture(block, face, surface)],
+                        None => face_textures(block, face, chunk.get_facing(x, y, z), above)
+                    };
+                    for (texture_layer, (texture, tint)) in textures.into_iter().enumerate() {
+                        let uv = atlas.uv(texture);
+                        let mut quad = build_quad(
+                            [origin_x as f32 + x as f32, y as f32, origin_z as f32 + z as f32],
+                            1.0,
+                            face,
+                            inset,
+                            uv,
+                            atlas.animation(texture),
+                            |i| {
+                                // corners sit on the grid between columns
+                                let corner_x = x + corners[i][0] as usize;
+                                let corner_z = z + corners[i][2] as usize;
+                                let color = tints.corner_color(colors, corner_x, corner_z, tint);
+                                let (light, occlusion) = corner_lights[i];
+                                let shade = shade * occlusion;
+                                ([color[0] * shade, color[1] * shade, color[2] * shade], light)
+                            }
+                        );
+                        if let Some(surface) = &surface {
+                            shape_fluid_quad(&mut quad, face, surface, uv);
+                        }
+                        // vines are seen from inside the block as well
+                        mesh.push_quad(quad_layer(block, texture_layer), &quad, &triangles, block == Block::Vine);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh.finish()
+}
+
+// border cells this close to the top of their column get skirts
+const LOD_SKIRT_CELLS: i32 = 2;
+
+/// Down sampled mesh of a distant chunk, level `n` merges 2^n blocks along each axis into
+/// one cell. A cell is solid when any of its blocks is and looks like the topmost of them,
+/// so coarser levels never sink below finer ones. The border cells near the top of each
+/// column always get their outward faces, these skirts cover the steps to neighbouring
+/// chunks meshed at another level.
+pub fn mesh_chunk_lod<W: BlockView>(
+    world: &W,
+    chunk: &Chunk,
+    atlas: &TextureAtlas,
+    colors: &BiomeColors,
+    level: u32
+) -> ChunkMeshData {
+    let scale = 1usize << level;
+    let size = (CHUNK_SIZE / scale) as i32;
+    let height = (CHUNK_HEIGHT / scale) as i32;
+    let origin_x = chunk.position.x * CHUNK_SIZE as i32;
+    let origin_z = chunk.position.z * CHUNK_SIZE as i32;
+    let tints = TintGrid::new(world, colors, origin_x, origin_z);
+
+    let index = |x: i32, y: i32, z: i32| ((y * size + z) * size + x) as usize;
+    let mut cells = vec![Block::Air; (size * size * height) as usize];
+    for y in 0..height {
+        for z in 0..size {
+            for x in 0..size {
+                cells[index(x, y, z)] = lod_cell(scale, |block_x, block_y, block_z| {
+                    chunk.get_block(x as usize * scale + block_x, y as usize * scale + block_y, z as usize * scale + block_z)
+                });
+            }
+        }
+    }
+
+    // cells outside the chunk are sampled from the world at the same level
+    let cell_at = |x: i32, y: i32, z: i32| -> Block {
+        if y < 0 || y >= height {
+            Block::Air
+        } else if x >= 0 && x < size && z >= 0 && z < size {
+            cells[index(x, y, z)]
+        } else {
+            lod_cell(scale, |block_x, block_y, block_z| world.get_block(
+                origin_x + x * scale as i32 + block_x as i32,
+                y * scale as i32 + block_y as i32,
+                origin_z + z * scale as i32 + block_z as i32
+            ))
+        }
+    };
+
+    let mut column_tops = vec![-1; (size * size) as usize];
+    for z in 0..size {
+        for x in 0..size {
+            column_tops[(z * size + x) as usize] = (0..height)
+                .rev()
+                .find(|&y| cells[index(x, y, z)] != Block::Air)
+                .unwrap_or(-1);
+        }
+    }
+
+    let mut mesh = MeshBuilder::new();
+    for y in 0..height {
+        let block_y = y as usize * scale;
+        if block_y % SECTION_HEIGHT == 0 {
+            mesh.start_section(block_y / SECTION_HEIGHT);
+        }
+        for z in 0..size {
+            for x in 0..size {
+                let block = cells[index(x, y, z)];
+                if block == Block::Air {
+                    continue;
+                }
+
+                let above = cell_at(x, y + 1, z);
+                let is_skirt = column_tops[(z * size + x) as usize] - y < LOD_SKIRT_CELLS;
+
+                for &face in Face::ALL.iter() {
+                    let (offset_x, offset_y, offset_z) = face.offset();
+                    let front = (x + offset_x, y + offset_y, z + offset_z);
+                    let neighbour = cell_at(front.0, front.1, front.2);
+
+                    let is_border = front.0 < 0 || front.0 >= size || front.2 < 0 || front.2 >= size;
+                    let is_hidden = neighbour != Block::Air && (neighbour.is_opaque() || neighbour == block);
+                    if is_hidden && !(is_border && is_skirt) {
+                        continue;
+                    }
+
+                    // flat light from the top layer of the cell in front
+                    let (light_x, light_y, light_z) = (
+                        origin_x + front.0 * scale as i32,
+                        front.1 * scale as i32 + scale as i32 - 1,
+                        origin_z + front.2 * scale as i32
+                    );
+                    let light = [
+                        world.get_light(LightChannel::Sky, light_x, light_y, light_z) as f32 / MAX_LIGHT as f32,
+                        world.get_light(LightChannel::Block, light_x, light_y, light_z) as f32 / MAX_LIGHT as f32
+                    ];
+                    let shade = face_shade(face);
+                    let corners = face_corners(face);
+
+                    // cells merge many blocks, their facings are not kept
+                    for (texture_layer, (texture, tint)) in face_textures(block, face, None, above).into_iter().enumerate() {
+                        let quad = build_quad(
+                            [
+                                (origin_x + x * scale as i32) as f32,
+                                (y * scale as i32) as f32,
+                                (origin_z + z * scale as i32) as f32
+                            ],
+                            scale as f32,
+                            face,
+                            0.0,
+                            atlas.uv(texture),
+                            atlas.animation(texture),
+                            |i| {
+                                let corner_x = x as usize * scale + corners[i][0] as usize * scale;
+                                let corner_z = z as usize * scale + corners[i][2] as usize * scale;
+                                let color = tints.corner_color(colors, corner_x, corner_z, tint);
+                                ([color[0] * shade, color[1] * shade, color[2] * shade], light)
+                            }
+                        );
+
+                        mesh.push_quad(quad_layer(block, texture_layer), &quad, &[0, 1, 2, 2, 3, 0], false);
+                    }
+                }
+            }
+        }
+    }
+
+    mesh.finish()
+}
+
+/// The topmost block of a `scale`^3 cell that is visible from afar, vines and torches
+/// are too small to matter
+fn lod_cell<F>(scale: usize, block_at: F) -> Block
+where F: Fn(usize, usize, usize) -> Block {
+    for y in (0..scale).rev() {
+        for z in 0..scale {
+            for x in 0..scale {
+                match block_at(x, y, z) {
+                    Block::Air | Block::Vine | Block::Torch => {}
+                    block => return block
+                }
+            }
+        }
+    }
+    Block::Air
+}
+
+fn build_quad<F>(
+    origin: [f32; 3],
+    size: f32,
+    face: Face,
+    inset: f32,
+    uv: [f32; 4],
+    animation: [f32; 3],
+    corner: F
+) -> [ChunkVertex; 4]
+where F: Fn(usize) -> ([f32; 3], [f32; 2]) {
+    let [u0, v0, u1, v1] = uv;
+    let tex_coords = match face {
+        Face::Up | Face::Down => [[u0, v0], [u0, v1], [u1, v1], [u1, v0]],
+        _ => [[u0, v1], [u1, v1], [u1, v0], [u0, v0]]
+    };
+    let (normal_x, normal_y, normal_z) = face.offset();
+
+    let corners = face_corners(face);
+    let vertex = |i: usize| {
+        let position = corners[i];
+        let (color, light) = corner(i);
+        ChunkVertex {
+            pos: [
+                origin[0] + position[0] * size - normal_x as f32 * inset,
+                origin[1] + position[1] * size - normal_y as f32 * inset,
+                origin[2] + position[2] * size - normal_z as f32 * inset,
+                1.0
+            ],
+            color: [color[0], color[1], color[2], 1.0],
+            tex_coord: tex_coords[i],
+            light,
+            animation
+        }
+    };
+
+    [vertex(0), vertex(1), vertex(2), vertex(3)]
+}
@@ -0,0 +1,590 @@
+use ash::vk;
+use cgmath::{MetricSpace, Point3};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::jobs::job_system::{JobKind, JobSystem};
+use crate::settings_loader::graphics_settings::GraphicsSettings;
+use crate::vulkan_engine::buffers::Buffers;
+use crate::vulkan_engine::camera::Camera;
+use crate::vulkan_engine::chunk_mesher::{self, ChunkMeshData, TranslucentQuads, BLOCK_TEXTURES, LAYER_COUNT};
+use crate::vulkan_engine::culling::{CullingStats, Frustum, SectionVisibility};
+use crate::vulkan_engine::descriptors::Descriptors;
+use crate::vulkan_engine::draw_list::{DrawList, IndirectMesh, Material};
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::geometry_arena::{GeometryArena, GeometryRange};
+use crate::vulkan_engine::graphics_pipeline::TerrainPipelines;
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage};
+use crate::vulkan_engine::texture::{Texture, TextureDescription};
+use crate::vulkan_engine::texture_atlas::TextureAtlas;
+use crate::vulkan_engine::upload_manager::UploadManager;
+use crate::vulkan_engine::utilities::constants::{MAX_FRAMES_IN_FLIGHT, TEXTURE_DIRECTORY};
+use crate::vulkan_engine::utilities::structures::ChunkVertex;
+use crate::world::block::{Face, RenderLayer};
+use crate::world::chunk::{ChunkPos, CHUNK_SIZE, SECTION_HEIGHT, SECTIONS_PER_CHUNK};
+use crate::world::colormap::BiomeColors;
+use crate::world::world::World;
+
+// 192 MiB of vertices, a quad uses 4 vertices and 6 indices
+const MAX_VERTICES: u32 = 4 * 1024 * 1024;
+const MAX_INDICES: u32 = MAX_VERTICES / 4 * 6;
+// one indirect command per visible section and render layer
+const MAX_DRAWS: usize = 64 * 1024;
+// 8x8x8 cells
+const MAX_LOD_LEVEL: u32 = 3;
+// finished meshes uploaded per update
+const MAX_MESHES_PER_UPDATE: usize = 16;
+// chunks around the camera whose translucent quads are sorted again when it moves,
+// further away the order of the quads is rarely visible
+const RESORT_DISTANCE: i32 = 3;
+
+/// The blended quads of a chunk, drawn with the vertices of its main geometry
+struct TranslucentMesh {
+    indices: GeometryRange,
+    quads: TranslucentQuads
+}
+
+struct ChunkMesh {
+    // 0 is full detail
+    level: u32,
+    geometry: GeometryRange,
+    // relative to the first index of the geometry, or of the translucent indices for that layer
+    section_starts: [[u32; SECTIONS_PER_CHUNK + 1]; LAYER_COUNT],
+    translucent: Option<TranslucentMesh>
+}
+
+impl ChunkMesh {
+    fn is_section_empty(&self, section: usize) -> bool {
+        self.section_starts.iter().all(|starts| starts[section] == starts[section + 1])
+    }
+}
+
+/// A mesh built by a job, a result without a level removes the chunk's mesh
+struct MeshResult {
+    position: ChunkPos,
+    version: u64,
+    level: Option<u32>,
+    mesh_data: Option<ChunkMeshData>,
+    // only for full detail meshes
+    visibility: Option<[SectionVisibility; SECTIONS_PER_CHUNK]>
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct SectionPos {
+    chunk: ChunkPos,
+    y: i32
+}
+
+impl SectionPos {
+    fn containing(position: Point3<f32>) -> SectionPos {
+        let y = (position.y.floor() as i32).div_euclid(SECTION_HEIGHT as i32);
+        SectionPos {
+            chunk: ChunkPos::from_block(position.x.floor() as i32, position.z.floor() as i32),
+            // the walk starts from the nearest section when the camera is above or below the world
+            y: y.clamp(0, SECTIONS_PER_CHUNK as i32 - 1)
+        }
+    }
+
+    fn neighbour(&self, face: Face) -> SectionPos {
+        let (offset_x, offset_y, offset_z) = face.offset();
+        SectionPos {
+            chunk: ChunkPos::new(self.chunk.x + offset_x, self.chunk.z + offset_z),
+            y: self.y + offset_y
+        }
+    }
+
+    fn min_corner(&self) -> Point3<f32> {
+        Point3::new(
+            (self.chunk.x * CHUNK_SIZE as i32) as f32,
+            (self.y * SECTION_HEIGHT as i32) as f32,
+            (self.chunk.z * CHUNK_SIZE as i32) as f32
+        )
+    }
+
+    fn center(&self) -> Point3<f32> {
+        let min = self.min_corner();
+        Point3::new(min.x + CHUNK_SIZE as f32 / 2.0, min.y + SECTION_HEIGHT as f32 / 2.0, min.z + CHUNK_SIZE as f32 / 2.0)
+    }
+
+    fn is_inside(&self, frustum: &Frustum) -> bool {
+        let min = self.min_corner();
+        let max = Point3::new(min.x + CHUNK_SIZE as f32, min.y + SECTION_HEIGHT as f32, min.z + CHUNK_SIZE as f32);
+        frustum.intersects_box(min, max)
+    }
+}
+
+/// Owns the block atlas and the geometry arena holding every meshed chunk. Chunks are
+/// meshed by jobs and the visible sections of each render layer are drawn by one
+/// indirect draw.
+pub struct ChunkRenderer {
+    atlas: Arc<TextureAtlas>,
+    texture: Texture,
+    biome_colors: Arc<BiomeColors>,
+    smooth_lighting: bool,
+    render_distance: u32,
+    lod_distances: Vec<u32>,
+
+    // the level of detail bands are centred on this chunk
+    camera_chunk: ChunkPos,
+    // level every chunk was last queued for, also the ones without geometry
+    levels: HashMap<ChunkPos, u32>,
+    // version of the latest mesh queued per chunk, older results are dropped
+    mesh_versions: HashMap<ChunkPos, u64>,
+    next_mesh_version: u64,
+    mesh_sender: Sender<MeshResult>,
+    mesh_receiver: Receiver<MeshResult>,
+    meshes: HashMap<ChunkPos, ChunkMesh>,
+    // kept for every full detail chunk, also the ones without geometry
+    visibility: HashMap<ChunkPos, [SectionVisibility; SECTIONS_PER_CHUNK]>,
+    arena: GeometryArena,
+    // replaced geometry and the number of frames submitted when it was replaced
+    retired_geometry: Vec<(u64, GeometryRange)>,
+    // translucent quads are sorted back to front from this point
+    eye: Point3<f32>,
+    // the block the eye was in when the quads were last sorted
+    sort_block: Option<Point3<i32>>,
+
+    // per swapchain image
+    indirect_buffers: Vec<vk::Buffer>,
+    indirect_allocations: Vec<Allocation>,
+    pub descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+impl ChunkRenderer {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        graphics_settings: &GraphicsSettings
+    ) -> EngineResult<ChunkRenderer> {
+        let texture_directory = Path::new(TEXTURE_DIRECTORY);
+        let atlas = TextureAtlas::build(&texture_directory.join("block"), &BLOCK_TEXTURES)?;
+        let biome_colors = BiomeColors::load(texture_directory, graphics_settings.biome_blend_radius)
+            .map_err(|error| EngineError::Asset { path: texture_directory.join("colormap"), message: error.to_string() })?;
+
+        let description = TextureDescription {
+            width: atlas.width,
+            height: atlas.height,
+            layer_count: 1,
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE
+        };
+        let texture = Texture::from_pixels(device, allocator, uploads, &atlas.pixels, &description)?;
+        let arena = GeometryArena::new::<ChunkVertex>(device, allocator, MAX_VERTICES, MAX_INDICES)?;
+        let (mesh_sender, mesh_receiver) = channel();
+
+        Ok(ChunkRenderer {
+            atlas: Arc::new(atlas),
+            texture,
+            biome_colors: Arc::new(biome_colors),
+            smooth_lighting: graphics_settings.smooth_lighting,
+            render_distance: graphics_settings.render_distance,
+            lod_distances: graphics_settings.lod_distances.clone(),
+            camera_chunk: ChunkPos::new(0, 0),
+            levels: HashMap::new(),
+            mesh_versions: HashMap::new(),
+            next_mesh_version: 0,
+            mesh_sender,
+            mesh_receiver,
+            meshes: HashMap::new(),
+            visibility: HashMap::new(),
+            arena,
+            retired_geometry: vec![],
+            eye: Point3::new(0.0, 0.0, 0.0),
+            sort_block: None,
+            indirect_buffers: vec![],
+            indirect_allocations: vec![],
+            descriptor_sets: vec![]
+        })
+    }
+
+    /// (Re)creates the per swapchain image indirect buffers and descriptor sets
+    pub fn create_swapchain_resources(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        uniform_buffers: &Vec<vk::Buffer>
+    ) -> EngineResult<()> {
+        self.indirect_buffers.clear();
+        self.indirect_allocations.clear();
+        for _ in 0..uniform_buffers.len() {
+            let (indirect_buffer, indirect_allocation) = Buffers::create_buffer(
+                device,
+                allocator,
+                (std::mem::size_of::<vk::DrawIndexedIndirectCommand>() * MAX_DRAWS * LAYER_COUNT) as vk::DeviceSize,
+                vk::BufferUsageFlags::INDIRECT_BUFFER,
+                MemoryUsage::CpuToGpu
+            )?;
+            self.indirect_buffers.push(indirect_buffer);
+            self.indirect_allocations.push(indirect_allocation);
+        }
+
+        self.descriptor_sets = Descriptors::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            uniform_buffers,
+            &self.texture
+        )?;
+
+        Ok(())
+    }
+
+    /// Descriptor sets are freed together with the pool
+    pub fn destroy_swapchain_resources(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        for (&buffer, allocation) in self.indirect_buffers.iter().zip(self.indirect_allocations.iter()) {
+            Buffers::destroy_buffer(device, allocator, buffer, allocation);
+        }
+    }
+
+    /// Chunks around the camera that are drawn, at full or reduced detail
+    pub fn view_distance(&self) -> u32 {
+        self.lod_distances.iter().copied().fold(self.render_distance, u32::max)
+    }
+
+    fn distance_to_camera(&self, position: ChunkPos) -> u32 {
+        (position.x - self.camera_chunk.x).abs().max((position.z - self.camera_chunk.z).abs()) as u32
+    }
+
+    /// Level of detail of a chunk, None when it is beyond the last band
+    fn level_of(&self, position: ChunkPos) -> Option<u32> {
+        let distance = self.distance_to_camera(position);
+        if distance <= self.render_distance {
+            return Some(0);
+        }
+
+        self.lod_distances
+            .iter()
+            .position(|&band_distance| distance <= band_distance)
+            .map(|band| (band as u32 + 1).min(MAX_LOD_LEVEL))
+    }
+
+    /// Centres the level of detail bands on the camera and returns the loaded chunks
+    /// that have to be meshed again at another level
+    pub fn track_camera(&mut self, jobs: &JobSystem, world: &World, camera_position: Point3<f32>) -> Vec<ChunkPos> {
+        let camera_chunk = ChunkPos::from_block(camera_position.x.floor() as i32, camera_position.z.floor() as i32);
+        if camera_chunk == self.camera_chunk {
+            return vec![];
+        }
+        self.camera_chunk = camera_chunk;
+        jobs.reprioritise(JobKind::Mesh, |position| Some(self.distance_to_camera(position)));
+
+        world.chunks
+            .keys()
+            .filter(|&&position| self.levels.get(&position).copied() != self.level_of(position))
+            .copied()
+            .collect()
+    }
+
+    /// Queues mesh jobs for the given chunks at their level of detail, nearest first.
+    /// Chunks beyond the last band or unloaded lose their mesh.
+    pub fn queue_meshes(&mut self, jobs: &JobSystem, world: &World, positions: &[ChunkPos]) {
+        for &position in positions.iter() {
+            self.next_mesh_version += 1;
+            let version = self.next_mesh_version;
+            self.mesh_versions.insert(position, version);
+
+            let level = match self.level_of(position).filter(|_| world.chunks.contains_key(&position)) {
+                Some(level) => level,
+                None => {
+                    self.levels.remove(&position);
+                    jobs.cancel(JobKind::Mesh, position);
+                    let _ = self.mesh_sender.send(MeshResult {
+                        position,
+                        version,
+                        level: None,
+                        mesh_data: None,
+                        visibility: None
+                    });
+                    continue;
+                }
+            };
+            self.levels.insert(position, level);
+
+            let neighbourhood = world.neighbourhood(position);
+            let atlas = Arc::clone(&self.atlas);
+            let biome_colors = Arc::clone(&self.biome_colors);
+            let smooth_lighting = self.smooth_lighting;
+            let sender = self.mesh_sender.clone();
+            jobs.submit(JobKind::Mesh, position, self.distance_to_camera(position), move || {
+                let chunk = match neighbourhood.center() {
+                    Some(chunk) => chunk,
+                    None => return
+                };
+
+                let (mesh_data, visibility) = if level == 0 {
+                    let mut visibility = [SectionVisibility::OPEN; SECTIONS_PER_CHUNK];
+                    for (section, section_visibility) in visibility.iter_mut().enumerate() {
+                        *section_visibility = SectionVisibility::compute(chunk, section);
+                    }
+                    let mesh_data = chunk_mesher::mesh_chunk(&neighbourhood, chunk, &atlas, &biome_colors, smooth_lighting);
+                    (mesh_data, Some(visibility))
+                } else {
+                    (chunk_mesher::mesh_chunk_lod(&neighbourhood, chunk, &atlas, &biome_colors, level), None)
+                };
+
+                let _ = sender.send(MeshResult {
+                    position,
+                    version,
+                    level: Some(level),
+                    mesh_data: Some(mesh_data),
+                    visibility
+                });
+            });
+        }
+    }
+
+    /// Uploads a few of the meshes finished by jobs, replacing the old ones. `submitted_frames`
+    /// counts the frames submitted so far, replaced geometry is freed once every frame that
+    /// may still draw it has finished. The new meshes are drawable once the uploads are flushed.
+    pub fn receive_meshes(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        submitted_frames: u64
+    ) -> EngineResult<()> {
+        let arena = &mut self.arena;
+        self.retired_geometry.retain(|&(retired_at, geometry)| {
+            let is_unused = retired_at + MAX_FRAMES_IN_FLIGHT as u64 <= submitted_frames;
+            if is_unused {
+                arena.free(geometry);
+            }
+            !is_unused
+        });
+
+        let results: Vec<MeshResult> = self.mesh_receiver
+            .try_iter()
+            .take(MAX_MESHES_PER_UPDATE)
+            .collect();
+
+        for result in results {
+            if self.mesh_versions.get(&result.position) != Some(&result.version) {
+                continue;
+            }
+            self.mesh_versions.remove(&result.position);
+
+            if let Some(old_mesh) = self.meshes.remove(&result.position) {
+                self.retired_geometry.push((submitted_frames, old_mesh.geometry));
+                if let Some(translucent) = old_mesh.translucent {
+                    self.retired_geometry.push((submitted_frames, translucent.indices));
+                }
+            }
+            self.visibility.remove(&result.position);
+            if let Some(visibility) = result.visibility {
+                self.visibility.insert(result.position, visibility);
+            }
+
+            let (level, mesh_data) = match (result.level, result.mesh_data) {
+                (Some(level), Some(mesh_data)) if !mesh_data.vertices.is_empty() => (level, mesh_data),
+                _ => continue
+            };
+
+            let geometry = self.arena.upload(device, allocator, uploads, &mesh_data.vertices, &mesh_data.indices)?;
+            let translucent = if mesh_data.translucent.indices.is_empty() {
+                None
+            } else {
+                let section_starts = &mesh_data.section_starts[RenderLayer::Translucent as usize];
+                let indices = mesh_data.translucent.sorted_indices(section_starts, self.eye);
+                Some(TranslucentMesh {
+                    indices: self.arena.upload_indices(device, allocator, uploads, &indices)?,
+                    quads: mesh_data.translucent
+                })
+            };
+
+            self.meshes.insert(result.position, ChunkMesh {
+                level,
+                geometry,
+                section_starts: mesh_data.section_starts,
+                translucent
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Sorts the translucent quads of the chunks near the camera back to front again once
+    /// it has moved to another block, the old index ranges are retired like replaced meshes
+    pub fn sort_translucency(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        submitted_frames: u64,
+        camera_position: Point3<f32>
+    ) -> EngineResult<()> {
+        self.eye = camera_position;
+        let block = Point3::new(
+            camera_position.x.floor() as i32,
+            camera_position.y.floor() as i32,
+            camera_position.z.floor() as i32
+        );
+        if self.sort_block == Some(block) {
+            return Ok(());
+        }
+        self.sort_block = Some(block);
+
+        let camera_chunk = ChunkPos::from_block(block.x, block.z);
+        for (position, mesh) in self.meshes.iter_mut() {
+            let translucent = match mesh.translucent.as_mut() {
+                Some(translucent) => translucent,
+                None => continue
+            };
+            if (position.x - camera_chunk.x).abs().max((position.z - camera_chunk.z).abs()) > RESORT_DISTANCE {
+                continue;
+            }
+
+            let section_starts = &mesh.section_starts[RenderLayer::Translucent as usize];
+            let indices = translucent.quads.sorted_indices(section_starts, camera_position);
+            let sorted = self.arena.upload_indices(device, allocator, uploads, &indices)?;
+            let unsorted = std::mem::replace(&mut translucent.indices, sorted);
+            self.retired_geometry.push((submitted_frames, unsorted));
+        }
+
+        Ok(())
+    }
+
+    /// Queues the sections the camera can see. Full detail sections are found front to back
+    /// by a walk starting at the camera's section, which only steps into sections inside the
+    /// frustum, only leaves a section through a face connected to the one it entered by, and
+    /// never turns back towards the camera. Distant sections are only frustum culled.
+    /// The opaque and cutout layers are drawn front to back, the translucent one back to front.
+    pub fn draw(
+        &self,
+        draw_list: &mut DrawList,
+        image_index: usize,
+        pipelines: &TerrainPipelines,
+        pipeline_layout: vk::PipelineLayout,
+        camera: &Camera
+    ) -> CullingStats {
+        let frustum = Frustum::from_matrix(camera.projection_matrix() * camera.view_matrix());
+
+        let start = SectionPos::containing(camera.position);
+        let mut visited = HashSet::new();
+        let mut queue = VecDeque::new();
+        let mut sections = vec![];
+        visited.insert(start);
+        // the face the section was entered through and the directions taken so far
+        queue.push_back((start, None, 0u8));
+
+        while let Some((section, entered_through, directions)) = queue.pop_front() {
+            let visibility = match self.visibility.get(&section.chunk) {
+                Some(visibility) => visibility[section.y as usize],
+                None => continue
+            };
+
+            if let Some(mesh) = self.meshes.get(&section.chunk) {
+                if !mesh.is_section_empty(section.y as usize) {
+                    sections.push((section, mesh));
+                }
+            }
+
+            for &face in Face::ALL.iter() {
+                if directions & (1 << face.opposite() as u8) != 0 {
+                    continue;
+                }
+                if let Some(entered_through) = entered_through {
+                    if !visibility.connects(entered_through, face) {
+                        continue;
+                    }
+                }
+
+                let next = section.neighbour(face);
+                if next.y < 0 || next.y >= SECTIONS_PER_CHUNK as i32
+                    || !self.visibility.contains_key(&next.chunk)
+                    || visited.contains(&next)
+                    || !next.is_inside(&frustum)
+                {
+                    continue;
+                }
+
+                visited.insert(next);
+                queue.push_back((next, Some(face.opposite()), directions | 1 << face as u8));
+            }
+        }
+
+        let mut stats = CullingStats::default();
+        for (&chunk, mesh) in self.meshes.iter() {
+            for y in 0..SECTIONS_PER_CHUNK {
+                if mesh.is_section_empty(y) {
+                    continue;
+                }
+
+                let section = SectionPos { chunk, y: y as i32 };
+                if mesh.level == 0 && visited.contains(&section) {
+                    stats.drawn += 1;
+                } else if !section.is_inside(&frustum) {
+                    stats.frustum_culled += 1;
+                } else if mesh.level > 0 {
+                    sections.push((section, mesh));
+                    stats.drawn += 1;
+                } else {
+                    stats.occlusion_culled += 1;
+                }
+            }
+        }
+
+        let mut commands = vec![];
+        let mut layer_draws = [(0, 0); LAYER_COUNT];
+        for &layer in RenderLayer::ALL.iter() {
+            if layer == RenderLayer::Translucent {
+                sections.sort_by(|(a, _), (b, _)| {
+                    b.center().distance2(camera.position).total_cmp(&a.center().distance2(camera.position))
+                });
+            }
+
+            let first_command = commands.len();
+            for &(section, mesh) in sections.iter() {
+                let command = ChunkRenderer::section_command(mesh, layer, section.y as usize);
+                if command.index_count > 0 && commands.len() - first_command < MAX_DRAWS {
+                    commands.push(command);
+                }
+            }
+            layer_draws[layer as usize] = (first_command as u32, (commands.len() - first_command) as u32);
+        }
+
+        self.indirect_allocations[image_index].write(&commands);
+        for &layer in RenderLayer::ALL.iter() {
+            let (first_command, draw_count) = layer_draws[layer as usize];
+            let material = Material {
+                pipeline: pipelines.get(layer),
+                pipeline_layout,
+                descriptor_set: self.descriptor_sets[image_index]
+            };
+            draw_list.push_indirect(material, IndirectMesh {
+                vertex_buffer: self.arena.vertex_buffer,
+                index_buffer: self.arena.index_buffer,
+                indirect_buffer: self.indirect_buffers[image_index],
+                first_command,
+                draw_count
+            });
+        }
+
+        stats
+    }
+
+    fn section_command(mesh: &ChunkMesh, layer: RenderLayer, section: usize) -> vk::DrawIndexedIndirectCommand {
+        let section_starts = &mesh.section_starts[layer as usize];
+        // translucent quads index the vertices of the main geometry from their own range
+        let first_index = match (layer, &mesh.translucent) {
+            (RenderLayer::Translucent, Some(translucent)) => translucent.indices.first_index,
+            _ => mesh.geometry.first_index
+        };
+
+        vk::DrawIndexedIndirectCommand {
+            index_count: section_starts[section + 1] - section_starts[section],
+            instance_count: 1,
+            first_index: first_index + section_starts[section],
+            vertex_offset: mesh.geometry.first_vertex as i32,
+            first_instance: 0
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        self.arena.destroy(device, allocator);
+        self.texture.destroy(device, allocator);
+    }
+}
@@ -0,0 +1,160 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use cgmath::{Point3, Vector3};
+use std::path::{Path, PathBuf};
+
+use crate::entity::block_interaction::CRACK_STAGES;
+use crate::vulkan_engine::descriptors::Descriptors;
+use crate::vulkan_engine::draw_list::{DrawList, Material, Mesh};
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, PipelineDescription};
+use crate::vulkan_engine::memory_allocator::MemoryAllocator;
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::vulkan_engine::texture::Texture;
+use crate::vulkan_engine::upload_manager::UploadManager;
+use crate::vulkan_engine::utilities::constants::TEXTURE_DIRECTORY;
+use crate::world::block::Block;
+use crate::world::raycast;
+
+// the cracks sit this far outside the block so they don't fight with its faces
+const CRACK_OFFSET: f32 = 0.001;
+const VERTICES_PER_CUBE: u32 = 36;
+
+/// Draws the breaking progress of a block as a textured cube over it, the box and the
+/// crack stage are passed as push constants and the vertex shader builds the faces
+pub struct CrackRenderer {
+    texture: Texture,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    pub descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+impl CrackRenderer {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        descriptor_set_layout: vk::DescriptorSetLayout
+    ) -> EngineResult<CrackRenderer> {
+        let texture_directory = Path::new(TEXTURE_DIRECTORY).join("block");
+        let stage_paths: Vec<PathBuf> = (0..CRACK_STAGES)
+            .map(|stage| texture_directory.join(format!("destroy_stage_{}.png", stage)))
+            .collect();
+        let stage_paths: Vec<&Path> = stage_paths.iter().map(|path| path.as_path()).collect();
+
+        let texture = Texture::new_array(
+            device,
+            allocator,
+            uploads,
+            &stage_paths,
+            vk::SamplerAddressMode::CLAMP_TO_EDGE
+        )?;
+
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<[[f32; 4]; 2]>() as u32,
+        }];
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(
+            device,
+            &[descriptor_set_layout],
+            &push_constant_ranges
+        )?;
+
+        Ok(CrackRenderer {
+            texture,
+            pipeline_layout,
+            pipeline: vk::Pipeline::null(),
+            descriptor_sets: vec![]
+        })
+    }
+
+    /// (Re)creates the per swapchain image descriptor sets
+    pub fn create_swapchain_resources(
+        &mut self,
+        device: &ash::Device,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        uniform_buffers: &Vec<vk::Buffer>
+    ) -> EngineResult<()> {
+        self.descriptor_sets = Descriptors::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            uniform_buffers,
+            &self.texture
+        )?;
+
+        Ok(())
+    }
+
+    pub fn create_pipeline(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<()> {
+        let description = PipelineDescription {
+            cull_mode: vk::CullModeFlags::NONE,
+            blend_enable: true,
+            depth_test: true,
+            ..PipelineDescription::new("crack.vert.spv", "crack.frag.spv")
+        };
+
+        self.pipeline = GraphicsPipeline::create_pipeline(
+            device,
+            render_pass,
+            self.pipeline_layout,
+            &description,
+            shaders,
+            pipeline_cache
+        )?;
+
+        Ok(())
+    }
+
+    pub fn destroy_pipeline(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        self.destroy_pipeline(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.texture.destroy(device, allocator);
+    }
+
+    /// `crack` is the block being broken and its stage, see `BlockInteraction::crack_stage`
+    pub fn draw(&self, draw_list: &mut DrawList, image_index: usize, crack: Option<(Point3<i32>, u32)>, block: Block) {
+        let (position, stage) = match crack {
+            Some(crack) => crack,
+            None => return
+        };
+
+        let material = Material {
+            pipeline: self.pipeline,
+            pipeline_layout: self.pipeline_layout,
+            descriptor_set: self.descriptor_sets[image_index]
+        };
+        let cube = Mesh {
+            vertex_buffer: vk::Buffer::null(),
+            first_vertex: 0,
+            vertex_count: VERTICES_PER_CUBE
+        };
+
+        let offset = Vector3::new(position.x as f32, position.y as f32, position.z as f32);
+        let (min, max) = raycast::outline_box(block);
+        let (min, max) = (min + offset, max + offset);
+        let crack_box: [f32; 8] = [
+            min.x - CRACK_OFFSET, min.y - CRACK_OFFSET, min.z - CRACK_OFFSET, 0.0,
+            max.x + CRACK_OFFSET, max.y + CRACK_OFFSET, max.z + CRACK_OFFSET, stage as f32
+        ];
+
+        let bytes: Vec<u8> = crack_box.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        draw_list.push_with_constants(material, cube, vk::ShaderStageFlags::VERTEX, &bytes);
+    }
+}
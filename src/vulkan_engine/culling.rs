@@ -0,0 +1,143 @@
+use cgmath::{Matrix, Matrix4, Point3, Vector4};
+use std::fmt;
+
+use crate::world::block::Face;
+use crate::world::chunk::{Chunk, SECTION_HEIGHT, CHUNK_SIZE};
+
+/// The six clip planes of a view projection matrix, normals point inwards
+pub struct Frustum {
+    planes: [Vector4<f32>; 6]
+}
+
+impl Frustum {
+    pub fn from_matrix(view_projection: Matrix4<f32>) -> Frustum {
+        let (row_x, row_y, row_z, row_w) = (
+            view_projection.row(0),
+            view_projection.row(1),
+            view_projection.row(2),
+            view_projection.row(3)
+        );
+
+        Frustum {
+            planes: [
+                row_w + row_x,
+                row_w - row_x,
+                row_w + row_y,
+                row_w - row_y,
+                row_w + row_z,
+                row_w - row_z
+            ]
+        }
+    }
+
+    /// False only when the box lies completely outside one of the planes
+    pub fn intersects_box(&self, min: Point3<f32>, max: Point3<f32>) -> bool {
+        self.planes.iter().all(|plane| {
+            // the corner furthest along the plane normal
+            let x = if plane.x >= 0.0 { max.x } else { min.x };
+            let y = if plane.y >= 0.0 { max.y } else { min.y };
+            let z = if plane.z >= 0.0 { max.z } else { min.z };
+            plane.x * x + plane.y * y + plane.z * z + plane.w >= 0.0
+        })
+    }
+}
+
+const SECTION_VOLUME: usize = CHUNK_SIZE * SECTION_HEIGHT * CHUNK_SIZE;
+
+/// Which faces of a 16^3 section can see each other through non opaque blocks,
+/// bit `from * 6 + to` is set when a path connects the two faces
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SectionVisibility(u64);
+
+impl SectionVisibility {
+    pub const OPEN: SectionVisibility = SectionVisibility((1 << 36) - 1);
+
+    pub fn connects(&self, from: Face, to: Face) -> bool {
+        self.0 & (1 << (from as usize * 6 + to as usize)) != 0
+    }
+
+    /// Flood fills every open region of the section and connects the faces it touches
+    pub fn compute(chunk: &Chunk, section: usize) -> SectionVisibility {
+        let base_y = section * SECTION_HEIGHT;
+        let index = |x: usize, y: usize, z: usize| (y * CHUNK_SIZE + z) * CHUNK_SIZE + x;
+
+        let mut is_closed = vec![false; SECTION_VOLUME];
+        let mut open_count = 0;
+        for y in 0..SECTION_HEIGHT {
+            for z in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    is_closed[index(x, y, z)] = chunk.get_block(x, base_y + y, z).is_opaque();
+                    open_count += !is_closed[index(x, y, z)] as usize;
+                }
+            }
+        }
+        if open_count == SECTION_VOLUME {
+            return SectionVisibility::OPEN;
+        }
+
+        let mut connections = 0u64;
+        let mut stack = vec![];
+        for start in 0..SECTION_VOLUME {
+            if is_closed[start] {
+                continue;
+            }
+
+            // reuse the closed flags to mark visited blocks
+            is_closed[start] = true;
+            stack.push(start);
+            let mut faces = 0u8;
+            while let Some(cell) = stack.pop() {
+                let (x, y, z) = (cell % CHUNK_SIZE, cell / (CHUNK_SIZE * CHUNK_SIZE), cell / CHUNK_SIZE % CHUNK_SIZE);
+                for &face in Face::ALL.iter() {
+                    let (offset_x, offset_y, offset_z) = face.offset();
+                    let (next_x, next_y, next_z) = (x as i32 + offset_x, y as i32 + offset_y, z as i32 + offset_z);
+                    if next_x < 0 || next_x >= CHUNK_SIZE as i32
+                        || next_y < 0 || next_y >= SECTION_HEIGHT as i32
+                        || next_z < 0 || next_z >= CHUNK_SIZE as i32
+                    {
+                        faces |= 1 << face as u8;
+                        continue;
+                    }
+
+                    let next = index(next_x as usize, next_y as usize, next_z as usize);
+                    if !is_closed[next] {
+                        is_closed[next] = true;
+                        stack.push(next);
+                    }
+                }
+            }
+
+            for from in 0..6 {
+                if faces & (1 << from) != 0 {
+                    for to in 0..6 {
+                        if faces & (1 << to) != 0 {
+                            connections |= 1 << (from * 6 + to);
+                        }
+                    }
+                }
+            }
+        }
+
+        SectionVisibility(connections)
+    }
+}
+
+/// Chunk sections with geometry, counted once per frame
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CullingStats {
+    pub drawn: u32,
+    pub frustum_culled: u32,
+    pub occlusion_culled: u32
+}
+
+impl fmt::Display for CullingStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "sections: {} drawn, {} outside the view, {} hidden",
+            self.drawn,
+            self.frustum_culled,
+            self.occlusion_culled
+        )
+    }
+}
@@ -0,0 +1,115 @@
+use ash::vk;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use std::ptr;
+
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage, ResourceLayout};
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+
+pub struct DepthBuffer {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub image_view: vk::ImageView
+}
+
+impl DepthBuffer {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        depth_format: vk::Format,
+        swapchain_extent: vk::Extent2D
+    ) -> EngineResult<DepthBuffer> {
+        let image_create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::ImageCreateFlags::empty(),
+            image_type: vk::ImageType::TYPE_2D,
+            format: depth_format,
+            extent: vk::Extent3D {
+                width: swapchain_extent.width,
+                height: swapchain_extent.height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: 1,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::DEPTH_STENCIL_ATTACHMENT,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::UNDEFINED,
+        };
+
+        let image = unsafe {
+            device.create_image(&image_create_info, None)?
+        };
+
+        let memory_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocation = allocator.allocate(memory_requirements, MemoryUsage::GpuOnly, ResourceLayout::Optimal)?;
+
+        unsafe {
+            device.bind_image_memory(image, allocation.memory, allocation.offset)?;
+        }
+
+        let imageview_create_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::ImageViewCreateFlags::empty(),
+            view_type: vk::ImageViewType::TYPE_2D,
+            format: depth_format,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            },
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::DEPTH,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count: 1,
+            },
+            image,
+        };
+
+        let image_view = unsafe {
+            device.create_image_view(&imageview_create_info, None)?
+        };
+
+        Ok(DepthBuffer {
+            image,
+            allocation,
+            image_view
+        })
+    }
+
+    pub fn find_depth_format(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> EngineResult<vk::Format> {
+        let candidates = [
+            vk::Format::D32_SFLOAT,
+            vk::Format::D32_SFLOAT_S8_UINT,
+            vk::Format::D24_UNORM_S8_UINT,
+        ];
+
+        for &format in candidates.iter() {
+            let format_properties =
+                unsafe { instance.get_physical_device_format_properties(physical_device, format) };
+            if format_properties
+                .optimal_tiling_features
+                .contains(vk::FormatFeatureFlags::DEPTH_STENCIL_ATTACHMENT)
+            {
+                return Ok(format);
+            }
+        }
+
+        Err(EngineError::NoDepthFormat)
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        unsafe {
+            device.destroy_image_view(self.image_view, None);
+            device.destroy_image(self.image, None);
+        }
+        allocator.free(&self.allocation);
+    }
+}
@@ -0,0 +1,178 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+
+/// How something is drawn, the pipeline and the resources bound to it
+#[derive(Clone, Copy, PartialEq)]
+pub struct Material {
+    pub pipeline: vk::Pipeline,
+    pub pipeline_layout: vk::PipelineLayout,
+    pub descriptor_set: vk::DescriptorSet
+}
+
+/// A vertex range, a null buffer draws without vertex input (e.g. a full screen triangle)
+#[derive(Clone, Copy)]
+pub struct Mesh {
+    pub vertex_buffer: vk::Buffer,
+    pub first_vertex: u32,
+    pub vertex_count: u32
+}
+
+/// Indexed draws whose parameters are read from `draw_count` consecutive
+/// `vk::DrawIndexedIndirectCommand`s of `indirect_buffer`, starting at `first_command`
+#[derive(Clone, Copy)]
+pub struct IndirectMesh {
+    pub vertex_buffer: vk::Buffer,
+    pub index_buffer: vk::Buffer,
+    pub indirect_buffer: vk::Buffer,
+    pub first_command: u32,
+    pub draw_count: u32
+}
+
+#[derive(Clone, Copy)]
+enum Geometry {
+    Vertices(Mesh),
+    IndexedIndirect(IndirectMesh)
+}
+
+struct Draw {
+    material: Material,
+    geometry: Geometry,
+    push_constant_stages: vk::ShaderStageFlags,
+    push_constants: Vec<u8>
+}
+
+/// Everything the game systems want drawn this frame, recorded in submission order
+/// into the frame's command buffer
+pub struct DrawList {
+    draws: Vec<Draw>,
+    multi_draw_indirect: bool
+}
+
+impl DrawList {
+    pub fn new(multi_draw_indirect: bool) -> DrawList {
+        DrawList {
+            draws: vec![],
+            multi_draw_indirect
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.draws.clear();
+    }
+
+    pub fn push(&mut self, material: Material, mesh: Mesh) {
+        self.push_with_constants(material, mesh, vk::ShaderStageFlags::empty(), &[]);
+    }
+
+    /// `push_constants` are written at offset 0 of the pipeline layout's push constant range
+    pub fn push_with_constants(
+        &mut self,
+        material: Material,
+        mesh: Mesh,
+        push_constant_stages: vk::ShaderStageFlags,
+        push_constants: &[u8]
+    ) {
+        if mesh.vertex_count == 0 {
+            return;
+        }
+
+        self.draws.push(Draw {
+            material,
+            geometry: Geometry::Vertices(mesh),
+            push_constant_stages,
+            push_constants: push_constants.to_vec()
+        });
+    }
+
+    pub fn push_indirect(&mut self, material: Material, mesh: IndirectMesh) {
+        if mesh.draw_count == 0 {
+            return;
+        }
+
+        self.draws.push(Draw {
+            material,
+            geometry: Geometry::IndexedIndirect(mesh),
+            push_constant_stages: vk::ShaderStageFlags::empty(),
+            push_constants: vec![]
+        });
+    }
+
+    /// Records the draws inside an active render pass, state is only rebound when it changes
+    pub fn record(&self, device: &ash::Device, command_buffer: vk::CommandBuffer) {
+        let mut bound_material: Option<Material> = None;
+        let mut bound_vertex_buffer = vk::Buffer::null();
+        let mut bound_index_buffer = vk::Buffer::null();
+
+        for draw in self.draws.iter() {
+            unsafe {
+                if bound_material.map(|material| material.pipeline) != Some(draw.material.pipeline) {
+                    device.cmd_bind_pipeline(command_buffer, vk::PipelineBindPoint::GRAPHICS, draw.material.pipeline);
+                }
+                if bound_material != Some(draw.material) {
+                    device.cmd_bind_descriptor_sets(
+                        command_buffer,
+                        vk::PipelineBindPoint::GRAPHICS,
+                        draw.material.pipeline_layout,
+                        0,
+                        &[draw.material.descriptor_set],
+                        &[]
+                    );
+                    bound_material = Some(draw.material);
+                }
+
+                let vertex_buffer = match draw.geometry {
+                    Geometry::Vertices(mesh) => mesh.vertex_buffer,
+                    Geometry::IndexedIndirect(mesh) => mesh.vertex_buffer
+                };
+                if vertex_buffer != vk::Buffer::null() && vertex_buffer != bound_vertex_buffer {
+                    device.cmd_bind_vertex_buffers(command_buffer, 0, &[vertex_buffer], &[0]);
+                    bound_vertex_buffer = vertex_buffer;
+                }
+
+                if !draw.push_constants.is_empty() {
+                    device.cmd_push_constants(
+                        command_buffer,
+                        draw.material.pipeline_layout,
+                        draw.push_constant_stages,
+                        0,
+                        &draw.push_constants
+                    );
+                }
+
+                match draw.geometry {
+                    Geometry::Vertices(mesh) => {
+                        device.cmd_draw(command_buffer, mesh.vertex_count, 1, mesh.first_vertex, 0);
+                    }
+                    Geometry::IndexedIndirect(mesh) => {
+                        if mesh.index_buffer != bound_index_buffer {
+                            device.cmd_bind_index_buffer(command_buffer, mesh.index_buffer, 0, vk::IndexType::UINT32);
+                            bound_index_buffer = mesh.index_buffer;
+                        }
+                        self.record_indirect(device, command_buffer, &mesh);
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_indirect(&self, device: &ash::Device, command_buffer: vk::CommandBuffer, mesh: &IndirectMesh) {
+        let stride = std::mem::size_of::<vk::DrawIndexedIndirectCommand>() as u32;
+        let offset = (mesh.first_command * stride) as vk::DeviceSize;
+
+        unsafe {
+            if self.multi_draw_indirect {
+                device.cmd_draw_indexed_indirect(command_buffer, mesh.indirect_buffer, offset, mesh.draw_count, stride);
+            } else {
+                for i in 0..mesh.draw_count {
+                    device.cmd_draw_indexed_indirect(
+                        command_buffer,
+                        mesh.indirect_buffer,
+                        offset + (i * stride) as vk::DeviceSize,
+                        1,
+                        stride
+                    );
+                }
+            }
+        }
+    }
+}
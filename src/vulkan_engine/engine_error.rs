@@ -0,0 +1,66 @@
+use ash::vk;
+use std::fmt;
+use std::path::PathBuf;
+
+pub type EngineResult<T> = Result<T, EngineError>;
+
+/// Everything that can stop the engine from starting or rendering a frame
+#[derive(Debug)]
+pub enum EngineError {
+    Vulkan(vk::Result),
+    Io(std::io::Error),
+    Shader { path: PathBuf, message: String },
+    Asset { path: PathBuf, message: String },
+    Window(String),
+    LoaderNotFound(String),
+    MissingValidationLayers,
+    NoSuitableGpu,
+    NoMemoryType,
+    NoDepthFormat,
+    OutOfGeometryMemory
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Vulkan(result) => write!(f, "Vulkan call failed: {}", result),
+            EngineError::Io(error) => write!(f, "I/O error: {}", error),
+            EngineError::Shader { path, message } => write!(f, "unable to load shader {:?}: {}", path, message),
+            EngineError::Asset { path, message } => write!(f, "unable to load asset {:?}: {}", path, message),
+            EngineError::Window(message) => write!(f, "unable to create the window: {}", message),
+            EngineError::LoaderNotFound(message) => write!(f, "the Vulkan loader could not be found, is a Vulkan driver installed? ({})", message),
+            EngineError::MissingValidationLayers => write!(f, "validation layers were requested but are not installed"),
+            EngineError::NoSuitableGpu => write!(f, "no Vulkan-capable GPU found"),
+            EngineError::NoMemoryType => write!(f, "the GPU has no suitable memory type"),
+            EngineError::NoDepthFormat => write!(f, "the GPU supports none of the depth formats"),
+            EngineError::OutOfGeometryMemory => write!(f, "the chunk geometry does not fit into the geometry arena")
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+impl From<vk::Result> for EngineError {
+    fn from(result: vk::Result) -> EngineError {
+        EngineError::Vulkan(result)
+    }
+}
+
+impl From<ash::InstanceError> for EngineError {
+    fn from(error: ash::InstanceError) -> EngineError {
+        match error {
+            // the loader reports this when no installed driver can create an instance
+            ash::InstanceError::VkError(vk::Result::ERROR_INCOMPATIBLE_DRIVER) => EngineError::NoSuitableGpu,
+            ash::InstanceError::VkError(result) => EngineError::Vulkan(result),
+            ash::InstanceError::LoadError(functions) => EngineError::LoaderNotFound(
+                format!("missing {}", functions.join(", "))
+            )
+        }
+    }
+}
+
+impl From<std::io::Error> for EngineError {
+    fn from(error: std::io::Error) -> EngineError {
+        EngineError::Io(error)
+    }
+}
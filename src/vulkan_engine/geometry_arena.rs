@@ -0,0 +1,197 @@
+use ash::vk;
+
+use crate::vulkan_engine::buffers::Buffers;
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage};
+use crate::vulkan_engine::upload_manager::UploadManager;
+
+/// First fit allocator over `capacity` elements, freed ranges merge with their neighbours
+struct RangeAllocator {
+    // (start, length), sorted by start
+    free_ranges: Vec<(u32, u32)>
+}
+
+impl RangeAllocator {
+    fn new(capacity: u32) -> RangeAllocator {
+        RangeAllocator {
+            free_ranges: vec![(0, capacity)]
+        }
+    }
+
+    fn allocate(&mut self, length: u32) -> Option<u32> {
+        let i = self.free_ranges.iter().position(|&(_, free_length)| free_length >= length)?;
+        let (start, free_length) = self.free_ranges[i];
+
+        if free_length == length {
+            self.free_ranges.remove(i);
+        } else {
+            self.free_ranges[i] = (start + length, free_length - length);
+        }
+
+        Some(start)
+    }
+
+    fn free(&mut self, start: u32, length: u32) {
+        if length == 0 {
+            return;
+        }
+        let i = self.free_ranges.partition_point(|&(free_start, _)| free_start < start);
+        self.free_ranges.insert(i, (start, length));
+
+        // merge with the following range, then with the preceding one
+        if i + 1 < self.free_ranges.len() && start + length == self.free_ranges[i + 1].0 {
+            self.free_ranges[i].1 += self.free_ranges[i + 1].1;
+            self.free_ranges.remove(i + 1);
+        }
+        if i > 0 && self.free_ranges[i - 1].0 + self.free_ranges[i - 1].1 == start {
+            self.free_ranges[i - 1].1 += self.free_ranges[i].1;
+            self.free_ranges.remove(i);
+        }
+    }
+}
+
+/// Where one mesh lives inside the arena, indices are relative to `first_vertex`
+#[derive(Clone, Copy, Debug)]
+pub struct GeometryRange {
+    pub first_vertex: u32,
+    pub vertex_count: u32,
+    pub first_index: u32,
+    pub index_count: u32
+}
+
+/// One large vertex buffer and index buffer shared by many meshes, so they can all be
+/// drawn without rebinding
+pub struct GeometryArena {
+    pub vertex_buffer: vk::Buffer,
+    vertex_allocation: Allocation,
+    pub index_buffer: vk::Buffer,
+    index_allocation: Allocation,
+
+    vertex_size: vk::DeviceSize,
+    vertices: RangeAllocator,
+    indices: RangeAllocator
+}
+
+impl GeometryArena {
+    pub fn new<V>(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        max_vertices: u32,
+        max_indices: u32
+    ) -> EngineResult<GeometryArena> {
+        let vertex_size = std::mem::size_of::<V>() as vk::DeviceSize;
+
+        let (vertex_buffer, vertex_allocation) = Buffers::create_buffer(
+            device,
+            allocator,
+            vertex_size * max_vertices as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::VERTEX_BUFFER,
+            MemoryUsage::GpuOnly
+        )?;
+        let (index_buffer, index_allocation) = Buffers::create_buffer(
+            device,
+            allocator,
+            std::mem::size_of::<u32>() as vk::DeviceSize * max_indices as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_DST | vk::BufferUsageFlags::INDEX_BUFFER,
+            MemoryUsage::GpuOnly
+        )?;
+
+        Ok(GeometryArena {
+            vertex_buffer,
+            vertex_allocation,
+            index_buffer,
+            index_allocation,
+            vertex_size,
+            vertices: RangeAllocator::new(max_vertices),
+            indices: RangeAllocator::new(max_indices)
+        })
+    }
+
+    /// Copies a mesh into free space of the arena, it is drawable once the uploads are flushed
+    pub fn upload<V: Copy>(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        vertices: &[V],
+        indices: &[u32]
+    ) -> EngineResult<GeometryRange> {
+        let vertex_count = vertices.len() as u32;
+        let index_count = indices.len() as u32;
+
+        let first_vertex = self.vertices.allocate(vertex_count)
+            .ok_or(EngineError::OutOfGeometryMemory)?;
+        let first_index = match self.indices.allocate(index_count) {
+            Some(first_index) => first_index,
+            None => {
+                self.vertices.free(first_vertex, vertex_count);
+                return Err(EngineError::OutOfGeometryMemory);
+            }
+        };
+
+        uploads.upload_to_buffer(
+            device,
+            allocator,
+            self.vertex_buffer,
+            first_vertex as vk::DeviceSize * self.vertex_size,
+            vk::BufferUsageFlags::VERTEX_BUFFER,
+            vertices
+        )?;
+        uploads.upload_to_buffer(
+            device,
+            allocator,
+            self.index_buffer,
+            first_index as vk::DeviceSize * std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            indices
+        )?;
+
+        Ok(GeometryRange {
+            first_vertex,
+            vertex_count,
+            first_index,
+            index_count
+        })
+    }
+
+    /// Copies indices into free space of the arena, for index lists that draw the vertices
+    /// of another range
+    pub fn upload_indices(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        indices: &[u32]
+    ) -> EngineResult<GeometryRange> {
+        let index_count = indices.len() as u32;
+        let first_index = self.indices.allocate(index_count)
+            .ok_or(EngineError::OutOfGeometryMemory)?;
+
+        uploads.upload_to_buffer(
+            device,
+            allocator,
+            self.index_buffer,
+            first_index as vk::DeviceSize * std::mem::size_of::<u32>() as vk::DeviceSize,
+            vk::BufferUsageFlags::INDEX_BUFFER,
+            indices
+        )?;
+
+        Ok(GeometryRange {
+            first_vertex: 0,
+            vertex_count: 0,
+            first_index,
+            index_count
+        })
+    }
+
+    /// The GPU must not be drawing the range anymore
+    pub fn free(&mut self, range: GeometryRange) {
+        self.vertices.free(range.first_vertex, range.vertex_count);
+        self.indices.free(range.first_index, range.index_count);
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        Buffers::destroy_buffer(device, allocator, self.vertex_buffer, &self.vertex_allocation);
+        Buffers::destroy_buffer(device, allocator, self.index_buffer, &self.index_allocation);
+    }
+}
@@ -1,57 +1,191 @@
-use std::path::Path;
-use std::io::Read;
 use ash::vk;
 use std::ptr;
 use ash::Device;
 use ash::version::DeviceV1_0;
 use std::ffi::CString;
+use crate::vulkan_engine::utilities::structures::ChunkVertex;
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::world::block::RenderLayer;
+
+/// The parts that differ between the pipelines of the engine, everything else
+/// (viewport, multisampling, depth) is shared
+pub struct PipelineDescription<'a> {
+    pub vertex_shader: &'a str,
+    pub fragment_shader: &'a str,
+    pub vertex_bindings: &'a [vk::VertexInputBindingDescription],
+    pub vertex_attributes: &'a [vk::VertexInputAttributeDescription],
+    pub topology: vk::PrimitiveTopology,
+    pub cull_mode: vk::CullModeFlags,
+    pub blend_enable: bool,
+    pub depth_test: bool,
+    pub depth_write: bool
+}
+
+impl<'a> PipelineDescription<'a> {
+    pub fn new(vertex_shader: &'a str, fragment_shader: &'a str) -> PipelineDescription<'a> {
+        PipelineDescription {
+            vertex_shader,
+            fragment_shader,
+            vertex_bindings: &[],
+            vertex_attributes: &[],
+            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            cull_mode: vk::CullModeFlags::BACK,
+            blend_enable: false,
+            depth_test: false,
+            depth_write: false
+        }
+    }
+}
+
+/// One terrain pipeline per render layer, sharing the chunk vertex layout and pipeline layout
+#[derive(Clone, Copy)]
+pub struct TerrainPipelines {
+    pub opaque: vk::Pipeline,
+    pub cutout: vk::Pipeline,
+    pub translucent: vk::Pipeline
+}
+
+impl TerrainPipelines {
+    pub fn get(&self, layer: RenderLayer) -> vk::Pipeline {
+        match layer {
+            RenderLayer::Opaque => self.opaque,
+            RenderLayer::Cutout => self.cutout,
+            RenderLayer::Translucent => self.translucent
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.opaque, None);
+            device.destroy_pipeline(self.cutout, None);
+            device.destroy_pipeline(self.translucent, None);
+        }
+    }
+}
 
 pub struct GraphicsPipeline{
     pub render_pass: vk::RenderPass,
     pub pipeline_layout: vk::PipelineLayout,
-    pub graphics_pipeline: vk::Pipeline,
+    pub terrain_pipelines: TerrainPipelines,
     _device: ash::Device
 }
 
 impl GraphicsPipeline {
-    pub fn new(device: &ash::Device, format: vk::Format, extent: vk::Extent2D) -> GraphicsPipeline{
+    pub fn new(
+        device: &ash::Device,
+        format: vk::Format,
+        depth_format: vk::Format,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<GraphicsPipeline>{
         let render_pass =
-            GraphicsPipeline::create_render_pass(device, format);
+            GraphicsPipeline::create_render_pass(device, format, depth_format)?;
 
-        let (graphics_pipeline, pipeline_layout) =
-            GraphicsPipeline::create_graphics_pipeline(device, render_pass, extent);
+        let (terrain_pipelines, pipeline_layout) =
+            GraphicsPipeline::create_graphics_pipeline(device, render_pass, descriptor_set_layout, shaders, pipeline_cache)?;
 
-        GraphicsPipeline{
+        Ok(GraphicsPipeline{
             render_pass,
             pipeline_layout,
-            graphics_pipeline,
+            terrain_pipelines,
             _device: device.clone()
-        }
+        })
     }
 
+    /// The terrain pipelines, drawing chunk meshes textured from the block atlas. Cutout
+    /// quads are alpha tested, translucent ones are blended without writing depth and
+    /// seen from both sides, like the surface of water from below.
     pub fn create_graphics_pipeline(
         device: &ash::Device,
         render_pass: vk::RenderPass,
-        swapchain_extent: vk::Extent2D
-    ) -> (vk::Pipeline, vk::PipelineLayout){
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<(TerrainPipelines, vk::PipelineLayout)>{
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(device, &[descriptor_set_layout], &[])?;
+
+        let binding_descriptions = ChunkVertex::get_binding_descriptions();
+        let attribute_descriptions = ChunkVertex::get_attribute_descriptions();
+        let opaque_description = PipelineDescription {
+            vertex_bindings: &binding_descriptions,
+            vertex_attributes: &attribute_descriptions,
+            depth_test: true,
+            depth_write: true,
+            ..PipelineDescription::new("simple_shader.vert.spv", "simple_shader.frag.spv")
+        };
+        let cutout_description = PipelineDescription {
+            fragment_shader: "simple_shader_cutout.frag.spv",
+            ..opaque_description
+        };
+        let translucent_description = PipelineDescription {
+            cull_mode: vk::CullModeFlags::NONE,
+            blend_enable: true,
+            depth_write: false,
+            ..opaque_description
+        };
 
+        let mut pipelines = vec![];
+        for description in [&opaque_description, &cutout_description, &translucent_description].iter() {
+            match GraphicsPipeline::create_pipeline(device, render_pass, pipeline_layout, description, shaders, pipeline_cache) {
+                Ok(pipeline) => pipelines.push(pipeline),
+                Err(error) => {
+                    unsafe {
+                        for &pipeline in pipelines.iter() {
+                            device.destroy_pipeline(pipeline, None);
+                        }
+                        device.destroy_pipeline_layout(pipeline_layout, None);
+                    }
+                    return Err(error);
+                }
+            }
+        }
 
-        let base_path = std::env::current_dir().unwrap();
+        let terrain_pipelines = TerrainPipelines {
+            opaque: pipelines[0],
+            cutout: pipelines[1],
+            translucent: pipelines[2]
+        };
+        Ok((terrain_pipelines, pipeline_layout))
+    }
 
-        let vert_path = base_path.join(Path::new("src/vulkan_engine/shaders/simple_shader.vert.spv"));
-        let vert_shader_code =
-            GraphicsPipeline::read_shader_code(vert_path.as_path());
+    pub fn create_pipeline_layout(
+        device: &ash::Device,
+        set_layouts: &[vk::DescriptorSetLayout],
+        push_constant_ranges: &[vk::PushConstantRange]
+    ) -> EngineResult<vk::PipelineLayout> {
+        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
+            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineLayoutCreateFlags::empty(),
+            set_layout_count: set_layouts.len() as u32,
+            p_set_layouts: set_layouts.as_ptr(),
+            push_constant_range_count: push_constant_ranges.len() as u32,
+            p_push_constant_ranges: push_constant_ranges.as_ptr(),
+        };
 
+        let pipeline_layout = unsafe {
+            device.create_pipeline_layout(&pipeline_layout_create_info, None)?
+        };
 
-        let frag_path = base_path.join(Path::new("src/vulkan_engine/shaders/simple_shader.frag.spv"));
-        let frag_shader_code =
-            GraphicsPipeline::read_shader_code(frag_path.as_path());
+        Ok(pipeline_layout)
+    }
 
+    pub fn create_pipeline(
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        pipeline_layout: vk::PipelineLayout,
+        description: &PipelineDescription,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<vk::Pipeline> {
         let vert_shader_module =
-            GraphicsPipeline::create_shader_module(device, vert_shader_code);
+            GraphicsPipeline::load_shader_module(device, shaders, description.vertex_shader)?;
 
         let frag_shader_module =
-            GraphicsPipeline::create_shader_module(device, frag_shader_code);
+            GraphicsPipeline::load_shader_module(device, shaders, description.fragment_shader)
+                .inspect_err(|_| unsafe { device.destroy_shader_module(vert_shader_module, None) })?;
 
         let main_function_name = CString::new("main").unwrap();
 
@@ -80,41 +214,36 @@ impl GraphicsPipeline {
             s_type: vk::StructureType::PIPELINE_VERTEX_INPUT_STATE_CREATE_INFO,
             p_next: ptr::null(),
             flags: vk::PipelineVertexInputStateCreateFlags::empty(),
-            vertex_attribute_description_count: 0,
-            p_vertex_attribute_descriptions: ptr::null(),
-            vertex_binding_description_count: 0,
-            p_vertex_binding_descriptions: ptr::null(),
+            vertex_attribute_description_count: description.vertex_attributes.len() as u32,
+            p_vertex_attribute_descriptions: description.vertex_attributes.as_ptr(),
+            vertex_binding_description_count: description.vertex_bindings.len() as u32,
+            p_vertex_binding_descriptions: description.vertex_bindings.as_ptr(),
         };
         let vertex_input_assembly_state_info = vk::PipelineInputAssemblyStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_INPUT_ASSEMBLY_STATE_CREATE_INFO,
             flags: vk::PipelineInputAssemblyStateCreateFlags::empty(),
             p_next: ptr::null(),
             primitive_restart_enable: vk::FALSE,
-            topology: vk::PrimitiveTopology::TRIANGLE_LIST,
+            topology: description.topology,
         };
 
-        let viewports = [vk::Viewport {
-            x: 0.0,
-            y: 0.0,
-            width: swapchain_extent.width as f32,
-            height: swapchain_extent.height as f32,
-            min_depth: 0.0,
-            max_depth: 1.0,
-        }];
-
-        let scissors = [vk::Rect2D {
-            offset: vk::Offset2D { x: 0, y: 0 },
-            extent: swapchain_extent,
-        }];
-
+        // set while recording, so resizing the window keeps the pipelines
         let viewport_state_create_info = vk::PipelineViewportStateCreateInfo {
             s_type: vk::StructureType::PIPELINE_VIEWPORT_STATE_CREATE_INFO,
             p_next: ptr::null(),
             flags: vk::PipelineViewportStateCreateFlags::empty(),
-            scissor_count: scissors.len() as u32,
-            p_scissors: scissors.as_ptr(),
-            viewport_count: viewports.len() as u32,
-            p_viewports: viewports.as_ptr(),
+            scissor_count: 1,
+            p_scissors: ptr::null(),
+            viewport_count: 1,
+            p_viewports: ptr::null(),
+        };
+        let dynamic_states = [vk::DynamicState::VIEWPORT, vk::DynamicState::SCISSOR];
+        let dynamic_state_create_info = vk::PipelineDynamicStateCreateInfo {
+            s_type: vk::StructureType::PIPELINE_DYNAMIC_STATE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineDynamicStateCreateFlags::empty(),
+            dynamic_state_count: dynamic_states.len() as u32,
+            p_dynamic_states: dynamic_states.as_ptr(),
         };
 
         let rasterization_statue_create_info = vk::PipelineRasterizationStateCreateInfo {
@@ -122,8 +251,9 @@ impl GraphicsPipeline {
             p_next: ptr::null(),
             flags: vk::PipelineRasterizationStateCreateFlags::empty(),
             depth_clamp_enable: vk::FALSE,
-            cull_mode: vk::CullModeFlags::BACK,
-            front_face: vk::FrontFace::CLOCKWISE,
+            cull_mode: description.cull_mode,
+            // counter clockwise seen from outside, the projection flips y
+            front_face: vk::FrontFace::COUNTER_CLOCKWISE,
             line_width: 1.0,
             polygon_mode: vk::PolygonMode::FILL,
             rasterizer_discard_enable: vk::FALSE,
@@ -158,8 +288,8 @@ impl GraphicsPipeline {
             s_type: vk::StructureType::PIPELINE_DEPTH_STENCIL_STATE_CREATE_INFO,
             p_next: ptr::null(),
             flags: vk::PipelineDepthStencilStateCreateFlags::empty(),
-            depth_test_enable: vk::FALSE,
-            depth_write_enable: vk::FALSE,
+            depth_test_enable: if description.depth_test { vk::TRUE } else { vk::FALSE },
+            depth_write_enable: if description.depth_write { vk::TRUE } else { vk::FALSE },
             depth_compare_op: vk::CompareOp::LESS_OR_EQUAL,
             depth_bounds_test_enable: vk::FALSE,
             stencil_test_enable: vk::FALSE,
@@ -169,15 +299,28 @@ impl GraphicsPipeline {
             min_depth_bounds: 0.0,
         };
 
-        let color_blend_attachment_states = [vk::PipelineColorBlendAttachmentState {
-            blend_enable: vk::FALSE,
-            color_write_mask: vk::ColorComponentFlags::all(),
-            src_color_blend_factor: vk::BlendFactor::ONE,
-            dst_color_blend_factor: vk::BlendFactor::ZERO,
-            color_blend_op: vk::BlendOp::ADD,
-            src_alpha_blend_factor: vk::BlendFactor::ONE,
-            dst_alpha_blend_factor: vk::BlendFactor::ZERO,
-            alpha_blend_op: vk::BlendOp::ADD,
+        let color_blend_attachment_states = [if description.blend_enable {
+            vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::TRUE,
+                color_write_mask: vk::ColorComponentFlags::all(),
+                src_color_blend_factor: vk::BlendFactor::SRC_ALPHA,
+                dst_color_blend_factor: vk::BlendFactor::ONE_MINUS_SRC_ALPHA,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+                alpha_blend_op: vk::BlendOp::ADD,
+            }
+        } else {
+            vk::PipelineColorBlendAttachmentState {
+                blend_enable: vk::FALSE,
+                color_write_mask: vk::ColorComponentFlags::all(),
+                src_color_blend_factor: vk::BlendFactor::ONE,
+                dst_color_blend_factor: vk::BlendFactor::ZERO,
+                color_blend_op: vk::BlendOp::ADD,
+                src_alpha_blend_factor: vk::BlendFactor::ONE,
+                dst_alpha_blend_factor: vk::BlendFactor::ZERO,
+                alpha_blend_op: vk::BlendOp::ADD,
+            }
         }];
 
         let color_blend_state = vk::PipelineColorBlendStateCreateInfo {
@@ -192,22 +335,6 @@ impl GraphicsPipeline {
         };
 
 
-        let pipeline_layout_create_info = vk::PipelineLayoutCreateInfo {
-            s_type: vk::StructureType::PIPELINE_LAYOUT_CREATE_INFO,
-            p_next: ptr::null(),
-            flags: vk::PipelineLayoutCreateFlags::empty(),
-            set_layout_count: 0,
-            p_set_layouts: ptr::null(),
-            push_constant_range_count: 0,
-            p_push_constant_ranges: ptr::null(),
-        };
-
-        let pipeline_layout = unsafe {
-            device
-                .create_pipeline_layout(&pipeline_layout_create_info, None)
-                .expect("Failed to create pipeline layout!")
-        };
-
         let graphic_pipeline_create_infos = [vk::GraphicsPipelineCreateInfo {
             s_type: vk::StructureType::GRAPHICS_PIPELINE_CREATE_INFO,
             p_next: ptr::null(),
@@ -222,7 +349,7 @@ impl GraphicsPipeline {
             p_multisample_state: &multisample_state_create_info,
             p_depth_stencil_state: &depth_state_create_info,
             p_color_blend_state: &color_blend_state,
-            p_dynamic_state: ptr::null(),
+            p_dynamic_state: &dynamic_state_create_info,
             layout: pipeline_layout,
             render_pass,
             subpass: 0,
@@ -231,24 +358,24 @@ impl GraphicsPipeline {
         }];
 
         let graphics_pipelines = unsafe {
-            device
-                .create_graphics_pipelines(
-                    vk::PipelineCache::null(),
-                    &graphic_pipeline_create_infos,
-                    None,
-                )
-                .expect("Failed to create Graphics Pipeline!.")
+            device.create_graphics_pipelines(
+                pipeline_cache,
+                &graphic_pipeline_create_infos,
+                None,
+            )
         };
 
+        // the modules are only needed while the pipeline is created, successful or not
         unsafe{
             device.destroy_shader_module(vert_shader_module, None);
             device.destroy_shader_module(frag_shader_module, None);
         }
 
-        (graphics_pipelines[0], pipeline_layout)
+        let graphics_pipelines = graphics_pipelines.map_err(|(_, result)| result)?;
+        Ok(graphics_pipelines[0])
     }
 
-    pub fn create_render_pass(device: &ash::Device, surface_format: vk::Format) -> vk::RenderPass{
+    pub fn create_render_pass(device: &ash::Device, surface_format: vk::Format, depth_format: vk::Format) -> EngineResult<vk::RenderPass>{
         let color_attachment = vk::AttachmentDescription {
             flags: vk::AttachmentDescriptionFlags::empty(),
             format: surface_format,
@@ -261,11 +388,28 @@ impl GraphicsPipeline {
             final_layout: vk::ImageLayout::PRESENT_SRC_KHR,
         };
 
+        let depth_attachment = vk::AttachmentDescription {
+            flags: vk::AttachmentDescriptionFlags::empty(),
+            format: depth_format,
+            samples: vk::SampleCountFlags::TYPE_1,
+            load_op: vk::AttachmentLoadOp::CLEAR,
+            store_op: vk::AttachmentStoreOp::DONT_CARE,
+            stencil_load_op: vk::AttachmentLoadOp::DONT_CARE,
+            stencil_store_op: vk::AttachmentStoreOp::DONT_CARE,
+            initial_layout: vk::ImageLayout::UNDEFINED,
+            final_layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+
         let color_attachment_ref = vk::AttachmentReference {
             attachment: 0,
             layout: vk::ImageLayout::COLOR_ATTACHMENT_OPTIMAL,
         };
 
+        let depth_attachment_ref = vk::AttachmentReference {
+            attachment: 1,
+            layout: vk::ImageLayout::DEPTH_STENCIL_ATTACHMENT_OPTIMAL,
+        };
+
 
         let subpasses = [vk::SubpassDescription {
             flags: vk::SubpassDescriptionFlags::empty(),
@@ -275,20 +419,23 @@ impl GraphicsPipeline {
             color_attachment_count: 1,
             p_color_attachments: &color_attachment_ref,
             p_resolve_attachments: ptr::null(),
-            p_depth_stencil_attachment: ptr::null(),
+            p_depth_stencil_attachment: &depth_attachment_ref,
             preserve_attachment_count: 0,
             p_preserve_attachments: ptr::null(),
         }];
 
-        let render_pass_attachments = [color_attachment];
+        let render_pass_attachments = [color_attachment, depth_attachment];
 
         let subpass_dependencies = [vk::SubpassDependency {
             src_subpass: vk::SUBPASS_EXTERNAL,
             dst_subpass: 0,
-            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
-            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT,
+            src_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
+            dst_stage_mask: vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT
+                | vk::PipelineStageFlags::EARLY_FRAGMENT_TESTS,
             src_access_mask: vk::AccessFlags::empty(),
-            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE,
+            dst_access_mask: vk::AccessFlags::COLOR_ATTACHMENT_WRITE
+                | vk::AccessFlags::DEPTH_STENCIL_ATTACHMENT_WRITE,
             dependency_flags: vk::DependencyFlags::empty(),
         }];
 
@@ -304,38 +451,31 @@ impl GraphicsPipeline {
             p_dependencies: subpass_dependencies.as_ptr(),
         };
 
-        unsafe {
-            device
-                .create_render_pass(&renderpass_create_info, None)
-                .expect("Failed to create render pass!")
-        }
-    }
-
-    fn read_shader_code(shader_path : &Path) -> Vec<u8>{
-        use std::fs::File;
-        use std::io::Read;
+        let render_pass = unsafe {
+            device.create_render_pass(&renderpass_create_info, None)?
+        };
 
-        let spv_file = File::open(shader_path)
-            .expect(&format!("Failed to find spv file at {:?}", shader_path));
+        Ok(render_pass)
+    }
 
-        let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
+    fn load_shader_module(device: &ash::Device, shaders: &ShaderLibrary, file_name: &str) -> EngineResult<vk::ShaderModule> {
+        let shader_code = shaders.get(file_name)?;
 
-        bytes_code
+        GraphicsPipeline::create_shader_module(device, &shader_code)
+            .map_err(|result| EngineError::Shader { path: file_name.into(), message: result.to_string() })
     }
 
-    fn create_shader_module(device: &ash::Device, code: Vec<u8>) -> vk::ShaderModule{
+    fn create_shader_module(device: &ash::Device, code: &[u32]) -> Result<vk::ShaderModule, vk::Result>{
         let shader_module_create_info = vk::ShaderModuleCreateInfo{
             s_type: vk::StructureType::SHADER_MODULE_CREATE_INFO,
             p_next: ptr::null(),
             flags: vk::ShaderModuleCreateFlags::empty(),
-            code_size: code.len(),
-            p_code: code.as_ptr() as *const u32,
+            code_size: std::mem::size_of_val(code),
+            p_code: code.as_ptr(),
         };
 
         unsafe {
-            device
-                .create_shader_module(&shader_module_create_info, None)
-                .expect("Failed to create Shader Module!")
+            device.create_shader_module(&shader_module_create_info, None)
         }
     }
 }
\ No newline at end of file
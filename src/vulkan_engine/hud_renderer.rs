@@ -0,0 +1,243 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::item::inventory::{Inventory, HOTBAR_SLOTS};
+use crate::item::item_model::{self, ITEM_MODEL_DIRECTORY};
+use crate::item::item_stack::ItemStack;
+use crate::vulkan_engine::descriptors::Descriptors;
+use crate::vulkan_engine::draw_list::{DrawList, Material, Mesh};
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, PipelineDescription};
+use crate::vulkan_engine::memory_allocator::MemoryAllocator;
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::vulkan_engine::texture::{Texture, TextureDescription};
+use crate::vulkan_engine::texture_atlas::TextureAtlas;
+use crate::vulkan_engine::upload_manager::UploadManager;
+use crate::vulkan_engine::utilities::constants::TEXTURE_DIRECTORY;
+use crate::world::block::Block;
+
+// sizes in GUI pixels like vanilla's, scaled by the largest whole factor that fits the
+// GUI_WIDTH x GUI_HEIGHT screen into the window
+const GUI_WIDTH: u32 = 320;
+const GUI_HEIGHT: u32 = 240;
+const SLOT_SIZE: f32 = 20.0;
+const ICON_SIZE: f32 = 16.0;
+const FRAME_WIDTH: f32 = 2.0;
+// between the offhand slot and the hotbar
+const OFFHAND_GAP: f32 = 9.0;
+
+const BAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+const SLOT_COLOR: [f32; 4] = [0.55, 0.55, 0.55, 0.4];
+const FRAME_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.9];
+const ICON_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+// plain quads sample this so every quad uses the same pipeline
+const WHITE_TEXTURE: &str = "misc/white";
+const VERTICES_PER_QUAD: u32 = 6;
+
+/// Draws the hotbar over the frame: its slots, a frame around the selected one and the
+/// icons of the items in them. Every quad is a draw of its own, its corners, atlas
+/// rectangle and colour are passed as push constants.
+pub struct HudRenderer {
+    texture: Texture,
+    // atlas rectangles as [u0, v0, u1, v1] by item id
+    icons: HashMap<String, [f32; 4]>,
+    white: [f32; 4],
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+    pub descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+impl HudRenderer {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        descriptor_set_layout: vk::DescriptorSetLayout
+    ) -> EngineResult<HudRenderer> {
+        let texture_directory = Path::new(TEXTURE_DIRECTORY);
+        let mut icon_textures = item_model::icon_textures(Path::new(ITEM_MODEL_DIRECTORY), texture_directory)
+            .map_err(|error| EngineError::Asset { path: PathBuf::from(ITEM_MODEL_DIRECTORY), message: error.to_string() })?;
+        // most blocks have no item model in the pack
+        for block in Block::ALL.iter() {
+            if let Some(texture) = item_model::item_texture(*block) {
+                icon_textures.entry(block.name().to_string()).or_insert(format!("block/{}", texture));
+            }
+        }
+
+        let mut names: Vec<&str> = icon_textures.values().map(|texture| texture.as_str()).collect();
+        names.push(WHITE_TEXTURE);
+        names.sort_unstable();
+        names.dedup();
+        let atlas = TextureAtlas::build(texture_directory, &names)?;
+
+        let description = TextureDescription {
+            width: atlas.width,
+            height: atlas.height,
+            layer_count: 1,
+            address_mode: vk::SamplerAddressMode::CLAMP_TO_EDGE
+        };
+        let texture = Texture::from_pixels(device, allocator, uploads, &atlas.pixels, &description)?;
+
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<[[f32; 4]; 3]>() as u32,
+        }];
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(
+            device,
+            &[descriptor_set_layout],
+            &push_constant_ranges
+        )?;
+
+        Ok(HudRenderer {
+            texture,
+            icons: icon_textures.iter().map(|(item, texture)| (item.clone(), atlas.uv(texture))).collect(),
+            white: atlas.uv(WHITE_TEXTURE),
+            pipeline_layout,
+            pipeline: vk::Pipeline::null(),
+            descriptor_sets: vec![]
+        })
+    }
+
+    /// (Re)creates the per swapchain image descriptor sets
+    pub fn create_swapchain_resources(
+        &mut self,
+        device: &ash::Device,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        uniform_buffers: &Vec<vk::Buffer>
+    ) -> EngineResult<()> {
+        self.descriptor_sets = Descriptors::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            uniform_buffers,
+            &self.texture
+        )?;
+
+        Ok(())
+    }
+
+    pub fn create_pipeline(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<()> {
+        let description = PipelineDescription {
+            cull_mode: vk::CullModeFlags::NONE,
+            blend_enable: true,
+            ..PipelineDescription::new("hud.vert.spv", "hud.frag.spv")
+        };
+
+        self.pipeline = GraphicsPipeline::create_pipeline(
+            device,
+            render_pass,
+            self.pipeline_layout,
+            &description,
+            shaders,
+            pipeline_cache
+        )?;
+
+        Ok(())
+    }
+
+    pub fn destroy_pipeline(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        self.destroy_pipeline(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.texture.destroy(device, allocator);
+    }
+
+    /// The hotbar centred at the bottom of the screen, with the offhand slot left of it
+    /// while it holds something
+    pub fn draw(&self, draw_list: &mut DrawList, image_index: usize, extent: vk::Extent2D, inventory: &Inventory) {
+        let mut quads = Quads {
+            draw_list,
+            material: Material {
+                pipeline: self.pipeline,
+                pipeline_layout: self.pipeline_layout,
+                descriptor_set: self.descriptor_sets[image_index]
+            },
+            extent,
+            scale: (extent.width / GUI_WIDTH).min(extent.height / GUI_HEIGHT).max(1) as f32
+        };
+
+        let bar_width = SLOT_SIZE * HOTBAR_SLOTS as f32;
+        let left = (extent.width as f32 / quads.scale - bar_width) / 2.0;
+        let top = extent.height as f32 / quads.scale - SLOT_SIZE;
+
+        quads.push([left, top, left + bar_width, top + SLOT_SIZE], self.white, BAR_COLOR);
+        for (slot, stack) in inventory.main[..HOTBAR_SLOTS].iter().enumerate() {
+            self.draw_slot(&mut quads, left + slot as f32 * SLOT_SIZE, top, stack.as_ref());
+        }
+        if let Some(stack) = &inventory.offhand {
+            let offhand_left = left - OFFHAND_GAP - SLOT_SIZE;
+            quads.push([offhand_left, top, offhand_left + SLOT_SIZE, top + SLOT_SIZE], self.white, BAR_COLOR);
+            self.draw_slot(&mut quads, offhand_left, top, Some(stack));
+        }
+
+        // a frame over the selected slot's edges, each side a quad
+        let x = left + inventory.selected() as f32 * SLOT_SIZE;
+        let half = FRAME_WIDTH / 2.0;
+        let (x0, y0) = (x - half, top - half);
+        let (x1, y1) = (x + SLOT_SIZE + half, top + SLOT_SIZE + half);
+        for side in [
+            [x0, y0, x1, y0 + FRAME_WIDTH],
+            [x0, y1 - FRAME_WIDTH, x1, y1],
+            [x0, y0, x0 + FRAME_WIDTH, y1],
+            [x1 - FRAME_WIDTH, y0, x1, y1]
+        ] {
+            quads.push(side, self.white, FRAME_COLOR);
+        }
+    }
+
+    /// A slot with its top left corner at `x`, `y`. Items without an icon leave it empty.
+    fn draw_slot(&self, quads: &mut Quads, x: f32, y: f32, stack: Option<&ItemStack>) {
+        quads.push([x + 1.0, y + 1.0, x + SLOT_SIZE - 1.0, y + SLOT_SIZE - 1.0], self.white, SLOT_COLOR);
+
+        if let Some(uv) = stack.and_then(|stack| self.icons.get(&stack.item)) {
+            let inset = (SLOT_SIZE - ICON_SIZE) / 2.0;
+            quads.push([x + inset, y + inset, x + inset + ICON_SIZE, y + inset + ICON_SIZE], *uv, ICON_COLOR);
+        }
+    }
+}
+
+/// Turns rectangles in GUI pixels into draws
+struct Quads<'a> {
+    draw_list: &'a mut DrawList,
+    material: Material,
+    extent: vk::Extent2D,
+    // window pixels per GUI pixel
+    scale: f32
+}
+
+impl Quads<'_> {
+    fn push(&mut self, rect: [f32; 4], uv: [f32; 4], color: [f32; 4]) {
+        let to_clip = |value: f32, size: u32| value * self.scale / size as f32 * 2.0 - 1.0;
+        let constants = [
+            to_clip(rect[0], self.extent.width), to_clip(rect[1], self.extent.height),
+            to_clip(rect[2], self.extent.width), to_clip(rect[3], self.extent.height),
+            uv[0], uv[1], uv[2], uv[3],
+            color[0], color[1], color[2], color[3]
+        ];
+
+        let quad = Mesh {
+            vertex_buffer: vk::Buffer::null(),
+            first_vertex: 0,
+            vertex_count: VERTICES_PER_QUAD
+        };
+        let bytes: Vec<u8> = constants.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        self.draw_list.push_with_constants(self.material, quad, vk::ShaderStageFlags::VERTEX, &bytes);
+    }
+}
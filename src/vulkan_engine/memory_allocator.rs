@@ -0,0 +1,477 @@
+use ash::vk;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use std::collections::HashMap;
+use std::fmt;
+use std::ptr;
+
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+
+// size of the blocks requests are carved from, resources larger than a block get their own
+const DEFAULT_BLOCK_SIZE: vk::DeviceSize = 64 * 1024 * 1024;
+// small heaps (integrated GPUs, the host visible BAR window) get smaller blocks
+const MAX_BLOCKS_PER_HEAP: vk::DeviceSize = 8;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryUsage {
+    /// Only touched by the GPU, filled through staging buffers
+    GpuOnly,
+    /// Written by the CPU every frame, stays mapped
+    CpuToGpu
+}
+
+impl MemoryUsage {
+    fn required_flags(self) -> vk::MemoryPropertyFlags {
+        match self {
+            MemoryUsage::GpuOnly => vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            MemoryUsage::CpuToGpu => vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT
+        }
+    }
+}
+
+/// Buffers and optimally tiled images are kept in separate blocks, so neighbouring
+/// resources never have to respect `bufferImageGranularity`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceLayout {
+    Linear,
+    Optimal
+}
+
+/// First fit bookkeeping of one block, free ranges are kept sorted and merged
+pub struct BlockAllocator {
+    size: vk::DeviceSize,
+    // (offset, size), sorted by offset and never adjacent to each other
+    free_ranges: Vec<(vk::DeviceSize, vk::DeviceSize)>,
+    used: vk::DeviceSize,
+    allocation_count: usize
+}
+
+impl BlockAllocator {
+    pub fn new(size: vk::DeviceSize) -> BlockAllocator {
+        BlockAllocator {
+            size,
+            free_ranges: vec![(0, size)],
+            used: 0,
+            allocation_count: 0
+        }
+    }
+
+    /// Offset of `size` bytes aligned to `alignment`, the padding in front stays free
+    pub fn allocate(&mut self, size: vk::DeviceSize, alignment: vk::DeviceSize) -> Option<vk::DeviceSize> {
+        let alignment = alignment.max(1);
+
+        for i in 0..self.free_ranges.len() {
+            let (range_offset, range_size) = self.free_ranges[i];
+            let offset = range_offset.div_ceil(alignment) * alignment;
+            let padding = offset - range_offset;
+            if padding + size > range_size {
+                continue;
+            }
+
+            let remainder = range_size - padding - size;
+            self.free_ranges.remove(i);
+            if remainder > 0 {
+                self.free_ranges.insert(i, (offset + size, remainder));
+            }
+            if padding > 0 {
+                self.free_ranges.insert(i, (range_offset, padding));
+            }
+
+            self.used += size;
+            self.allocation_count += 1;
+            return Some(offset);
+        }
+
+        None
+    }
+
+    /// Returns a range handed out by `allocate`, merging it with free neighbours
+    pub fn free(&mut self, offset: vk::DeviceSize, size: vk::DeviceSize) {
+        let index = self.free_ranges
+            .iter()
+            .position(|&(range_offset, _)| range_offset > offset)
+            .unwrap_or(self.free_ranges.len());
+        self.free_ranges.insert(index, (offset, size));
+
+        if index + 1 < self.free_ranges.len() && offset + size == self.free_ranges[index + 1].0 {
+            self.free_ranges[index].1 += self.free_ranges[index + 1].1;
+            self.free_ranges.remove(index + 1);
+        }
+        if index > 0 && self.free_ranges[index - 1].0 + self.free_ranges[index - 1].1 == offset {
+            self.free_ranges[index - 1].1 += self.free_ranges[index].1;
+            self.free_ranges.remove(index);
+        }
+
+        self.used -= size;
+        self.allocation_count -= 1;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocation_count == 0
+    }
+
+    pub fn size(&self) -> vk::DeviceSize {
+        self.size
+    }
+
+    pub fn used(&self) -> vk::DeviceSize {
+        self.used
+    }
+
+    pub fn allocation_count(&self) -> usize {
+        self.allocation_count
+    }
+
+    pub fn largest_free_range(&self) -> vk::DeviceSize {
+        self.free_ranges.iter().map(|&(_, size)| size).max().unwrap_or(0)
+    }
+}
+
+/// A memory type that allows `type_filter` and has all `required` flags,
+/// preferring one that also has the `preferred` flags
+pub fn find_memory_type(
+    memory_properties: &vk::PhysicalDeviceMemoryProperties,
+    type_filter: u32,
+    required: vk::MemoryPropertyFlags,
+    preferred: vk::MemoryPropertyFlags
+) -> Option<u32> {
+    let types = &memory_properties.memory_types[..memory_properties.memory_type_count as usize];
+    let find = |flags: vk::MemoryPropertyFlags| types
+        .iter()
+        .enumerate()
+        .position(|(i, memory_type)| (type_filter & (1 << i)) != 0 && memory_type.property_flags.contains(flags))
+        .map(|i| i as u32);
+
+    find(required | preferred).or_else(|| find(required))
+}
+
+/// A piece of a block, bind resources at `memory` + `offset`
+pub struct Allocation {
+    pub memory: vk::DeviceMemory,
+    pub offset: vk::DeviceSize,
+    pub size: vk::DeviceSize,
+    mapped: *mut u8,
+    pool: PoolKey,
+    block_id: u64
+}
+
+impl Allocation {
+    /// Copies `data` to the start of a `CpuToGpu` allocation
+    pub fn write<T: Copy>(&self, data: &[T]) {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        assert!(!self.mapped.is_null(), "Allocation is not host visible");
+        assert!(size <= self.size, "Write of {} bytes exceeds the allocation of {} bytes", size, self.size);
+
+        unsafe {
+            (self.mapped as *mut T).copy_from_nonoverlapping(data.as_ptr(), data.len());
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+struct PoolKey {
+    memory_type: u32,
+    layout: ResourceLayout
+}
+
+struct MemoryBlock {
+    id: u64,
+    memory: vk::DeviceMemory,
+    mapped: *mut u8,
+    allocator: BlockAllocator
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PoolStats {
+    pub block_count: usize,
+    pub allocation_count: usize,
+    pub reserved_bytes: vk::DeviceSize,
+    pub used_bytes: vk::DeviceSize,
+    // biggest allocation that still fits without a new block
+    pub largest_free_bytes: vk::DeviceSize
+}
+
+pub struct MemoryStats {
+    pub total: PoolStats,
+    // (memory type, layout, stats) per pool that owns blocks
+    pub pools: Vec<(u32, ResourceLayout, PoolStats)>
+}
+
+impl fmt::Display for MemoryStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const MIB: f64 = 1024.0 * 1024.0;
+        write!(
+            f,
+            "GPU memory: {:.1} of {:.1} MiB used in {} allocations, {} blocks",
+            self.total.used_bytes as f64 / MIB,
+            self.total.reserved_bytes as f64 / MIB,
+            self.total.allocation_count,
+            self.total.block_count
+        )?;
+        for (memory_type, layout, stats) in self.pools.iter() {
+            write!(
+                f,
+                "\n\ttype {} {:?}: {:.1} of {:.1} MiB, {} allocations, {} blocks, largest free range {:.1} MiB",
+                memory_type,
+                layout,
+                stats.used_bytes as f64 / MIB,
+                stats.reserved_bytes as f64 / MIB,
+                stats.allocation_count,
+                stats.block_count,
+                stats.largest_free_bytes as f64 / MIB
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Hands out pieces of a few large `vk::DeviceMemory` blocks per memory type,
+/// staying far below `maxMemoryAllocationCount`
+pub struct MemoryAllocator {
+    device: ash::Device,
+    memory_properties: vk::PhysicalDeviceMemoryProperties,
+    pools: HashMap<PoolKey, Vec<MemoryBlock>>,
+    next_block_id: u64
+}
+
+impl MemoryAllocator {
+    pub fn new(instance: &ash::Instance, physical_device: vk::PhysicalDevice, device: &ash::Device) -> MemoryAllocator {
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        MemoryAllocator {
+            device: device.clone(),
+            memory_properties,
+            pools: HashMap::new(),
+            next_block_id: 0
+        }
+    }
+
+    pub fn allocate(
+        &mut self,
+        requirements: vk::MemoryRequirements,
+        usage: MemoryUsage,
+        layout: ResourceLayout
+    ) -> EngineResult<Allocation> {
+        let memory_type = find_memory_type(
+            &self.memory_properties,
+            requirements.memory_type_bits,
+            usage.required_flags(),
+            vk::MemoryPropertyFlags::empty()
+        ).ok_or(EngineError::NoMemoryType)?;
+        let pool_key = PoolKey { memory_type, layout };
+
+        let blocks = self.pools.entry(pool_key).or_default();
+        for block in blocks.iter_mut() {
+            if let Some(offset) = block.allocator.allocate(requirements.size, requirements.alignment) {
+                return Ok(MemoryAllocator::allocation(block, pool_key, offset, requirements.size));
+            }
+        }
+
+        let block_size = self.block_size(memory_type).max(requirements.size);
+        let mut block = self.create_block(memory_type, block_size)?;
+        let offset = block.allocator
+            .allocate(requirements.size, requirements.alignment)
+            .expect("A new block always fits its first allocation");
+        let allocation = MemoryAllocator::allocation(&block, pool_key, offset, requirements.size);
+
+        self.pools.entry(pool_key).or_default().push(block);
+        Ok(allocation)
+    }
+
+    fn allocation(block: &MemoryBlock, pool: PoolKey, offset: vk::DeviceSize, size: vk::DeviceSize) -> Allocation {
+        Allocation {
+            memory: block.memory,
+            offset,
+            size,
+            mapped: if block.mapped.is_null() { ptr::null_mut() } else { unsafe { block.mapped.add(offset as usize) } },
+            pool,
+            block_id: block.id
+        }
+    }
+
+    fn block_size(&self, memory_type: u32) -> vk::DeviceSize {
+        let heap_index = self.memory_properties.memory_types[memory_type as usize].heap_index;
+        let heap_size = self.memory_properties.memory_heaps[heap_index as usize].size;
+        DEFAULT_BLOCK_SIZE.min(heap_size / MAX_BLOCKS_PER_HEAP)
+    }
+
+    fn create_block(&mut self, memory_type: u32, size: vk::DeviceSize) -> EngineResult<MemoryBlock> {
+        let allocate_info = vk::MemoryAllocateInfo {
+            s_type: vk::StructureType::MEMORY_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            allocation_size: size,
+            memory_type_index: memory_type,
+        };
+        let memory = unsafe { self.device.allocate_memory(&allocate_info, None)? };
+
+        // host visible blocks stay mapped for their whole life, a block can only be mapped once
+        let property_flags = self.memory_properties.memory_types[memory_type as usize].property_flags;
+        let mapped = if property_flags.contains(vk::MemoryPropertyFlags::HOST_VISIBLE) {
+            let result = unsafe { self.device.map_memory(memory, 0, vk::WHOLE_SIZE, vk::MemoryMapFlags::empty()) };
+            match result {
+                Ok(pointer) => pointer as *mut u8,
+                Err(result) => {
+                    unsafe { self.device.free_memory(memory, None) };
+                    return Err(EngineError::Vulkan(result));
+                }
+            }
+        } else {
+            ptr::null_mut()
+        };
+
+        self.next_block_id += 1;
+        Ok(MemoryBlock {
+            id: self.next_block_id,
+            memory,
+            mapped,
+            allocator: BlockAllocator::new(size)
+        })
+    }
+
+    /// Returns the allocation to its block, empty blocks are released unless they are
+    /// the last one of their pool
+    pub fn free(&mut self, allocation: &Allocation) {
+        let blocks = self.pools
+            .get_mut(&allocation.pool)
+            .expect("Allocation freed twice or by another allocator");
+        let index = blocks
+            .iter()
+            .position(|block| block.id == allocation.block_id)
+            .expect("Allocation freed twice or by another allocator");
+
+        blocks[index].allocator.free(allocation.offset, allocation.size);
+        if blocks[index].allocator.is_empty() && blocks.len() > 1 {
+            let block = blocks.remove(index);
+            MemoryAllocator::destroy_block(&self.device, &block);
+        }
+    }
+
+    fn destroy_block(device: &ash::Device, block: &MemoryBlock) {
+        unsafe {
+            if !block.mapped.is_null() {
+                device.unmap_memory(block.memory);
+            }
+            device.free_memory(block.memory, None);
+        }
+    }
+
+    pub fn stats(&self) -> MemoryStats {
+        let mut total = PoolStats::default();
+        let mut pools = vec![];
+
+        for (key, blocks) in self.pools.iter() {
+            let mut stats = PoolStats::default();
+            for block in blocks.iter() {
+                stats.block_count += 1;
+                stats.allocation_count += block.allocator.allocation_count();
+                stats.reserved_bytes += block.allocator.size();
+                stats.used_bytes += block.allocator.used();
+                stats.largest_free_bytes = stats.largest_free_bytes.max(block.allocator.largest_free_range());
+            }
+            if stats.block_count == 0 {
+                continue;
+            }
+
+            total.block_count += stats.block_count;
+            total.allocation_count += stats.allocation_count;
+            total.reserved_bytes += stats.reserved_bytes;
+            total.used_bytes += stats.used_bytes;
+            total.largest_free_bytes = total.largest_free_bytes.max(stats.largest_free_bytes);
+            pools.push((key.memory_type, key.layout, stats));
+        }
+        pools.sort_by_key(|&(memory_type, layout, _)| (memory_type, layout == ResourceLayout::Optimal));
+
+        MemoryStats { total, pools }
+    }
+
+    /// Frees every block, all resources bound to them must be destroyed already
+    pub fn destroy(&mut self) {
+        for (_, blocks) in self.pools.drain() {
+            for block in blocks.iter() {
+                MemoryAllocator::destroy_block(&self.device, block);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allocations_are_aligned_and_padding_stays_free() {
+        let mut block = BlockAllocator::new(1024);
+
+        assert_eq!(block.allocate(10, 1), Some(0));
+        assert_eq!(block.allocate(100, 256), Some(256));
+        // the gap between 10 and 256 is still usable
+        assert_eq!(block.allocate(200, 16), Some(16));
+        assert_eq!(block.used(), 310);
+        assert_eq!(block.allocation_count(), 3);
+    }
+
+    #[test]
+    fn allocation_fails_when_nothing_fits() {
+        let mut block = BlockAllocator::new(256);
+
+        assert_eq!(block.allocate(200, 1), Some(0));
+        assert_eq!(block.allocate(100, 1), None);
+        assert_eq!(block.allocate(56, 1), Some(200));
+        assert_eq!(block.largest_free_range(), 0);
+    }
+
+    #[test]
+    fn freeing_merges_neighbouring_ranges() {
+        let mut block = BlockAllocator::new(300);
+        let a = block.allocate(100, 1).unwrap();
+        let b = block.allocate(100, 1).unwrap();
+        let c = block.allocate(100, 1).unwrap();
+
+        block.free(a, 100);
+        block.free(c, 100);
+        assert_eq!(block.largest_free_range(), 100);
+
+        block.free(b, 100);
+        assert!(block.is_empty());
+        assert_eq!(block.largest_free_range(), 300);
+        assert_eq!(block.allocate(300, 1), Some(0));
+    }
+
+    #[test]
+    fn freed_aligned_allocation_rejoins_its_padding() {
+        let mut block = BlockAllocator::new(512);
+        let small = block.allocate(4, 1).unwrap();
+        let aligned = block.allocate(128, 128).unwrap();
+        assert_eq!(aligned, 128);
+
+        block.free(aligned, 128);
+        block.free(small, 4);
+        assert_eq!(block.largest_free_range(), 512);
+        assert_eq!(block.used(), 0);
+    }
+
+    fn memory_properties(flags: &[vk::MemoryPropertyFlags]) -> vk::PhysicalDeviceMemoryProperties {
+        let mut properties = vk::PhysicalDeviceMemoryProperties {
+            memory_type_count: flags.len() as u32,
+            ..Default::default()
+        };
+        for (i, &property_flags) in flags.iter().enumerate() {
+            properties.memory_types[i] = vk::MemoryType { property_flags, heap_index: 0 };
+        }
+        properties
+    }
+
+    #[test]
+    fn memory_type_respects_filter_and_preference() {
+        let host = vk::MemoryPropertyFlags::HOST_VISIBLE | vk::MemoryPropertyFlags::HOST_COHERENT;
+        let properties = memory_properties(&[
+            vk::MemoryPropertyFlags::DEVICE_LOCAL,
+            host,
+            host | vk::MemoryPropertyFlags::DEVICE_LOCAL
+        ]);
+
+        assert_eq!(find_memory_type(&properties, 0b111, vk::MemoryPropertyFlags::DEVICE_LOCAL, vk::MemoryPropertyFlags::empty()), Some(0));
+        assert_eq!(find_memory_type(&properties, 0b111, host, vk::MemoryPropertyFlags::DEVICE_LOCAL), Some(2));
+        assert_eq!(find_memory_type(&properties, 0b011, host, vk::MemoryPropertyFlags::DEVICE_LOCAL), Some(1));
+        assert_eq!(find_memory_type(&properties, 0b001, host, vk::MemoryPropertyFlags::empty()), None);
+    }
+}
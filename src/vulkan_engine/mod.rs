@@ -1,6 +1,26 @@
 pub mod utilities;
+pub mod engine_error;
 pub mod vulkan_engine;
 mod setup;
 mod presentation;
 mod graphics_pipeline;
 mod buffers;
+mod memory_allocator;
+mod camera;
+mod descriptors;
+mod texture;
+mod sky_renderer;
+mod weather_renderer;
+mod depth_buffer;
+mod texture_atlas;
+mod chunk_mesher;
+mod chunk_renderer;
+mod upload_manager;
+mod shader_library;
+mod pipeline_cache;
+mod draw_list;
+mod culling;
+mod geometry_arena;
+mod outline_renderer;
+mod crack_renderer;
+mod hud_renderer;
@@ -0,0 +1,110 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use cgmath::Vector3;
+
+use crate::vulkan_engine::draw_list::{DrawList, Material, Mesh};
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, PipelineDescription};
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::world::raycast::{self, RaycastHit};
+
+// the outline sits this far outside the block so it doesn't fight with its faces
+const OUTLINE_OFFSET: f32 = 0.002;
+const VERTICES_PER_OUTLINE: u32 = 24;
+
+/// Draws the edges of the targeted block as lines, the box is passed as push constants
+/// and the vertex shader builds the edges from it
+pub struct OutlineRenderer {
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline
+}
+
+impl OutlineRenderer {
+    pub fn new(device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout) -> EngineResult<OutlineRenderer> {
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::VERTEX,
+            offset: 0,
+            size: std::mem::size_of::<[[f32; 4]; 2]>() as u32,
+        }];
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(
+            device,
+            &[descriptor_set_layout],
+            &push_constant_ranges
+        )?;
+
+        Ok(OutlineRenderer {
+            pipeline_layout,
+            pipeline: vk::Pipeline::null()
+        })
+    }
+
+    pub fn create_pipeline(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<()> {
+        let description = PipelineDescription {
+            topology: vk::PrimitiveTopology::LINE_LIST,
+            cull_mode: vk::CullModeFlags::NONE,
+            blend_enable: true,
+            depth_test: true,
+            ..PipelineDescription::new("outline.vert.spv", "outline.frag.spv")
+        };
+
+        self.pipeline = GraphicsPipeline::create_pipeline(
+            device,
+            render_pass,
+            self.pipeline_layout,
+            &description,
+            shaders,
+            pipeline_cache
+        )?;
+
+        Ok(())
+    }
+
+    pub fn destroy_pipeline(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        self.destroy_pipeline(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+
+    pub fn draw(&self, draw_list: &mut DrawList, descriptor_set: vk::DescriptorSet, target: Option<&RaycastHit>) {
+        let target = match target {
+            Some(target) => target,
+            None => return
+        };
+
+        let material = Material {
+            pipeline: self.pipeline,
+            pipeline_layout: self.pipeline_layout,
+            descriptor_set
+        };
+        let edges = Mesh {
+            vertex_buffer: vk::Buffer::null(),
+            first_vertex: 0,
+            vertex_count: VERTICES_PER_OUTLINE
+        };
+
+        let position = target.block_position;
+        let offset = Vector3::new(position.x as f32, position.y as f32, position.z as f32);
+        let (min, max) = raycast::outline_box(target.block);
+        let (min, max) = (min + offset, max + offset);
+        let outline_box: [f32; 8] = [
+            min.x - OUTLINE_OFFSET, min.y - OUTLINE_OFFSET, min.z - OUTLINE_OFFSET, 0.0,
+            max.x + OUTLINE_OFFSET, max.y + OUTLINE_OFFSET, max.z + OUTLINE_OFFSET, 0.0
+        ];
+
+        let bytes: Vec<u8> = outline_box.iter().flat_map(|value| value.to_ne_bytes()).collect();
+        draw_list.push_with_constants(material, edges, vk::ShaderStageFlags::VERTEX, &bytes);
+    }
+}
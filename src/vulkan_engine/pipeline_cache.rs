@@ -0,0 +1,82 @@
+use ash::vk;
+use ash::version::{DeviceV1_0, InstanceV1_0};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::settings_loader::settings_directory;
+use crate::vulkan_engine::engine_error::EngineResult;
+
+const PIPELINE_CACHE_FILE: &str = "pipeline_cache.bin";
+// header length, header version, vendor id, device id, pipeline cache UUID
+const HEADER_SIZE: usize = 16 + vk::UUID_SIZE;
+
+/// Compiled pipelines kept in the settings directory between launches
+pub struct PipelineCache;
+
+impl PipelineCache {
+    /// Starts from the saved data when it was written by the same driver and GPU,
+    /// otherwise from an empty cache
+    pub fn load(
+        instance: &ash::Instance,
+        physical_device: vk::PhysicalDevice,
+        device: &ash::Device
+    ) -> EngineResult<vk::PipelineCache> {
+        let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+
+        let initial_data = match std::fs::read(PipelineCache::path()) {
+            Ok(data) if PipelineCache::is_compatible(&data, &device_properties) => data,
+            Ok(_) => {
+                println!("Pipeline cache was written by another GPU or driver, starting empty");
+                vec![]
+            }
+            Err(_) => vec![]
+        };
+
+        let pipeline_cache_create_info = vk::PipelineCacheCreateInfo {
+            s_type: vk::StructureType::PIPELINE_CACHE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::PipelineCacheCreateFlags::empty(),
+            initial_data_size: initial_data.len(),
+            p_initial_data: initial_data.as_ptr() as *const std::os::raw::c_void,
+        };
+
+        let pipeline_cache = unsafe {
+            device.create_pipeline_cache(&pipeline_cache_create_info, None)?
+        };
+
+        Ok(pipeline_cache)
+    }
+
+    fn is_compatible(data: &[u8], device_properties: &vk::PhysicalDeviceProperties) -> bool {
+        if data.len() < HEADER_SIZE {
+            return false;
+        }
+
+        let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+
+        read_u32(0) as usize >= HEADER_SIZE
+            && read_u32(4) == vk::PipelineCacheHeaderVersion::ONE.as_raw() as u32
+            && read_u32(8) == device_properties.vendor_id
+            && read_u32(12) == device_properties.device_id
+            && data[16..HEADER_SIZE] == device_properties.pipeline_cache_uuid
+    }
+
+    /// Writes the cache to disk, a failure only costs compile time on the next launch
+    pub fn save(device: &ash::Device, pipeline_cache: vk::PipelineCache) {
+        let data = match unsafe { device.get_pipeline_cache_data(pipeline_cache) } {
+            Ok(data) => data,
+            Err(vk_result) => {
+                eprintln!("Unable to read the pipeline cache: {}", vk_result);
+                return;
+            }
+        };
+
+        if let Err(error) = std::fs::write(PipelineCache::path(), data) {
+            eprintln!("Unable to save the pipeline cache: {}", error);
+        }
+    }
+
+    fn path() -> PathBuf {
+        settings_directory().join(PIPELINE_CACHE_FILE)
+    }
+}
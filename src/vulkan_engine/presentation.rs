@@ -7,6 +7,7 @@ use std::ptr;
 use ash::{Instance, Device};
 use ash::vk::PhysicalDevice;
 use ash::version::DeviceV1_0;
+use crate::vulkan_engine::engine_error::EngineResult;
 
 
 pub struct Presentation {
@@ -28,7 +29,7 @@ impl Presentation{
         physical_device: PhysicalDevice,
         queue_family_indices: &QueueFamilyIndices,
         window: &winit::window::Window
-    ) -> Presentation{
+    ) -> EngineResult<Presentation>{
 
         let swapchain_struct = Presentation::create_swapchain(
             instance,
@@ -37,15 +38,15 @@ impl Presentation{
             window,
             &surface_struct,
             queue_family_indices
-        );
+        )?;
 
         let swapchain_imageviews = Presentation::create_image_views(
             device,
             swapchain_struct.swapchain_format,
             &swapchain_struct.swapchain_images,
-        );
+        )?;
 
-        Presentation{
+        Ok(Presentation{
             swapchain_loader: swapchain_struct.swapchain_loader,
             swapchain: swapchain_struct.swapchain,
             swapchain_images: swapchain_struct.swapchain_images,
@@ -53,14 +54,14 @@ impl Presentation{
             swapchain_extent: swapchain_struct.swapchain_extent,
             swapchain_imageviews,
             _device: device.clone(),
-        }
+        })
     }
 
     pub fn create_image_views(
         device: &ash::Device,
         surface_format: vk::Format,
         images: &Vec<vk::Image>,
-    ) -> Vec<vk::ImageView> {
+    ) -> EngineResult<Vec<vk::ImageView>> {
         let mut swapchain_imageviews = vec![];
 
         for &image in images.iter(){
@@ -87,15 +88,13 @@ impl Presentation{
             };
 
             let imageview = unsafe{
-                device
-                    .create_image_view(&imageview_create_info, None)
-                    .expect("Failed to create Image View")
+                device.create_image_view(&imageview_create_info, None)?
             };
 
             swapchain_imageviews.push(imageview)
         }
 
-        swapchain_imageviews
+        Ok(swapchain_imageviews)
     }
 
     pub fn create_swapchain(
@@ -105,10 +104,10 @@ impl Presentation{
         window: &winit::window::Window,
         surface_struct: &utilities::structures::SurfaceStruct,
         queue_family: &QueueFamilyIndices
-    ) -> utilities::structures::SwapChainStruct{
+    ) -> EngineResult<utilities::structures::SwapChainStruct>{
 
 
-        let swapchain_support = Presentation::query_swapchain_support(physical_device, surface_struct);
+        let swapchain_support = Presentation::query_swapchain_support(physical_device, surface_struct)?;
 
         let surface_format = Presentation::choose_swapchain_format(&swapchain_support.formats);
         let present_mode =
@@ -159,49 +158,42 @@ impl Presentation{
 
         let swapchain_loader = ash::extensions::khr::Swapchain::new(instance, device);
         let swapchain = unsafe {
-            swapchain_loader
-                .create_swapchain(&swapchain_create_info, None)
-                .expect("Failed to create Swapchain!")
+            swapchain_loader.create_swapchain(&swapchain_create_info, None)?
         };
 
         let swapchain_images = unsafe {
-            swapchain_loader
-                .get_swapchain_images(swapchain)
-                .expect("Failed to get Swapchain Images.")
+            swapchain_loader.get_swapchain_images(swapchain)?
         };
 
-        SwapChainStruct {
+        Ok(SwapChainStruct {
             swapchain_loader,
             swapchain,
             swapchain_format: surface_format.format,
             swapchain_extent: extent,
             swapchain_images,
-        }
+        })
     }
 
     fn query_swapchain_support(
         physical_device: vk::PhysicalDevice,
         surface_stuff: &SurfaceStruct,
-    ) -> SwapChainSupportDetail {
+    ) -> EngineResult<SwapChainSupportDetail> {
         unsafe {
             let capabilities = surface_stuff
                 .surface_loader
-                .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)
-                .expect("Failed to query for surface capabilities.");
+                .get_physical_device_surface_capabilities(physical_device, surface_stuff.surface)?;
             let formats = surface_stuff
                 .surface_loader
-                .get_physical_device_surface_formats(physical_device, surface_stuff.surface)
-                .expect("Failed to query for surface formats.");
+                .get_physical_device_surface_formats(physical_device, surface_stuff.surface)?;
             let present_modes = surface_stuff
                 .surface_loader
-                .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)
-                .expect("Failed to query for surface present mode.");
+                .get_physical_device_surface_present_modes(physical_device, surface_stuff.surface)?;
 
-            SwapChainSupportDetail {
+            Ok(SwapChainSupportDetail {
                 capabilities,
                 formats,
                 present_modes,
-            }
+            })
         }
     }
 
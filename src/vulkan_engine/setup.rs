@@ -7,6 +7,15 @@ use std::ffi::CString;
 use crate::vulkan_engine::utilities::debug::{check_validation_layer_support, populate_debug_messenger_create_info, ValidationInfo};
 use std::ptr;
 use std::os::raw::{c_void, c_char};
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::depth_buffer::DepthBuffer;
+use crate::vulkan_engine::utilities::constants::DEVICE_EXTENSIONS;
+
+// picks a GPU by index or name, takes precedence over the graphics settings
+const GPU_ENV_VAR: &str = "RUSTCRAFT_GPU";
+
+// (device type rank, device local memory), higher is better
+type DeviceScore = (u32, vk::DeviceSize);
 
 pub struct Setup {
     pub entry: ash::Entry,
@@ -21,25 +30,42 @@ pub struct Setup {
 
     pub graphics_queue: vk::Queue,
     pub present_queue: vk::Queue,
-    pub queue_family_indices: QueueFamilyIndices
+    // the graphics queue when there is no separate transfer family
+    pub transfer_queue: vk::Queue,
+    pub queue_family_indices: QueueFamilyIndices,
+    // several indirect draws per call, otherwise they are issued one at a time
+    pub multi_draw_indirect: bool
 }
 
 impl Setup {
-    pub fn new(window: &winit::window::Window) -> Setup {
-        let entry = ash::Entry::new().unwrap();
-        let instance = Setup::create_instance(&entry);
+    pub fn new(window: &winit::window::Window, gpu_setting: Option<&str>) -> EngineResult<Setup> {
+        let entry = ash::Entry::new()
+            .map_err(|error| EngineError::LoaderNotFound(error.to_string()))?;
+        let instance = Setup::create_instance(&entry)?;
 
         //setup debug messenger
-        let (debug_utils_loader, debug_messenger) = utilities::debug::setup_debug_utils(VALIDATION.is_enable, &entry, &instance);
+        let (debug_utils_loader, debug_messenger) = utilities::debug::setup_debug_utils(VALIDATION.is_enable, &entry, &instance)?;
 
         //create surface
-        let surface_struct = Setup::create_surface(&entry, &instance, &window);
+        let surface_struct = Setup::create_surface(&entry, &instance, &window)?;
 
         //pick physical device
-        let physical_device = Setup::pick_physical_device(&instance, &surface_struct);
+        let gpu_preference = std::env::var(GPU_ENV_VAR)
+            .ok()
+            .filter(|preference| !preference.trim().is_empty())
+            .or_else(|| gpu_setting.map(String::from));
+        let physical_device = Setup::pick_physical_device(&instance, &surface_struct, gpu_preference.as_deref())?;
 
         //create logical device
-        let (logical_device, queue_family_indices) = Setup::create_logical_device(&instance, physical_device, &VALIDATION, &surface_struct);
+        let multi_draw_indirect = unsafe { instance.get_physical_device_features(physical_device) }
+            .multi_draw_indirect == vk::TRUE;
+        let (logical_device, queue_family_indices) = Setup::create_logical_device(
+            &instance,
+            physical_device,
+            &VALIDATION,
+            &surface_struct,
+            multi_draw_indirect
+        )?;
 
         let graphics_queue =
             unsafe { logical_device.get_device_queue(queue_family_indices.graphics_family.unwrap(), 0) };
@@ -47,7 +73,13 @@ impl Setup {
         let present_queue =
             unsafe { logical_device.get_device_queue(queue_family_indices.present_family.unwrap(), 0) };
 
-        Setup {
+        let transfer_family = queue_family_indices.transfer_family
+            .or(queue_family_indices.graphics_family)
+            .unwrap();
+        let transfer_queue =
+            unsafe { logical_device.get_device_queue(transfer_family, 0) };
+
+        Ok(Setup {
             entry,
             instance,
             surface_loader: surface_struct.surface_loader,
@@ -60,13 +92,15 @@ impl Setup {
 
             graphics_queue,
             present_queue,
-            queue_family_indices
-        }
+            transfer_queue,
+            queue_family_indices,
+            multi_draw_indirect
+        })
     }
 
-    fn create_instance(entry: &ash::Entry) -> ash::Instance{
-        if utilities::constants::VALIDATION.is_enable && check_validation_layer_support(entry) == false{
-            panic!("Validation layers not found")
+    fn create_instance(entry: &ash::Entry) -> EngineResult<ash::Instance>{
+        if utilities::constants::VALIDATION.is_enable && check_validation_layer_support(entry)? == false{
+            return Err(EngineError::MissingValidationLayers)
         }
 
         let app_name = CString::new("Minecraft").unwrap();
@@ -119,12 +153,10 @@ impl Setup {
         };
 
         let instance: ash::Instance = unsafe {
-            entry
-                .create_instance(&create_info, None)
-                .expect("Failed to create instance!")
+            entry.create_instance(&create_info, None)?
         };
 
-        instance
+        Ok(instance)
     }
 
     fn create_logical_device(
@@ -132,13 +164,16 @@ impl Setup {
         physical_device: vk::PhysicalDevice,
         validation: &ValidationInfo,
         surface_struct: &utilities::structures::SurfaceStruct,
-    ) -> (ash::Device, QueueFamilyIndices) {
+        multi_draw_indirect: bool
+    ) -> EngineResult<(ash::Device, QueueFamilyIndices)> {
         let indices = Setup::find_queue_family(instance, physical_device, surface_struct);
 
         use std::collections::HashSet;
         let mut unique_queue_families = HashSet::new();
         unique_queue_families.insert(indices.graphics_family.unwrap());
         unique_queue_families.insert(indices.present_family.unwrap());
+        unique_queue_families.extend(indices.transfer_family);
+        unique_queue_families.extend(indices.compute_family);
 
         let queue_priorities = [1.0_f32];
         let mut queue_create_infos = vec![];
@@ -155,7 +190,8 @@ impl Setup {
         }
 
         let physical_device_features = vk::PhysicalDeviceFeatures {
-            ..Default::default() // default just enable no feature.
+            multi_draw_indirect: multi_draw_indirect as vk::Bool32,
+            ..Default::default()
         };
 
         let required_validation_layer_raw_names: Vec<CString> = validation
@@ -190,39 +226,40 @@ impl Setup {
         };
 
         let device: ash::Device = unsafe {
-            instance
-                .create_device(physical_device, &device_create_info, None)
-                .expect("Failed to create logical device!")
+            instance.create_device(physical_device, &device_create_info, None)?
         };
 
-        (device, indices)
+        Ok((device, indices))
     }
 
     fn create_surface(
         entry: &ash::Entry,
         instance: &ash::Instance,
         window: &winit::window::Window
-    ) -> utilities::structures::SurfaceStruct {
+    ) -> EngineResult<utilities::structures::SurfaceStruct> {
         let surface = unsafe {
-            utilities::platforms::create_surface(entry, instance, window)
-                .expect("Failed to create surface")
+            utilities::platforms::create_surface(entry, instance, window)?
         };
 
         let surface_loader = ash::extensions::khr::Surface::new(entry, instance);
 
-        utilities::structures::SurfaceStruct{
+        Ok(utilities::structures::SurfaceStruct{
             surface,
             surface_loader,
             screen_width: window.inner_size().width,
             screen_height: window.inner_size().height
-        }
+        })
     }
 
-    fn pick_physical_device(instance: &ash::Instance, surface_struct: &utilities::structures::SurfaceStruct) -> vk::PhysicalDevice{
+    /// The preferred GPU when it can render to the window, otherwise the best rated one.
+    /// Why a preferred GPU was passed over is printed.
+    fn pick_physical_device(
+        instance: &ash::Instance,
+        surface_struct: &utilities::structures::SurfaceStruct,
+        gpu_preference: Option<&str>
+    ) -> EngineResult<vk::PhysicalDevice>{
         let physical_devices = unsafe {
-            instance
-                .enumerate_physical_devices()
-                .expect("Failed to enumerate physical devices")
+            instance.enumerate_physical_devices()?
         };
 
         println!(
@@ -230,80 +267,175 @@ impl Setup {
             physical_devices.len()
         );
 
-        let mut result = None;
+        let scores: Vec<Result<DeviceScore, &str>> = physical_devices
+            .iter()
+            .map(|&physical_device| Setup::rate_physical_device(instance, physical_device, surface_struct))
+            .collect();
 
-        for &physical_device in physical_devices.iter() {
-            if Setup::is_physical_device_suitable(instance, physical_device, surface_struct) {
-                if result.is_none() {
-                    result = Some(physical_device)
-                }
+        for (i, (&physical_device, score)) in physical_devices.iter().zip(scores.iter()).enumerate() {
+            match score {
+                Ok(_) => println!("\t[{}] {}", i, Setup::describe_physical_device(instance, physical_device)),
+                Err(reason) => println!("\t[{}] {}, unsuitable: {}", i, Setup::describe_physical_device(instance, physical_device), reason)
             }
         }
 
-        match result {
-            None => panic!("Failed to find a suitable GPU!"),
-            Some(physical_device) => physical_device,
+        if let Some(preference) = gpu_preference {
+            match Setup::find_preferred_device(instance, &physical_devices, preference) {
+                Some(i) => match &scores[i] {
+                    Ok(_) => return Ok(physical_devices[i]),
+                    Err(reason) => println!("GPU {} was selected but {}, picking the best one instead", i, reason)
+                },
+                None => println!("No GPU matches '{}', picking the best one instead", preference)
+            }
         }
+
+        // reversed so the first of equally rated devices wins
+        let best = physical_devices
+            .iter()
+            .zip(scores)
+            .filter_map(|(&physical_device, score)| score.ok().map(|score| (score, physical_device)))
+            .rev()
+            .max_by_key(|&(score, _)| score);
+
+        best.map(|(_, physical_device)| physical_device).ok_or(EngineError::NoSuitableGpu)
     }
 
-    fn is_physical_device_suitable(
+    /// A GPU index as printed by `--list-gpus`, or part of its name
+    fn find_preferred_device(
+        instance: &ash::Instance,
+        physical_devices: &[vk::PhysicalDevice],
+        preference: &str
+    ) -> Option<usize> {
+        if let Ok(index) = preference.trim().parse::<usize>() {
+            return if index < physical_devices.len() { Some(index) } else { None };
+        }
+
+        let preference = preference.to_lowercase();
+        physical_devices.iter().position(|&physical_device| {
+            let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+            utilities::tools::vk_to_string(&device_properties.device_name)
+                .to_lowercase()
+                .contains(&preference)
+        })
+    }
+
+    /// Ranks by device type first and the size of its device local memory second,
+    /// or explains why the device can't render to the surface
+    fn rate_physical_device(
         instance: &ash::Instance,
         physical_device: vk::PhysicalDevice,
         surface_struct: &utilities::structures::SurfaceStruct
-    ) -> bool {
+    ) -> Result<DeviceScore, &'static str> {
+        if !Setup::find_queue_family(instance, physical_device, surface_struct).is_complete() {
+            return Err("no graphics or present queue");
+        }
+        if !Setup::supports_device_extensions(instance, physical_device) {
+            return Err("swapchain extension missing");
+        }
+
+        let (formats, present_modes) = unsafe {
+            (
+                surface_struct.surface_loader
+                    .get_physical_device_surface_formats(physical_device, surface_struct.surface)
+                    .unwrap_or_default(),
+                surface_struct.surface_loader
+                    .get_physical_device_surface_present_modes(physical_device, surface_struct.surface)
+                    .unwrap_or_default()
+            )
+        };
+        if formats.is_empty() || present_modes.is_empty() {
+            return Err("can't present to the window");
+        }
+        if DepthBuffer::find_depth_format(instance, physical_device).is_err() {
+            return Err("no depth buffer format");
+        }
+
+        let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
+        let type_rank = match device_properties.device_type {
+            vk::PhysicalDeviceType::DISCRETE_GPU => 4,
+            vk::PhysicalDeviceType::INTEGRATED_GPU => 3,
+            vk::PhysicalDeviceType::VIRTUAL_GPU => 2,
+            vk::PhysicalDeviceType::CPU => 1,
+            _ => 0,
+        };
+
+        Ok((type_rank, Setup::device_local_memory(instance, physical_device)))
+    }
+
+    fn supports_device_extensions(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> bool {
+        let available_extensions = unsafe {
+            instance
+                .enumerate_device_extension_properties(physical_device)
+                .unwrap_or_default()
+        };
+
+        DEVICE_EXTENSIONS.names.iter().all(|&required| {
+            available_extensions
+                .iter()
+                .any(|extension| utilities::tools::vk_to_string(&extension.extension_name) == required)
+        })
+    }
+
+    fn device_local_memory(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> vk::DeviceSize {
+        let memory_properties = unsafe { instance.get_physical_device_memory_properties(physical_device) };
+
+        memory_properties.memory_heaps[..memory_properties.memory_heap_count as usize]
+            .iter()
+            .filter(|heap| heap.flags.contains(vk::MemoryHeapFlags::DEVICE_LOCAL))
+            .map(|heap| heap.size)
+            .sum()
+    }
+
+    fn describe_physical_device(instance: &ash::Instance, physical_device: vk::PhysicalDevice) -> String {
         let device_properties = unsafe { instance.get_physical_device_properties(physical_device) };
-        let device_features = unsafe { instance.get_physical_device_features(physical_device) };
-        let device_queue_families =
-            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
         let device_type = match device_properties.device_type {
             vk::PhysicalDeviceType::CPU => "Cpu",
             vk::PhysicalDeviceType::INTEGRATED_GPU => "Integrated GPU",
             vk::PhysicalDeviceType::DISCRETE_GPU => "Discrete GPU",
             vk::PhysicalDeviceType::VIRTUAL_GPU => "Virtual GPU",
-            vk::PhysicalDeviceType::OTHER => "Unknown",
-            _ => panic!(),
+            _ => "Unknown",
         };
 
-        let device_name = utilities::tools::vk_to_string(&device_properties.device_name);
-        println!(
-            "\tDevice Name: {}, id: {}, type: {}",
-            device_name, device_properties.device_id, device_type
-        );
+        format!(
+            "{} ({}, {} MiB, API Version {}.{}.{})",
+            utilities::tools::vk_to_string(&device_properties.device_name),
+            device_type,
+            Setup::device_local_memory(instance, physical_device) / (1024 * 1024),
+            vk_version_major!(device_properties.api_version),
+            vk_version_minor!(device_properties.api_version),
+            vk_version_patch!(device_properties.api_version)
+        )
+    }
 
-        let major_version = vk_version_major!(device_properties.api_version);
-        let minor_version = vk_version_minor!(device_properties.api_version);
-        let patch_version = vk_version_patch!(device_properties.api_version);
+    /// Prints every GPU with the index `RUSTCRAFT_GPU` and the graphics settings accept,
+    /// no window is needed
+    pub fn list_physical_devices() -> EngineResult<()> {
+        let entry = ash::Entry::new()
+            .map_err(|error| EngineError::LoaderNotFound(error.to_string()))?;
+        let instance = Setup::create_instance(&entry)?;
+
+        let physical_devices = unsafe { instance.enumerate_physical_devices() };
+        if let Ok(physical_devices) = &physical_devices {
+            println!("{} devices (GPU) found with vulkan support:", physical_devices.len());
+            for (i, &physical_device) in physical_devices.iter().enumerate() {
+                println!("[{}] {}", i, Setup::describe_physical_device(&instance, physical_device));
+                Setup::print_queue_families(&instance, physical_device);
+            }
+        }
 
-        println!(
-            "\tAPI Version: {}.{}.{}",
-            major_version, minor_version, patch_version
-        );
+        unsafe { instance.destroy_instance(None) };
+        physical_devices.map(|_| ()).map_err(EngineError::Vulkan)
+    }
+
+    fn print_queue_families(instance: &ash::Instance, physical_device: vk::PhysicalDevice) {
+        let device_queue_families =
+            unsafe { instance.get_physical_device_queue_family_properties(physical_device) };
 
         println!("\tSupport Queue Family: {}", device_queue_families.len());
         println!("\t\tQueue Count | Graphics, Compute, Transfer, Sparse Binding");
         for queue_family in device_queue_families.iter() {
-            let is_graphics_support = if queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-            {
-                "support"
-            } else {
-                "unsupport"
-            };
-            let is_compute_support = if queue_family.queue_flags.contains(vk::QueueFlags::COMPUTE) {
-                "support"
-            } else {
-                "unsupport"
-            };
-            let is_transfer_support = if queue_family.queue_flags.contains(vk::QueueFlags::TRANSFER)
-            {
-                "support"
-            } else {
-                "unsupport"
-            };
-            let is_sparse_support = if queue_family
-                .queue_flags
-                .contains(vk::QueueFlags::SPARSE_BINDING)
-            {
+            let support = |flag: vk::QueueFlags| if queue_family.queue_flags.contains(flag) {
                 "support"
             } else {
                 "unsupport"
@@ -312,25 +444,12 @@ impl Setup {
             println!(
                 "\t\t{}\t    | {},  {},  {},  {}",
                 queue_family.queue_count,
-                is_graphics_support,
-                is_compute_support,
-                is_transfer_support,
-                is_sparse_support
+                support(vk::QueueFlags::GRAPHICS),
+                support(vk::QueueFlags::COMPUTE),
+                support(vk::QueueFlags::TRANSFER),
+                support(vk::QueueFlags::SPARSE_BINDING)
             );
         }
-
-        println!(
-            "\tGeometry Shader support: {}",
-            if device_features.geometry_shader == 1 {
-                "Support"
-            } else {
-                "Unsupport"
-            }
-        );
-
-        let indices = Setup::find_queue_family(instance, physical_device, surface_struct);
-
-        return indices.is_complete();
     }
 
     fn find_queue_family(
@@ -343,11 +462,14 @@ impl Setup {
 
         let mut queue_family_indices = QueueFamilyIndices::new();
 
-        let mut index = 0;
-        for queue_family in queue_families.iter() {
-            if queue_family.queue_count > 0
-                && queue_family.queue_flags.contains(vk::QueueFlags::GRAPHICS)
-            {
+        for (index, queue_family) in queue_families.iter().enumerate() {
+            let index = index as u32;
+            let flags = queue_family.queue_flags;
+            if queue_family.queue_count == 0 {
+                continue;
+            }
+
+            if flags.contains(vk::QueueFlags::GRAPHICS) && queue_family_indices.graphics_family.is_none() {
                 queue_family_indices.graphics_family = Some(index);
             }
 
@@ -355,21 +477,31 @@ impl Setup {
                 surface_struct.surface_loader
                     .get_physical_device_surface_support(
                         physical_device,
-                        index as u32,
+                        index,
                         surface_struct.surface,
                     )
             };
-            if queue_family.queue_count > 0 && is_present_support {
+            // presenting from the graphics family avoids sharing the swapchain images
+            if is_present_support
+                && (queue_family_indices.present_family.is_none() || queue_family_indices.graphics_family == Some(index))
+            {
                 queue_family_indices.present_family = Some(index);
             }
 
-            if queue_family_indices.is_complete() {
-                break;
+            if !flags.contains(vk::QueueFlags::GRAPHICS) {
+                // a transfer only family is usually backed by the copy engines
+                let is_transfer_only = !flags.contains(vk::QueueFlags::COMPUTE);
+                if flags.contains(vk::QueueFlags::TRANSFER)
+                    && (queue_family_indices.transfer_family.is_none() || is_transfer_only)
+                {
+                    queue_family_indices.transfer_family = Some(index);
+                }
+                if flags.contains(vk::QueueFlags::COMPUTE) && queue_family_indices.compute_family.is_none() {
+                    queue_family_indices.compute_family = Some(index);
+                }
             }
-
-            index += 1;
         }
 
         queue_family_indices
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+
+// GLSL sources, watched for changes in debug builds
+const SHADER_DIRECTORY: &str = "src/vulkan_engine/shaders";
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// SPIR-V compiled by build.rs, `(file name, bytes)`
+const EMBEDDED_SHADERS: &[(&str, &[u8])] = include!(concat!(env!("OUT_DIR"), "/embedded_shaders.rs"));
+
+/// SPIR-V for the pipelines. Release builds only use the shaders embedded at build time,
+/// debug builds recompile edited sources with glslc so the pipelines can be rebuilt live.
+pub struct ShaderLibrary {
+    reloaded: HashMap<String, Vec<u8>>,
+    modified_times: HashMap<PathBuf, SystemTime>,
+    last_poll: Instant
+}
+
+impl ShaderLibrary {
+    pub fn new() -> ShaderLibrary {
+        let mut shader_library = ShaderLibrary {
+            reloaded: HashMap::new(),
+            modified_times: HashMap::new(),
+            last_poll: Instant::now()
+        };
+        if cfg!(debug_assertions) {
+            shader_library.modified_times = ShaderLibrary::source_modified_times();
+        }
+
+        shader_library
+    }
+
+    /// The SPIR-V words of e.g. `sky.vert.spv`
+    pub fn get(&self, file_name: &str) -> EngineResult<Vec<u32>> {
+        let shader_path = Path::new(SHADER_DIRECTORY).join(file_name);
+        let shader_code = match self.reloaded.get(file_name) {
+            Some(code) => &code[..],
+            None => EMBEDDED_SHADERS
+                .iter()
+                .find(|(name, _)| *name == file_name)
+                .map(|(_, code)| *code)
+                .ok_or_else(|| EngineError::Shader { path: shader_path.clone(), message: "not embedded".to_string() })?
+        };
+
+        if shader_code.is_empty() {
+            return Err(EngineError::Shader {
+                path: shader_path,
+                message: "not compiled, glslc was missing during the build".to_string()
+            });
+        }
+        // SPIR-V is a stream of 32 bit words starting with the magic number
+        if shader_code.len() % 4 != 0
+            || u32::from_le_bytes([shader_code[0], shader_code[1], shader_code[2], shader_code[3]]) != 0x0723_0203 {
+            return Err(EngineError::Shader { path: shader_path, message: "not a SPIR-V binary".to_string() });
+        }
+
+        Ok(shader_code
+            .chunks_exact(4)
+            .map(|word| u32::from_le_bytes([word[0], word[1], word[2], word[3]]))
+            .collect())
+    }
+
+    /// Recompiles the sources edited since the last call, true when the pipelines need
+    /// to be rebuilt. A source that fails to compile keeps its previous SPIR-V.
+    pub fn reload_changed(&mut self) -> bool {
+        if !cfg!(debug_assertions) || self.last_poll.elapsed() < POLL_INTERVAL {
+            return false;
+        }
+        self.last_poll = Instant::now();
+
+        let mut is_reloaded = false;
+        for (path, modified_time) in ShaderLibrary::source_modified_times() {
+            if self.modified_times.get(&path) == Some(&modified_time) {
+                continue;
+            }
+            self.modified_times.insert(path.clone(), modified_time);
+
+            match ShaderLibrary::compile(&path) {
+                Ok(code) => {
+                    println!("Reloaded shader {}", path.display());
+                    let spv_name = format!("{}.spv", path.file_name().unwrap().to_string_lossy());
+                    self.reloaded.insert(spv_name, code);
+                    is_reloaded = true;
+                }
+                Err(message) => eprintln!("Unable to compile {}: {}", path.display(), message)
+            }
+        }
+
+        is_reloaded
+    }
+
+    fn source_modified_times() -> HashMap<PathBuf, SystemTime> {
+        let entries = match std::fs::read_dir(SHADER_DIRECTORY) {
+            Ok(entries) => entries,
+            Err(_) => return HashMap::new()
+        };
+
+        entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| matches!(path.extension().and_then(|extension| extension.to_str()), Some("vert") | Some("frag") | Some("comp")))
+            .filter_map(|path| {
+                let modified_time = std::fs::metadata(&path).and_then(|metadata| metadata.modified()).ok()?;
+                Some((path, modified_time))
+            })
+            .collect()
+    }
+
+    fn compile(source: &Path) -> Result<Vec<u8>, String> {
+        let glslc = match std::env::var_os("VULKAN_SDK") {
+            Some(sdk) => Path::new(&sdk).join("bin").join("glslc"),
+            None => PathBuf::from("glslc")
+        };
+
+        // "-o -" writes the SPIR-V to stdout
+        let output = Command::new(glslc)
+            .arg(source)
+            .arg("-o")
+            .arg("-")
+            .output()
+            .map_err(|error| format!("glslc could not be started ({})", error))?;
+
+        if output.status.success() {
+            Ok(output.stdout)
+        } else {
+            Err(String::from_utf8_lossy(&output.stderr).into_owned())
+        }
+    }
+}
@@ -0,0 +1,15 @@
+#version 450
+
+layout(binding = 1) uniform sampler2DArray cracks;
+
+layout(location = 0) in vec3 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 texel = texture(cracks, fragTexCoord);
+    if (texel.a < 0.01) {
+        discard;
+    }
+    outColor = texel;
+}
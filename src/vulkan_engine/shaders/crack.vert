@@ -0,0 +1,43 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+// w of box_max is the crack stage, the layer of the texture array
+layout(push_constant) uniform PushConstants {
+    vec4 box_min;
+    vec4 box_max;
+} push;
+
+layout(location = 0) out vec3 fragTexCoord;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// the four corners of every face of a unit cube, up, down, north, south, east, west
+vec3 corners[24] = vec3[](
+vec3(0.0, 1.0, 0.0), vec3(0.0, 1.0, 1.0), vec3(1.0, 1.0, 1.0), vec3(1.0, 1.0, 0.0),
+vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 0.0, 1.0),
+vec3(1.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0), vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0),
+vec3(0.0, 1.0, 1.0), vec3(0.0, 0.0, 1.0), vec3(1.0, 0.0, 1.0), vec3(1.0, 1.0, 1.0),
+vec3(1.0, 1.0, 1.0), vec3(1.0, 0.0, 1.0), vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0),
+vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 0.0), vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 1.0)
+);
+
+vec2 tex_coords[4] = vec2[](
+vec2(0.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0), vec2(1.0, 0.0)
+);
+
+// two triangles per face
+int quad_indices[6] = int[](0, 1, 2, 2, 3, 0);
+
+void main() {
+    int corner = gl_VertexIndex / 6 * 4 + quad_indices[gl_VertexIndex % 6];
+    vec3 position = mix(push.box_min.xyz, push.box_max.xyz, corners[corner]);
+    gl_Position = ubo.proj * ubo.view * vec4(position, 1.0);
+    fragTexCoord = vec3(tex_coords[corner % 4], push.box_max.w);
+}
@@ -0,0 +1,16 @@
+#version 450
+
+layout(binding = 1) uniform sampler2D icons;
+
+layout(location = 0) in vec2 fragTexCoord;
+layout(location = 1) in vec4 fragColor;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 texel = texture(icons, fragTexCoord) * fragColor;
+    if (texel.a < 0.01) {
+        discard;
+    }
+    outColor = texel;
+}
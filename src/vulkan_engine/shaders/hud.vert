@@ -0,0 +1,28 @@
+#version 450
+
+// the quad's corners and texture corners as (x0, y0, x1, y1), in clip space and atlas UVs
+layout(push_constant) uniform PushConstants {
+    vec4 rect;
+    vec4 uv;
+    vec4 color;
+} push;
+
+layout(location = 0) out vec2 fragTexCoord;
+layout(location = 1) out vec4 fragColor;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// two triangles
+vec2 corners[6] = vec2[](
+vec2(0.0, 0.0), vec2(0.0, 1.0), vec2(1.0, 1.0),
+vec2(1.0, 1.0), vec2(1.0, 0.0), vec2(0.0, 0.0)
+);
+
+void main() {
+    vec2 corner = corners[gl_VertexIndex];
+    gl_Position = vec4(mix(push.rect.xy, push.rect.zw, corner), 0.0, 1.0);
+    fragTexCoord = mix(push.uv.xy, push.uv.zw, corner);
+    fragColor = push.color;
+}
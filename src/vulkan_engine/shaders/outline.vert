@@ -0,0 +1,37 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+} ubo;
+
+layout(push_constant) uniform PushConstants {
+    vec4 box_min;
+    vec4 box_max;
+} push;
+
+out gl_PerVertex {
+    vec4 gl_Position;
+};
+
+// the twelve edges of a unit cube as a line list
+vec3 corners[24] = vec3[](
+vec3(0.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0),
+vec3(1.0, 0.0, 0.0), vec3(1.0, 0.0, 1.0),
+vec3(1.0, 0.0, 1.0), vec3(0.0, 0.0, 1.0),
+vec3(0.0, 0.0, 1.0), vec3(0.0, 0.0, 0.0),
+vec3(0.0, 1.0, 0.0), vec3(1.0, 1.0, 0.0),
+vec3(1.0, 1.0, 0.0), vec3(1.0, 1.0, 1.0),
+vec3(1.0, 1.0, 1.0), vec3(0.0, 1.0, 1.0),
+vec3(0.0, 1.0, 1.0), vec3(0.0, 1.0, 0.0),
+vec3(0.0, 0.0, 0.0), vec3(0.0, 1.0, 0.0),
+vec3(1.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0),
+vec3(1.0, 0.0, 1.0), vec3(1.0, 1.0, 1.0),
+vec3(0.0, 0.0, 1.0), vec3(0.0, 1.0, 1.0)
+);
+
+void main() {
+    vec3 position = mix(push.box_min.xyz, push.box_max.xyz, corners[gl_VertexIndex]);
+    gl_Position = ubo.proj * ubo.view * vec4(position, 1.0);
+}
@@ -1,10 +1,15 @@
 #version 450
 
-layout(location = 0) in vec3 fragColor;
+layout(binding = 1) uniform sampler2D blockAtlas;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in float fragBrightness;
 
 layout(location = 0) out vec4 outColor;
 
 void main() {
-    outColor = vec4(fragColor, 1.0);
+    vec4 texel = texture(blockAtlas, fragTexCoord);
+    // the vertex colour carries the biome tint and face shading
+    outColor = vec4(texel.rgb * fragColor.rgb * fragBrightness, texel.a * fragColor.a);
 }
-
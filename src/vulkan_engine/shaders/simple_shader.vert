@@ -1,24 +1,38 @@
 #version 450
 
-out gl_PerVertex {
-    vec4 gl_Position;
-};
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec4 sky_color;
+    vec4 weather;
+    vec4 lighting;
+} ubo;
 
-layout(location = 0) out vec3 fragColor;
+layout(location = 0) in vec4 inPosition;
+layout(location = 1) in vec4 inColor;
+layout(location = 2) in vec2 inTexCoord;
+layout(location = 3) in vec2 inLight;
+// frame count, seconds per frame, distance along u between frames
+layout(location = 4) in vec3 inAnimation;
 
-vec2 positions[3] = vec2[](
-vec2(0.0, -0.5),
-vec2(0.5, 0.5),
-vec2(-0.5, 0.5)
-);
+layout(location = 0) out vec4 fragColor;
+layout(location = 1) out vec2 fragTexCoord;
+layout(location = 2) out float fragBrightness;
 
-vec3 colors[3] = vec3[](
-vec3(1.0, 0.0, 0.0),
-vec3(0.0, 1.0, 0.0),
-vec3(0.0, 0.0, 1.0)
-);
+out gl_PerVertex {
+    vec4 gl_Position;
+};
 
 void main() {
-    gl_Position = vec4(positions[gl_VertexIndex], 0.0, 1.0);
-    fragColor = colors[gl_VertexIndex];
-}
\ No newline at end of file
+    gl_Position = ubo.proj * ubo.view * ubo.model * inPosition;
+    fragColor = inColor;
+    float frame = mod(floor(ubo.weather.x / inAnimation.y), inAnimation.x);
+    fragTexCoord = inTexCoord + vec2(frame * inAnimation.z, 0.0);
+
+    // x: sky light, y: block light, each step of the 0-15 light level is 80% of the next
+    float sky = pow(0.8, 15.0 * (1.0 - inLight.x)) * ubo.lighting.x;
+    float block = pow(0.8, 15.0 * (1.0 - inLight.y));
+    float flash = inLight.x * ubo.lighting.y * 0.5;
+    fragBrightness = clamp(max(sky, block) + flash, 0.05, 1.0);
+}
@@ -0,0 +1,19 @@
+#version 450
+
+layout(binding = 1) uniform sampler2D blockAtlas;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+layout(location = 2) in float fragBrightness;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 texel = texture(blockAtlas, fragTexCoord);
+    // leaves, flowers and the grass overlay have holes
+    if (texel.a < 0.5) {
+        discard;
+    }
+    // the vertex colour carries the biome tint and face shading
+    outColor = vec4(texel.rgb * fragColor.rgb * fragBrightness, texel.a * fragColor.a);
+}
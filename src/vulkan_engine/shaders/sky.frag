@@ -0,0 +1,16 @@
+#version 450
+
+layout(binding = 0) uniform UniformBufferObject {
+    mat4 model;
+    mat4 view;
+    mat4 proj;
+    vec4 sky_color;
+    vec4 weather;
+} ubo;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    // the swapchain is sRGB, the sky colour is authored in sRGB
+    outColor = vec4(pow(ubo.sky_color.rgb, vec3(2.2)), 1.0);
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(binding = 1) uniform sampler2DArray precipitation;
+
+layout(push_constant) uniform PushConstants {
+    uint layer;
+} push;
+
+layout(location = 0) in vec4 fragColor;
+layout(location = 1) in vec2 fragTexCoord;
+
+layout(location = 0) out vec4 outColor;
+
+void main() {
+    vec4 texel = texture(precipitation, vec3(fragTexCoord, push.layer));
+    outColor = vec4(texel.rgb * fragColor.rgb, texel.a * fragColor.a);
+}
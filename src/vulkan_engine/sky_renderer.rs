@@ -0,0 +1,77 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+
+use crate::vulkan_engine::draw_list::{DrawList, Material, Mesh};
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, PipelineDescription};
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+
+/// Fills the background with the sky colour from the uniform buffer, drawn as a
+/// single full screen triangle before anything else
+pub struct SkyRenderer {
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline
+}
+
+impl SkyRenderer {
+    pub fn new(device: &ash::Device, descriptor_set_layout: vk::DescriptorSetLayout) -> EngineResult<SkyRenderer> {
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(device, &[descriptor_set_layout], &[])?;
+
+        Ok(SkyRenderer {
+            pipeline_layout,
+            pipeline: vk::Pipeline::null()
+        })
+    }
+
+    pub fn create_pipeline(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<()> {
+        let description = PipelineDescription {
+            cull_mode: vk::CullModeFlags::NONE,
+            ..PipelineDescription::new("sky.vert.spv", "sky.frag.spv")
+        };
+
+        self.pipeline = GraphicsPipeline::create_pipeline(
+            device,
+            render_pass,
+            self.pipeline_layout,
+            &description,
+            shaders,
+            pipeline_cache
+        )?;
+
+        Ok(())
+    }
+
+    pub fn destroy_pipeline(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device) {
+        self.destroy_pipeline(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+    }
+
+    pub fn draw(&self, draw_list: &mut DrawList, descriptor_set: vk::DescriptorSet) {
+        let material = Material {
+            pipeline: self.pipeline,
+            pipeline_layout: self.pipeline_layout,
+            descriptor_set
+        };
+        let full_screen_triangle = Mesh {
+            vertex_buffer: vk::Buffer::null(),
+            first_vertex: 0,
+            vertex_count: 3
+        };
+
+        draw_list.push(material, full_screen_triangle);
+    }
+}
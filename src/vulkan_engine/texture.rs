@@ -0,0 +1,194 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use std::path::Path;
+use std::ptr;
+
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage, ResourceLayout};
+use crate::vulkan_engine::upload_manager::UploadManager;
+
+/// Size and sampling of the layers handed to `Texture::from_pixels`
+pub struct TextureDescription {
+    pub width: u32,
+    pub height: u32,
+    pub layer_count: u32,
+    pub address_mode: vk::SamplerAddressMode
+}
+
+/// A 2D texture (array), every layer is resized to the size of the first image
+pub struct Texture {
+    pub image: vk::Image,
+    pub allocation: Allocation,
+    pub image_view: vk::ImageView,
+    pub sampler: vk::Sampler
+}
+
+impl Texture {
+    pub fn new_array(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        paths: &[&Path],
+        address_mode: vk::SamplerAddressMode
+    ) -> EngineResult<Texture> {
+        let mut pixels: Vec<u8> = vec![];
+        let mut size = None;
+
+        for path in paths.iter() {
+            let image_object = image::open(path)
+                .map_err(|error| EngineError::Asset { path: path.to_path_buf(), message: error.to_string() })?;
+
+            let (width, height) = *size.get_or_insert_with(|| {
+                let rgba = image_object.to_rgba();
+                rgba.dimensions()
+            });
+
+            let rgba = image_object
+                .resize_exact(width, height, image::FilterType::Nearest)
+                .to_rgba();
+            pixels.extend_from_slice(&rgba.into_raw());
+        }
+
+        let (width, height) = size.expect("Texture array needs at least one layer");
+        let layer_count = paths.len() as u32;
+
+        let description = TextureDescription { width, height, layer_count, address_mode };
+        Texture::from_pixels(device, allocator, uploads, &pixels, &description)
+    }
+
+    /// Uploads tightly packed RGBA8 layers, usable once the uploads are flushed
+    pub fn from_pixels(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        pixels: &[u8],
+        description: &TextureDescription
+    ) -> EngineResult<Texture> {
+        let &TextureDescription { width, height, layer_count, address_mode } = description;
+        let (image, allocation) = Texture::create_image(device, allocator, width, height, layer_count)?;
+        uploads.upload_image(device, allocator, image, vk::Extent2D { width, height }, layer_count, pixels)?;
+
+        let image_view = Texture::create_image_view(device, image, layer_count)?;
+        let sampler = Texture::create_sampler(device, address_mode)?;
+
+        Ok(Texture {
+            image,
+            allocation,
+            image_view,
+            sampler
+        })
+    }
+
+    fn create_image(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        width: u32,
+        height: u32,
+        layer_count: u32
+    ) -> EngineResult<(vk::Image, Allocation)> {
+        let image_create_info = vk::ImageCreateInfo {
+            s_type: vk::StructureType::IMAGE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::ImageCreateFlags::empty(),
+            image_type: vk::ImageType::TYPE_2D,
+            format: vk::Format::R8G8B8A8_SRGB,
+            extent: vk::Extent3D {
+                width,
+                height,
+                depth: 1,
+            },
+            mip_levels: 1,
+            array_layers: layer_count,
+            samples: vk::SampleCountFlags::TYPE_1,
+            tiling: vk::ImageTiling::OPTIMAL,
+            usage: vk::ImageUsageFlags::TRANSFER_DST | vk::ImageUsageFlags::SAMPLED,
+            sharing_mode: vk::SharingMode::EXCLUSIVE,
+            queue_family_index_count: 0,
+            p_queue_family_indices: ptr::null(),
+            initial_layout: vk::ImageLayout::UNDEFINED,
+        };
+
+        let image = unsafe {
+            device.create_image(&image_create_info, None)?
+        };
+
+        let memory_requirements = unsafe { device.get_image_memory_requirements(image) };
+        let allocation = allocator.allocate(memory_requirements, MemoryUsage::GpuOnly, ResourceLayout::Optimal)?;
+
+        unsafe {
+            device.bind_image_memory(image, allocation.memory, allocation.offset)?;
+        }
+
+        Ok((image, allocation))
+    }
+
+    fn create_image_view(device: &ash::Device, image: vk::Image, layer_count: u32) -> EngineResult<vk::ImageView> {
+        let imageview_create_info = vk::ImageViewCreateInfo {
+            s_type: vk::StructureType::IMAGE_VIEW_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::ImageViewCreateFlags::empty(),
+            // single layer textures such as the block atlas are sampled as plain 2D images
+            view_type: if layer_count == 1 { vk::ImageViewType::TYPE_2D } else { vk::ImageViewType::TYPE_2D_ARRAY },
+            format: vk::Format::R8G8B8A8_SRGB,
+            components: vk::ComponentMapping {
+                r: vk::ComponentSwizzle::IDENTITY,
+                g: vk::ComponentSwizzle::IDENTITY,
+                b: vk::ComponentSwizzle::IDENTITY,
+                a: vk::ComponentSwizzle::IDENTITY,
+            },
+            subresource_range: vk::ImageSubresourceRange {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                base_mip_level: 0,
+                level_count: 1,
+                base_array_layer: 0,
+                layer_count,
+            },
+            image,
+        };
+
+        let image_view = unsafe {
+            device.create_image_view(&imageview_create_info, None)?
+        };
+
+        Ok(image_view)
+    }
+
+    fn create_sampler(device: &ash::Device, address_mode: vk::SamplerAddressMode) -> EngineResult<vk::Sampler> {
+        // nearest filtering keeps the pixel art crisp
+        let sampler_create_info = vk::SamplerCreateInfo {
+            s_type: vk::StructureType::SAMPLER_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::SamplerCreateFlags::empty(),
+            mag_filter: vk::Filter::NEAREST,
+            min_filter: vk::Filter::NEAREST,
+            mipmap_mode: vk::SamplerMipmapMode::NEAREST,
+            address_mode_u: address_mode,
+            address_mode_v: address_mode,
+            address_mode_w: address_mode,
+            mip_lod_bias: 0.0,
+            anisotropy_enable: vk::FALSE,
+            max_anisotropy: 1.0,
+            compare_enable: vk::FALSE,
+            compare_op: vk::CompareOp::ALWAYS,
+            min_lod: 0.0,
+            max_lod: 0.0,
+            border_color: vk::BorderColor::INT_OPAQUE_BLACK,
+            unnormalized_coordinates: vk::FALSE,
+        };
+
+        let sampler = unsafe {
+            device.create_sampler(&sampler_create_info, None)?
+        };
+
+        Ok(sampler)
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        unsafe {
+            device.destroy_sampler(self.sampler, None);
+            device.destroy_image_view(self.image_view, None);
+            device.destroy_image(self.image, None);
+        }
+        allocator.free(&self.allocation);
+    }
+}
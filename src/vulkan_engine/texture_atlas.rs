@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use image::GenericImageView;
+use serde_json::Value;
+
+use crate::game_loop::tick_clock::SECONDS_PER_TICK;
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+
+// vanilla block and item textures are 16x16, animated ones are vertical strips of square frames
+const TILE_SIZE: u32 = 16;
+
+/// Where a texture lies in the atlas, animated ones have their frames side by side
+#[derive(Clone, Copy)]
+struct Tile {
+    uv: [f32; 4],
+    frame_count: u32,
+    // in ticks, from the texture's .mcmeta
+    frame_time: u32
+}
+
+/// Packs textures into one grid so everything using them draws with a single texture,
+/// like the block textures of every chunk or the item icons of the HUD
+pub struct TextureAtlas {
+    pub pixels: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    tiles: HashMap<String, Tile>
+}
+
+impl TextureAtlas {
+    pub fn build(texture_directory: &Path, names: &[&str]) -> EngineResult<TextureAtlas> {
+        let mut frames = vec![];
+        let mut frame_times = vec![];
+        for name in names.iter() {
+            let path = texture_directory.join(format!("{}.png", name));
+            let mut image_object = image::open(&path)
+                .map_err(|error| EngineError::Asset { path: path.clone(), message: error.to_string() })?;
+
+            let width = image_object.width();
+            let mut texture_frames = vec![];
+            for frame in 0..(image_object.height() / width).max(1) {
+                texture_frames.push(image_object
+                    .crop(0, frame * width, width, width)
+                    .resize_exact(TILE_SIZE, TILE_SIZE, image::FilterType::Nearest)
+                    .to_rgba());
+            }
+            frames.push(texture_frames);
+            frame_times.push(read_frame_time(&texture_directory.join(format!("{}.png.mcmeta", name)))?);
+        }
+
+        // the frames of a texture never wrap around to the next row
+        let frame_total: usize = frames.iter().map(|texture_frames| texture_frames.len()).sum();
+        let longest = frames.iter().map(|texture_frames| texture_frames.len()).max().unwrap_or(1);
+        let tiles_per_row = ((frame_total as f32).sqrt().ceil() as usize).max(longest).max(1);
+
+        let mut positions = Vec::with_capacity(frames.len());
+        let (mut column, mut row) = (0, 0);
+        for texture_frames in frames.iter() {
+            if column + texture_frames.len() > tiles_per_row {
+                column = 0;
+                row += 1;
+            }
+            positions.push((column as u32, row as u32));
+            column += texture_frames.len();
+        }
+
+        let width = tiles_per_row as u32 * TILE_SIZE;
+        let height = (row as u32 + 1) * TILE_SIZE;
+        let mut pixels = vec![0_u8; (width * height * 4) as usize];
+        let mut tiles = HashMap::new();
+
+        for (i, name) in names.iter().enumerate() {
+            let (column, row) = positions[i];
+            for (frame, frame_image) in frames[i].iter().enumerate() {
+                let tile_x = (column + frame as u32) * TILE_SIZE;
+                let tile_y = row * TILE_SIZE;
+                for (x, y, pixel) in frame_image.enumerate_pixels() {
+                    let offset = (((tile_y + y) * width + tile_x + x) * 4) as usize;
+                    pixels[offset..offset + 4].copy_from_slice(&pixel.0);
+                }
+            }
+
+            let (tile_x, tile_y) = (column * TILE_SIZE, row * TILE_SIZE);
+            tiles.insert(name.to_string(), Tile {
+                uv: [
+                    tile_x as f32 / width as f32,
+                    tile_y as f32 / height as f32,
+                    (tile_x + TILE_SIZE) as f32 / width as f32,
+                    (tile_y + TILE_SIZE) as f32 / height as f32
+                ],
+                frame_count: frames[i].len() as u32,
+                frame_time: frame_times[i]
+            });
+        }
+
+        Ok(TextureAtlas {
+            pixels,
+            width,
+            height,
+            tiles
+        })
+    }
+
+    fn tile(&self, name: &str) -> &Tile {
+        self.tiles
+            .get(name)
+            .unwrap_or_else(|| panic!("Texture {} is not part of the atlas", name))
+    }
+
+    /// UV rectangle of a texture's first frame as [u0, v0, u1, v1]
+    pub fn uv(&self, name: &str) -> [f32; 4] {
+        self.tile(name).uv
+    }
+
+    /// How the vertex shader plays a texture: the number of frames, seconds per frame and
+    /// the distance along u from one frame to the next
+    pub fn animation(&self, name: &str) -> [f32; 3] {
+        let tile = self.tile(name);
+        [
+            tile.frame_count as f32,
+            tile.frame_time as f32 * SECONDS_PER_TICK,
+            tile.uv[2] - tile.uv[0]
+        ]
+    }
+}
+
+/// Ticks per frame from a texture's `.mcmeta`, 1 like vanilla when it does not say
+fn read_frame_time(path: &Path) -> EngineResult<u32> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(_) => return Ok(1)
+    };
+    let metadata: Value = serde_json::from_slice(&content)
+        .map_err(|error| EngineError::Asset { path: path.to_path_buf(), message: error.to_string() })?;
+
+    Ok(metadata["animation"]["frametime"].as_u64().unwrap_or(1).max(1) as u32)
+}
@@ -0,0 +1,519 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use std::ptr;
+
+use crate::vulkan_engine::buffers::Buffers;
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage};
+use crate::vulkan_engine::utilities::structures::QueueFamilyIndices;
+
+/// Copies recorded between two flushes, submitted together
+struct UploadBatch {
+    transfer_command_buffer: vk::CommandBuffer,
+    // takes ownership on the graphics queue, only used with a separate transfer family
+    acquire_command_buffer: vk::CommandBuffer,
+    transfer_finished: vk::Semaphore,
+    fence: vk::Fence,
+    staging_buffers: Vec<(vk::Buffer, Allocation)>
+}
+
+/// Batches staging copies on the transfer queue. When the transfer queue belongs to its own
+/// family the resources are released there and acquired on the graphics queue, which waits
+/// on a semaphore instead of the CPU waiting for the copies.
+pub struct UploadManager {
+    transfer_family: u32,
+    graphics_family: u32,
+    transfer_queue: vk::Queue,
+    graphics_queue: vk::Queue,
+    transfer_pool: vk::CommandPool,
+    graphics_pool: vk::CommandPool,
+
+    recording: Option<UploadBatch>,
+    in_flight: Vec<UploadBatch>
+}
+
+impl UploadManager {
+    pub fn new(
+        device: &ash::Device,
+        queue_families: &QueueFamilyIndices,
+        graphics_queue: vk::Queue,
+        transfer_queue: vk::Queue
+    ) -> EngineResult<UploadManager> {
+        let graphics_family = queue_families.graphics_family.unwrap();
+        let transfer_family = queue_families.transfer_family.unwrap_or(graphics_family);
+
+        let transfer_pool = UploadManager::create_command_pool(device, transfer_family)?;
+        let graphics_pool = UploadManager::create_command_pool(device, graphics_family)?;
+
+        Ok(UploadManager {
+            transfer_family,
+            graphics_family,
+            transfer_queue,
+            graphics_queue,
+            transfer_pool,
+            graphics_pool,
+            recording: None,
+            in_flight: vec![]
+        })
+    }
+
+    fn create_command_pool(device: &ash::Device, queue_family_index: u32) -> EngineResult<vk::CommandPool> {
+        let command_pool_create_info = vk::CommandPoolCreateInfo {
+            s_type: vk::StructureType::COMMAND_POOL_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::CommandPoolCreateFlags::TRANSIENT,
+            queue_family_index
+        };
+
+        let command_pool = unsafe {
+            device.create_command_pool(&command_pool_create_info, None)?
+        };
+
+        Ok(command_pool)
+    }
+
+    fn is_separate_family(&self) -> bool {
+        self.transfer_family != self.graphics_family
+    }
+
+    /// Writes `data` at `offset` of an existing device local buffer once the next flush has
+    /// executed. Only that range changes owner, the rest of the buffer stays usable for drawing.
+    pub fn upload_to_buffer<T: Copy>(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        buffer: vk::Buffer,
+        offset: vk::DeviceSize,
+        usage: vk::BufferUsageFlags,
+        data: &[T]
+    ) -> EngineResult<()> {
+        let size = std::mem::size_of_val(data) as vk::DeviceSize;
+        let staging_buffer = self.stage(device, allocator, data)?;
+
+        let (transfer_command_buffer, acquire_command_buffer) = self.command_buffers(device)?;
+        let copy_regions = [vk::BufferCopy {
+            src_offset: 0,
+            dst_offset: offset,
+            size,
+        }];
+        let (consumer_stage, consumer_access) = UploadManager::buffer_consumer(usage);
+
+        let mut barrier = vk::BufferMemoryBarrier {
+            s_type: vk::StructureType::BUFFER_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: consumer_access,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            buffer,
+            offset,
+            size,
+        };
+
+        unsafe {
+            device.cmd_copy_buffer(transfer_command_buffer, staging_buffer, buffer, &copy_regions);
+
+            if self.is_separate_family() {
+                barrier.src_queue_family_index = self.transfer_family;
+                barrier.dst_queue_family_index = self.graphics_family;
+
+                let release = vk::BufferMemoryBarrier { dst_access_mask: vk::AccessFlags::empty(), ..barrier };
+                device.cmd_pipeline_barrier(
+                    transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[release],
+                    &[],
+                );
+
+                let acquire = vk::BufferMemoryBarrier { src_access_mask: vk::AccessFlags::empty(), ..barrier };
+                device.cmd_pipeline_barrier(
+                    acquire_command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    consumer_stage,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[acquire],
+                    &[],
+                );
+            } else {
+                device.cmd_pipeline_barrier(
+                    transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    consumer_stage,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[barrier],
+                    &[],
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Fills every layer of a freshly created image and leaves it ready for sampling
+    /// in fragment shaders
+    pub fn upload_image(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        image: vk::Image,
+        extent: vk::Extent2D,
+        layer_count: u32,
+        pixels: &[u8]
+    ) -> EngineResult<()> {
+        let staging_buffer = self.stage(device, allocator, pixels)?;
+        let (transfer_command_buffer, acquire_command_buffer) = self.command_buffers(device)?;
+
+        let subresource_range = vk::ImageSubresourceRange {
+            aspect_mask: vk::ImageAspectFlags::COLOR,
+            base_mip_level: 0,
+            level_count: 1,
+            base_array_layer: 0,
+            layer_count,
+        };
+        let to_transfer_dst = vk::ImageMemoryBarrier {
+            s_type: vk::StructureType::IMAGE_MEMORY_BARRIER,
+            p_next: ptr::null(),
+            src_access_mask: vk::AccessFlags::empty(),
+            dst_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            old_layout: vk::ImageLayout::UNDEFINED,
+            new_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            src_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            dst_queue_family_index: vk::QUEUE_FAMILY_IGNORED,
+            image,
+            subresource_range,
+        };
+        let mut to_shader_read = vk::ImageMemoryBarrier {
+            src_access_mask: vk::AccessFlags::TRANSFER_WRITE,
+            dst_access_mask: vk::AccessFlags::SHADER_READ,
+            old_layout: vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+            new_layout: vk::ImageLayout::SHADER_READ_ONLY_OPTIMAL,
+            ..to_transfer_dst
+        };
+
+        let buffer_image_regions = [vk::BufferImageCopy {
+            image_subresource: vk::ImageSubresourceLayers {
+                aspect_mask: vk::ImageAspectFlags::COLOR,
+                mip_level: 0,
+                base_array_layer: 0,
+                layer_count,
+            },
+            image_extent: vk::Extent3D {
+                width: extent.width,
+                height: extent.height,
+                depth: 1,
+            },
+            buffer_offset: 0,
+            buffer_image_height: 0,
+            buffer_row_length: 0,
+            image_offset: vk::Offset3D { x: 0, y: 0, z: 0 },
+        }];
+
+        unsafe {
+            device.cmd_pipeline_barrier(
+                transfer_command_buffer,
+                vk::PipelineStageFlags::TOP_OF_PIPE,
+                vk::PipelineStageFlags::TRANSFER,
+                vk::DependencyFlags::empty(),
+                &[],
+                &[],
+                &[to_transfer_dst],
+            );
+            device.cmd_copy_buffer_to_image(
+                transfer_command_buffer,
+                staging_buffer,
+                image,
+                vk::ImageLayout::TRANSFER_DST_OPTIMAL,
+                &buffer_image_regions,
+            );
+
+            // the layout transition happens once, as part of the ownership transfer
+            if self.is_separate_family() {
+                to_shader_read.src_queue_family_index = self.transfer_family;
+                to_shader_read.dst_queue_family_index = self.graphics_family;
+
+                let release = vk::ImageMemoryBarrier { dst_access_mask: vk::AccessFlags::empty(), ..to_shader_read };
+                device.cmd_pipeline_barrier(
+                    transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::BOTTOM_OF_PIPE,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[release],
+                );
+
+                let acquire = vk::ImageMemoryBarrier { src_access_mask: vk::AccessFlags::empty(), ..to_shader_read };
+                device.cmd_pipeline_barrier(
+                    acquire_command_buffer,
+                    vk::PipelineStageFlags::TOP_OF_PIPE,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[acquire],
+                );
+            } else {
+                device.cmd_pipeline_barrier(
+                    transfer_command_buffer,
+                    vk::PipelineStageFlags::TRANSFER,
+                    vk::PipelineStageFlags::FRAGMENT_SHADER,
+                    vk::DependencyFlags::empty(),
+                    &[],
+                    &[],
+                    &[to_shader_read],
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Where the graphics queue first reads a buffer with the given usage
+    fn buffer_consumer(usage: vk::BufferUsageFlags) -> (vk::PipelineStageFlags, vk::AccessFlags) {
+        let mut stages = vk::PipelineStageFlags::empty();
+        let mut access = vk::AccessFlags::empty();
+
+        if usage.contains(vk::BufferUsageFlags::VERTEX_BUFFER) {
+            stages |= vk::PipelineStageFlags::VERTEX_INPUT;
+            access |= vk::AccessFlags::VERTEX_ATTRIBUTE_READ;
+        }
+        if usage.contains(vk::BufferUsageFlags::INDEX_BUFFER) {
+            stages |= vk::PipelineStageFlags::VERTEX_INPUT;
+            access |= vk::AccessFlags::INDEX_READ;
+        }
+        if usage.contains(vk::BufferUsageFlags::INDIRECT_BUFFER) {
+            stages |= vk::PipelineStageFlags::DRAW_INDIRECT;
+            access |= vk::AccessFlags::INDIRECT_COMMAND_READ;
+        }
+        if usage.intersects(vk::BufferUsageFlags::UNIFORM_BUFFER | vk::BufferUsageFlags::STORAGE_BUFFER) {
+            stages |= vk::PipelineStageFlags::VERTEX_SHADER
+                | vk::PipelineStageFlags::FRAGMENT_SHADER
+                | vk::PipelineStageFlags::COMPUTE_SHADER;
+            access |= vk::AccessFlags::SHADER_READ;
+        }
+
+        if stages.is_empty() {
+            (vk::PipelineStageFlags::ALL_COMMANDS, vk::AccessFlags::MEMORY_READ)
+        } else {
+            (stages, access)
+        }
+    }
+
+    fn stage<T: Copy>(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        data: &[T]
+    ) -> EngineResult<vk::Buffer> {
+        let (staging_buffer, staging_allocation) = Buffers::create_buffer(
+            device,
+            allocator,
+            std::mem::size_of_val(data) as vk::DeviceSize,
+            vk::BufferUsageFlags::TRANSFER_SRC,
+            MemoryUsage::CpuToGpu
+        )?;
+        staging_allocation.write(data);
+
+        self.begin_batch(device)?;
+        self.recording
+            .as_mut()
+            .unwrap()
+            .staging_buffers
+            .push((staging_buffer, staging_allocation));
+
+        Ok(staging_buffer)
+    }
+
+    /// The transfer and acquire command buffers of the batch being recorded
+    fn command_buffers(&mut self, device: &ash::Device) -> EngineResult<(vk::CommandBuffer, vk::CommandBuffer)> {
+        self.begin_batch(device)?;
+        let batch = self.recording.as_ref().unwrap();
+
+        Ok((batch.transfer_command_buffer, batch.acquire_command_buffer))
+    }
+
+    fn begin_batch(&mut self, device: &ash::Device) -> EngineResult<()> {
+        if self.recording.is_some() {
+            return Ok(());
+        }
+
+        let transfer_command_buffer = UploadManager::begin_command_buffer(device, self.transfer_pool)?;
+        let (acquire_command_buffer, transfer_finished) = if self.is_separate_family() {
+            let semaphore_create_info = vk::SemaphoreCreateInfo {
+                s_type: vk::StructureType::SEMAPHORE_CREATE_INFO,
+                p_next: ptr::null(),
+                flags: vk::SemaphoreCreateFlags::empty(),
+            };
+
+            (
+                UploadManager::begin_command_buffer(device, self.graphics_pool)?,
+                unsafe { device.create_semaphore(&semaphore_create_info, None)? }
+            )
+        } else {
+            (vk::CommandBuffer::null(), vk::Semaphore::null())
+        };
+
+        let fence_create_info = vk::FenceCreateInfo {
+            s_type: vk::StructureType::FENCE_CREATE_INFO,
+            p_next: ptr::null(),
+            flags: vk::FenceCreateFlags::empty(),
+        };
+        let fence = unsafe { device.create_fence(&fence_create_info, None)? };
+
+        self.recording = Some(UploadBatch {
+            transfer_command_buffer,
+            acquire_command_buffer,
+            transfer_finished,
+            fence,
+            staging_buffers: vec![]
+        });
+
+        Ok(())
+    }
+
+    fn begin_command_buffer(device: &ash::Device, command_pool: vk::CommandPool) -> EngineResult<vk::CommandBuffer> {
+        let command_buffer_allocate_info = vk::CommandBufferAllocateInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_ALLOCATE_INFO,
+            p_next: ptr::null(),
+            command_buffer_count: 1,
+            command_pool,
+            level: vk::CommandBufferLevel::PRIMARY,
+        };
+
+        let command_buffer = unsafe {
+            device.allocate_command_buffers(&command_buffer_allocate_info)?
+        }[0];
+
+        let command_buffer_begin_info = vk::CommandBufferBeginInfo {
+            s_type: vk::StructureType::COMMAND_BUFFER_BEGIN_INFO,
+            p_next: ptr::null(),
+            p_inheritance_info: ptr::null(),
+            flags: vk::CommandBufferUsageFlags::ONE_TIME_SUBMIT,
+        };
+
+        unsafe {
+            device.begin_command_buffer(command_buffer, &command_buffer_begin_info)?;
+        }
+
+        Ok(command_buffer)
+    }
+
+    /// Submits everything recorded since the last flush. Graphics work submitted afterwards
+    /// sees the uploaded data, the CPU does not wait for it.
+    pub fn flush(&mut self, device: &ash::Device) -> EngineResult<()> {
+        let batch = match self.recording.take() {
+            Some(batch) => batch,
+            None => return Ok(())
+        };
+
+        let transfer_command_buffers = [batch.transfer_command_buffer];
+        let transfer_finished = [batch.transfer_finished];
+
+        unsafe {
+            device.end_command_buffer(batch.transfer_command_buffer)?;
+
+            if self.is_separate_family() {
+                device.end_command_buffer(batch.acquire_command_buffer)?;
+
+                let transfer_submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: 0,
+                    p_wait_semaphores: ptr::null(),
+                    p_wait_dst_stage_mask: ptr::null(),
+                    command_buffer_count: 1,
+                    p_command_buffers: transfer_command_buffers.as_ptr(),
+                    signal_semaphore_count: 1,
+                    p_signal_semaphores: transfer_finished.as_ptr(),
+                }];
+                device.queue_submit(self.transfer_queue, &transfer_submit_infos, vk::Fence::null())?;
+
+                // later frames on the graphics queue are ordered after the acquire barriers
+                let acquire_command_buffers = [batch.acquire_command_buffer];
+                let wait_stages = [vk::PipelineStageFlags::ALL_COMMANDS];
+                let acquire_submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: 1,
+                    p_wait_semaphores: transfer_finished.as_ptr(),
+                    p_wait_dst_stage_mask: wait_stages.as_ptr(),
+                    command_buffer_count: 1,
+                    p_command_buffers: acquire_command_buffers.as_ptr(),
+                    signal_semaphore_count: 0,
+                    p_signal_semaphores: ptr::null(),
+                }];
+                device.queue_submit(self.graphics_queue, &acquire_submit_infos, batch.fence)?;
+            } else {
+                let submit_infos = [vk::SubmitInfo {
+                    s_type: vk::StructureType::SUBMIT_INFO,
+                    p_next: ptr::null(),
+                    wait_semaphore_count: 0,
+                    p_wait_semaphores: ptr::null(),
+                    p_wait_dst_stage_mask: ptr::null(),
+                    command_buffer_count: 1,
+                    p_command_buffers: transfer_command_buffers.as_ptr(),
+                    signal_semaphore_count: 0,
+                    p_signal_semaphores: ptr::null(),
+                }];
+                device.queue_submit(self.graphics_queue, &submit_infos, batch.fence)?;
+            }
+        }
+
+        self.in_flight.push(batch);
+
+        Ok(())
+    }
+
+    /// Frees the staging buffers of the batches the GPU has finished
+    pub fn collect(&mut self, device: &ash::Device, allocator: &mut MemoryAllocator) -> EngineResult<()> {
+        let mut i = 0;
+        while i < self.in_flight.len() {
+            let is_finished = match unsafe { device.get_fence_status(self.in_flight[i].fence) } {
+                Ok(()) => true,
+                Err(vk::Result::NOT_READY) => false,
+                Err(vk_result) => return Err(vk_result.into())
+            };
+            if is_finished {
+                let batch = self.in_flight.swap_remove(i);
+                self.destroy_batch(device, allocator, batch);
+            } else {
+                i += 1;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn destroy_batch(&self, device: &ash::Device, allocator: &mut MemoryAllocator, batch: UploadBatch) {
+        unsafe {
+            device.free_command_buffers(self.transfer_pool, &[batch.transfer_command_buffer]);
+            if self.is_separate_family() {
+                device.free_command_buffers(self.graphics_pool, &[batch.acquire_command_buffer]);
+                device.destroy_semaphore(batch.transfer_finished, None);
+            }
+            device.destroy_fence(batch.fence, None);
+        }
+
+        for (buffer, allocation) in batch.staging_buffers.iter() {
+            Buffers::destroy_buffer(device, allocator, *buffer, allocation);
+        }
+    }
+
+    /// The device must be idle
+    pub fn destroy(&mut self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        let batches: Vec<UploadBatch> = self.in_flight.drain(..).chain(self.recording.take()).collect();
+        for batch in batches {
+            self.destroy_batch(device, allocator, batch);
+        }
+
+        unsafe {
+            device.destroy_command_pool(self.transfer_pool, None);
+            device.destroy_command_pool(self.graphics_pool, None);
+        }
+    }
+}
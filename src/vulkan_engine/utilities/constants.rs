@@ -19,6 +19,7 @@ pub const DEVICE_EXTENSIONS: DeviceExtension = DeviceExtension {
 };
 pub const MAX_FRAMES_IN_FLIGHT: usize = 2;
 pub const IS_PAINT_FPS_COUNTER: bool = false;
+pub const TEXTURE_DIRECTORY: &str = "TextureData/assets/minecraft/textures";
 
 impl DeviceExtension {
     pub fn get_extensions_raw_names(&self) -> [*const c_char; 1] {
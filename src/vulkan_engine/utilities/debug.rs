@@ -6,6 +6,7 @@ use std::os::raw::c_void;
 use std::ptr;
 use crate::vulkan_engine::utilities::tools;
 use crate::vulkan_engine::utilities::constants::VALIDATION;
+use crate::vulkan_engine::engine_error::EngineResult;
 
 unsafe extern "system" fn vulkan_debug_utils_callback(
     message_severity: vk::DebugUtilsMessageSeverityFlagsEXT,
@@ -37,14 +38,12 @@ pub struct ValidationInfo {
     pub required_validation_layers: [&'static str; 1],
 }
 
-pub fn check_validation_layer_support(entry: &ash::Entry) -> bool {
-    let layer_properties = entry
-        .enumerate_instance_layer_properties()
-        .expect("Failed to enumerate Instance Layers Properties!");
+pub fn check_validation_layer_support(entry: &ash::Entry) -> EngineResult<bool> {
+    let layer_properties = entry.enumerate_instance_layer_properties()?;
 
     if layer_properties.len() <= 0 {
         eprintln!("No available layers.");
-        return false;
+        return Ok(false);
     } else {
         println!("Instance Available Layers: ");
         for layer in layer_properties.iter() {
@@ -65,32 +64,30 @@ pub fn check_validation_layer_support(entry: &ash::Entry) -> bool {
         }
 
         if is_layer_found == false {
-            return false;
+            return Ok(false);
         }
     }
 
-    true
+    Ok(true)
 }
 
 pub fn setup_debug_utils(
     is_enable_debug: bool,
     entry: &ash::Entry,
     instance: &ash::Instance,
-) -> (ash::extensions::ext::DebugUtils, vk::DebugUtilsMessengerEXT) {
+) -> EngineResult<(ash::extensions::ext::DebugUtils, vk::DebugUtilsMessengerEXT)> {
     let debug_utils_loader = ash::extensions::ext::DebugUtils::new(entry, instance);
 
     if is_enable_debug == false {
-        (debug_utils_loader, ash::vk::DebugUtilsMessengerEXT::null())
+        Ok((debug_utils_loader, ash::vk::DebugUtilsMessengerEXT::null()))
     } else {
         let messenger_ci = populate_debug_messenger_create_info();
 
         let utils_messenger = unsafe {
-            debug_utils_loader
-                .create_debug_utils_messenger(&messenger_ci, None)
-                .expect("Debug Utils Callback")
+            debug_utils_loader.create_debug_utils_messenger(&messenger_ci, None)?
         };
 
-        (debug_utils_loader, utils_messenger)
+        Ok((debug_utils_loader, utils_messenger))
     }
 }
 
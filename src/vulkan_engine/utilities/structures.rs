@@ -30,14 +30,19 @@ pub struct SwapChainSupportDetail {
 
 pub struct QueueFamilyIndices {
     pub graphics_family: Option<u32>,
-    pub present_family: Option<u32>
+    pub present_family: Option<u32>,
+    // families without graphics support, None when the device only has the graphics family
+    pub transfer_family: Option<u32>,
+    pub compute_family: Option<u32>
 }
 
 impl QueueFamilyIndices {
     pub fn new() -> QueueFamilyIndices {
         QueueFamilyIndices {
             graphics_family: None,
-            present_family: None
+            present_family: None,
+            transfer_family: None,
+            compute_family: None
         }
     }
 
@@ -58,6 +63,11 @@ pub struct UniformBufferObject {
     pub model: Matrix4<f32>,
     pub view: Matrix4<f32>,
     pub proj: Matrix4<f32>,
+    pub sky_color: [f32; 4],
+    // x: seconds since start, y: lightning flash, z: rain level, w: thunder level
+    pub weather: [f32; 4],
+    // x: how bright full sky light is, y: lightning flash
+    pub lighting: [f32; 4],
 }
 
 #[repr(C)]
@@ -133,6 +143,62 @@ impl VertexV3 {
     }
 }
 
+/// Terrain vertex, `light` holds the sky and block light of the face in [0, 1] and
+/// `animation` how its texture plays, see `TextureAtlas::animation`
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct ChunkVertex {
+    pub pos: [f32; 4],
+    pub color: [f32; 4],
+    pub tex_coord: [f32; 2],
+    pub light: [f32; 2],
+    pub animation: [f32; 3],
+}
+impl ChunkVertex {
+    pub fn get_binding_descriptions() -> [vk::VertexInputBindingDescription; 1] {
+        [vk::VertexInputBindingDescription {
+            binding: 0,
+            stride: ::std::mem::size_of::<Self>() as u32,
+            input_rate: vk::VertexInputRate::VERTEX,
+        }]
+    }
+
+    pub fn get_attribute_descriptions() -> [vk::VertexInputAttributeDescription; 5] {
+        [
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 0,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, pos) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 1,
+                format: vk::Format::R32G32B32A32_SFLOAT,
+                offset: offset_of!(Self, color) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 2,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, tex_coord) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 3,
+                format: vk::Format::R32G32_SFLOAT,
+                offset: offset_of!(Self, light) as u32,
+            },
+            vk::VertexInputAttributeDescription {
+                binding: 0,
+                location: 4,
+                format: vk::Format::R32G32B32_SFLOAT,
+                offset: offset_of!(Self, animation) as u32,
+            },
+        ]
+    }
+}
+
 pub const RECT_VERTICES_DATA: [VertexV1; 4] = [
     VertexV1 {
         pos: [-0.5, -0.5],
@@ -1,6 +1,5 @@
 use std::ffi::CStr;
 use std::os::raw::c_char;
-use std::path::Path;
 use winit::event::VirtualKeyCode;
 
 /// Helper function to convert [c_char; SIZE] to string
@@ -14,15 +13,4 @@ pub fn vk_to_string(raw_string_array: &[c_char]) -> String {
         .to_str()
         .expect("Failed to convert vulkan raw string.")
         .to_owned()
-}
-
-pub fn read_shader_code(shader_path: &Path) -> Vec<u8> {
-    use std::fs::File;
-    use std::io::Read;
-
-    let spv_file =
-        File::open(shader_path).expect(&format!("Failed to find spv file at {:?}", shader_path));
-    let bytes_code: Vec<u8> = spv_file.bytes().filter_map(|byte| byte.ok()).collect();
-
-    bytes_code
 }
\ No newline at end of file
@@ -1,4 +1,4 @@
-use winit::event::{Event, ElementState, KeyboardInput, WindowEvent, VirtualKeyCode};
+use winit::event::{Event, DeviceEvent, ElementState, KeyboardInput, MouseButton, MouseScrollDelta, WindowEvent, VirtualKeyCode};
 use winit::event_loop::{EventLoop, ControlFlow};
 use ash::version::{InstanceV1_0, EntryV1_0, DeviceV1_0};
 use std::ffi::CString;
@@ -14,14 +14,51 @@ use crate::vulkan_engine::utilities::structures::{QueueFamilyIndices, SwapChainS
 use cgmath::num_traits::clamp;
 use crate::vulkan_engine::setup::Setup;
 use crate::settings_loader::key_mappings::KeyMappings;
+use crate::settings_loader::graphics_settings::GraphicsSettings;
 use crate::vulkan_engine::presentation::Presentation;
-use crate::vulkan_engine::graphics_pipeline::GraphicsPipeline;
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, TerrainPipelines};
 use crate::vulkan_engine::buffers::Buffers;
+use crate::vulkan_engine::camera::Camera;
+use crate::vulkan_engine::chunk_renderer::ChunkRenderer;
+use crate::vulkan_engine::depth_buffer::DepthBuffer;
+use crate::vulkan_engine::engine_error::{EngineError, EngineResult};
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator};
+use crate::vulkan_engine::descriptors::Descriptors;
+use crate::vulkan_engine::sky_renderer::SkyRenderer;
+use crate::vulkan_engine::weather_renderer::WeatherRenderer;
+use crate::vulkan_engine::outline_renderer::OutlineRenderer;
+use crate::vulkan_engine::crack_renderer::CrackRenderer;
+use crate::vulkan_engine::hud_renderer::HudRenderer;
+use crate::vulkan_engine::upload_manager::UploadManager;
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::vulkan_engine::pipeline_cache::PipelineCache;
+use crate::vulkan_engine::draw_list::DrawList;
+use crate::vulkan_engine::culling::CullingStats;
+use crate::vulkan_engine::utilities::structures::UniformBufferObject;
+use crate::entity::block_interaction::{BlockInteraction, InteractionInput};
+use crate::entity::player::{Player, PlayerInput};
+use crate::game_loop;
+use crate::game_loop::tick_clock::TickClock;
+use crate::jobs::job_system::JobSystem;
+use crate::world::raycast::{self, RaycastHit};
+use crate::world::block::Block;
+use crate::world::world::World;
+use crate::commands::command::Command;
+use crate::commands::console::Console;
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
 
 const WINDOW_TITLE: &'static str = "Minecraft";
 const WINDOW_HEIGHT: u32 = 600;
 const WINDOW_WIDTH: u32 = 800;
 
+const SKY_COLOR: [f32; 3] = [0.47, 0.65, 1.0];
+// the culling stats in the title are refreshed this often
+const TITLE_UPDATE_INTERVAL: Duration = Duration::from_secs(1);
+// degrees the camera turns per pixel of mouse movement
+const MOUSE_SENSITIVITY: f32 = 0.15;
+
 pub struct VulkanEngine {
     window: winit::window::Window,
 
@@ -34,6 +71,8 @@ pub struct VulkanEngine {
 
     physical_device: vk::PhysicalDevice,
     device: ash::Device,
+    allocator: MemoryAllocator,
+    uploads: UploadManager,
 
     queue_family: QueueFamilyIndices,
     graphics_queue: vk::Queue,
@@ -46,34 +85,74 @@ pub struct VulkanEngine {
     swapchain_extent: vk::Extent2D,
     swapchain_imageviews: Vec<vk::ImageView>,
     swapchain_framebuffers: Vec<vk::Framebuffer>,
+    depth_format: vk::Format,
+    depth_buffer: DepthBuffer,
 
     render_pass: vk::RenderPass,
     pipeline_layout: vk::PipelineLayout,
-    graphics_pipeline: vk::Pipeline,
+    terrain_pipelines: TerrainPipelines,
+    shaders: ShaderLibrary,
+    pipeline_cache: vk::PipelineCache,
 
-    command_pool: vk::CommandPool,
+    // indexed by the current frame in flight
+    command_pools: Vec<vk::CommandPool>,
     command_buffers: Vec<vk::CommandBuffer>,
+    draw_list: DrawList,
+
+    uniform_buffers: Vec<vk::Buffer>,
+    uniform_buffer_allocations: Vec<Allocation>,
+    descriptor_set_layout: vk::DescriptorSetLayout,
+    descriptor_pool: vk::DescriptorPool,
+
+    sky_renderer: SkyRenderer,
+    chunk_renderer: ChunkRenderer,
+    weather_renderer: WeatherRenderer,
+    outline_renderer: OutlineRenderer,
+    crack_renderer: CrackRenderer,
+    hud_renderer: HudRenderer,
 
     image_available_semaphores: Vec<vk::Semaphore>,
     render_finished_semaphores: Vec<vk::Semaphore>,
     in_flight_fences: Vec<vk::Fence>,
+    // per swapchain image, the fence of the frame that last drew to it, null before the
+    // first. Its uniform and indirect buffers are only written once that frame finished.
+    images_in_flight: Vec<vk::Fence>,
     current_frame: usize,
-
-    is_framebuffer_resized: bool
+    // frames submitted so far
+    submitted_frames: u64,
+
+    is_framebuffer_resized: bool,
+
+    // finishes its running jobs before the world is saved
+    jobs: JobSystem,
+    world: World,
+    player: Player,
+    interaction: BlockInteraction,
+    camera: Camera,
+    // the block the camera looks at within reach
+    target: Option<RaycastHit>,
+    // keys held down while the window has focus
+    held_keys: HashSet<VirtualKeyCode>,
+    held_buttons: HashSet<MouseButton>,
+    is_focused: bool,
+    console: Console,
+    clock: TickClock,
+    culling_stats: CullingStats,
+    last_title_update: Instant
 }
 
 impl VulkanEngine {
 
-    pub fn new(event_loop: &winit::event_loop::EventLoop<()>) -> VulkanEngine {
-        let window = VulkanEngine::init_window(event_loop);
+    pub fn new(event_loop: &winit::event_loop::EventLoop<()>, graphics_settings: &GraphicsSettings) -> EngineResult<VulkanEngine> {
+        let window = VulkanEngine::init_window(event_loop)?;
 
-        let vulkan_setup = Setup::new(&window);
+        let vulkan_setup = Setup::new(&window, graphics_settings.gpu.as_deref())?;
 
         let surface_struct = SurfaceStruct{
             surface_loader:    vulkan_setup.surface_loader.clone(),
             surface:vulkan_setup.surface,
-            screen_width: WINDOW_WIDTH,
-            screen_height: WINDOW_HEIGHT
+            screen_width: window.inner_size().width,
+            screen_height: window.inner_size().height
         };
 
         let presentation = Presentation::new(
@@ -83,27 +162,167 @@ impl VulkanEngine {
             vulkan_setup.physical_device,
             &vulkan_setup.queue_family_indices,
             &window
-        );
+        )?;
+
+        let mut allocator = MemoryAllocator::new(&vulkan_setup.instance, vulkan_setup.physical_device, &vulkan_setup.device);
 
+        let depth_format = DepthBuffer::find_depth_format(&vulkan_setup.instance, vulkan_setup.physical_device)?;
+        let depth_buffer = DepthBuffer::new(
+            &vulkan_setup.device,
+            &mut allocator,
+            depth_format,
+            presentation.swapchain_extent
+        )?;
+
+        let descriptor_set_layout = Descriptors::create_descriptor_set_layout(&vulkan_setup.device)?;
+
+        let shaders = ShaderLibrary::new();
+        let pipeline_cache = PipelineCache::load(&vulkan_setup.instance, vulkan_setup.physical_device, &vulkan_setup.device)?;
         let graphics_pipeline = GraphicsPipeline::new(
             &vulkan_setup.device,
             presentation.swapchain_format,
-            presentation.swapchain_extent
-        );
+            depth_format,
+            descriptor_set_layout,
+            &shaders,
+            pipeline_cache
+        )?;
 
         let buffers = Buffers::new(
             &vulkan_setup.device,
             graphics_pipeline.render_pass,
             &presentation.swapchain_imageviews,
+            depth_buffer.image_view,
             presentation.swapchain_extent,
+            &vulkan_setup.queue_family_indices
+        )?;
+
+        let (uniform_buffers, uniform_buffer_allocations) = Buffers::create_uniform_buffers::<UniformBufferObject>(
+            &vulkan_setup.device,
+            &mut allocator,
+            presentation.swapchain_images.len()
+        )?;
+        let descriptor_pool = Descriptors::create_descriptor_pool(
+            &vulkan_setup.device,
+            presentation.swapchain_images.len()
+        )?;
+
+        let mut sky_renderer = SkyRenderer::new(&vulkan_setup.device, descriptor_set_layout)?;
+        sky_renderer.create_pipeline(
+            &vulkan_setup.device,
+            graphics_pipeline.render_pass,
+            &shaders,
+            pipeline_cache
+        )?;
+
+        let mut uploads = UploadManager::new(
+            &vulkan_setup.device,
             &vulkan_setup.queue_family_indices,
-            graphics_pipeline.graphics_pipeline
+            vulkan_setup.graphics_queue,
+            vulkan_setup.transfer_queue
+        )?;
+
+        let mut weather_renderer = WeatherRenderer::new(
+            &vulkan_setup.device,
+            &mut allocator,
+            &mut uploads,
+            descriptor_set_layout
+        )?;
+        weather_renderer.create_pipeline(
+            &vulkan_setup.device,
+            graphics_pipeline.render_pass,
+            &shaders,
+            pipeline_cache
+        )?;
+        weather_renderer.create_swapchain_resources(
+            &vulkan_setup.device,
+            &mut allocator,
+            descriptor_pool,
+            descriptor_set_layout,
+            &uniform_buffers
+        )?;
+
+        let mut outline_renderer = OutlineRenderer::new(&vulkan_setup.device, descriptor_set_layout)?;
+        outline_renderer.create_pipeline(
+            &vulkan_setup.device,
+            graphics_pipeline.render_pass,
+            &shaders,
+            pipeline_cache
+        )?;
+
+        let mut crack_renderer = CrackRenderer::new(
+            &vulkan_setup.device,
+            &mut allocator,
+            &mut uploads,
+            descriptor_set_layout
+        )?;
+        crack_renderer.create_pipeline(
+            &vulkan_setup.device,
+            graphics_pipeline.render_pass,
+            &shaders,
+            pipeline_cache
+        )?;
+        crack_renderer.create_swapchain_resources(
+            &vulkan_setup.device,
+            descriptor_pool,
+            descriptor_set_layout,
+            &uniform_buffers
+        )?;
+
+        let mut hud_renderer = HudRenderer::new(
+            &vulkan_setup.device,
+            &mut allocator,
+            &mut uploads,
+            descriptor_set_layout
+        )?;
+        hud_renderer.create_pipeline(
+            &vulkan_setup.device,
+            graphics_pipeline.render_pass,
+            &shaders,
+            pipeline_cache
+        )?;
+        hud_renderer.create_swapchain_resources(
+            &vulkan_setup.device,
+            descriptor_pool,
+            descriptor_set_layout,
+            &uniform_buffers
+        )?;
+
+        let sync_objects = VulkanEngine::create_sync_objects(&vulkan_setup.device)?;
+
+        let mut world = game_loop::open_world();
+        let [spawn_x, spawn_y, spawn_z] = world.level.spawn;
+
+        let mut chunk_renderer = ChunkRenderer::new(
+            &vulkan_setup.device,
+            &mut allocator,
+            &mut uploads,
+            graphics_settings
+        )?;
+        chunk_renderer.create_swapchain_resources(
+            &vulkan_setup.device,
+            &mut allocator,
+            descriptor_pool,
+            descriptor_set_layout,
+            &uniform_buffers
+        )?;
+
+        let player = Player::new(Point3::new(spawn_x as f32 + 0.5, spawn_y as f32, spawn_z as f32 + 0.5));
+        let camera = Camera::new(
+            player.eye_position(1.0),
+            presentation.swapchain_extent.width as f32 / presentation.swapchain_extent.height as f32
         );
 
-        let sync_objects = VulkanEngine::create_sync_objects(&vulkan_setup.device);
+        // every generated chunk is dirty, so the level changes need no meshing of their own
+        let jobs = JobSystem::new();
+        chunk_renderer.track_camera(&jobs, &world, camera.position);
+        world.stream_around(&jobs, spawn_x, spawn_z, chunk_renderer.view_distance() as i32);
+        let dirty_chunks = world.take_dirty_chunks();
+        chunk_renderer.queue_meshes(&jobs, &world, &dirty_chunks);
+        uploads.flush(&vulkan_setup.device)?;
+        println!("{}", allocator.stats());
 
 
-        VulkanEngine {
+        let vulkan_engine = VulkanEngine {
             window,
 
             _entry: vulkan_setup.entry,
@@ -115,6 +334,8 @@ impl VulkanEngine {
 
             physical_device: vulkan_setup.physical_device,
             device: vulkan_setup.device,
+            allocator,
+            uploads,
 
             queue_family: vulkan_setup.queue_family_indices,
             graphics_queue: vulkan_setup.graphics_queue,
@@ -122,26 +343,66 @@ impl VulkanEngine {
 
             swapchain_loader: presentation.swapchain_loader,
             swapchain: presentation.swapchain,
+            images_in_flight: vec![vk::Fence::null(); presentation.swapchain_images.len()],
             swapchain_images: presentation.swapchain_images,
             swapchain_format: presentation.swapchain_format,
             swapchain_extent: presentation.swapchain_extent,
             swapchain_imageviews: presentation.swapchain_imageviews,
             swapchain_framebuffers: buffers.framebuffers,
+            depth_format,
+            depth_buffer,
 
             render_pass: graphics_pipeline.render_pass,
             pipeline_layout: graphics_pipeline.pipeline_layout,
-            graphics_pipeline: graphics_pipeline.graphics_pipeline,
+            terrain_pipelines: graphics_pipeline.terrain_pipelines,
+            shaders,
+            pipeline_cache,
 
-            command_pool: buffers.command_pool,
+            command_pools: buffers.command_pools,
             command_buffers: buffers.command_buffers,
+            draw_list: DrawList::new(vulkan_setup.multi_draw_indirect),
+
+            uniform_buffers,
+            uniform_buffer_allocations,
+            descriptor_set_layout,
+            descriptor_pool,
+
+            sky_renderer,
+            chunk_renderer,
+            weather_renderer,
+            outline_renderer,
+            crack_renderer,
+            hud_renderer,
 
             image_available_semaphores: sync_objects.image_available_semaphores,
             render_finished_semaphores: sync_objects.render_finished_semaphores,
             in_flight_fences: sync_objects.inflight_fences,
             current_frame: 0,
+            submitted_frames: 0,
+
+            is_framebuffer_resized: false,
+
+            jobs,
+            world,
+            player,
+            interaction: BlockInteraction::new(),
+            camera,
+            target: None,
+            held_keys: HashSet::new(),
+            held_buttons: HashSet::new(),
+            is_focused: true,
+            console: Console::new(),
+            clock: TickClock::new(Instant::now()),
+            culling_stats: CullingStats::default(),
+            last_title_update: Instant::now()
+        };
 
-            is_framebuffer_resized: false
-        }
+        Ok(vulkan_engine)
+    }
+
+    /// Prints the GPUs that can be selected in the graphics settings
+    pub fn list_gpus() -> EngineResult<()> {
+        Setup::list_physical_devices()
     }
 
     pub fn run(self, event_loop: winit::event_loop::EventLoop<()>, keymappings : KeyMappings){
@@ -149,23 +410,210 @@ impl VulkanEngine {
     }
 
 
-    pub fn init_window(event_loop: &EventLoop<()>) -> winit::window::Window{
+    pub fn init_window(event_loop: &EventLoop<()>) -> EngineResult<winit::window::Window>{
         winit::window::WindowBuilder::new()
             .with_title(WINDOW_TITLE)
             .with_inner_size(winit::dpi::LogicalSize::new(WINDOW_WIDTH, WINDOW_HEIGHT))
             .build(event_loop)
-            .expect("Failed to create window")
+            .map_err(|error| EngineError::Window(error.to_string()))
     }
 
-    fn draw_frame(&mut self) {
-        let wait_fences = [self.in_flight_fences[self.current_frame]];
+    fn player_input(&self, keymappings: &KeyMappings) -> PlayerInput {
+        let is_held = |key: VirtualKeyCode| self.held_keys.contains(&key);
+        let axis = |positive: VirtualKeyCode, negative: VirtualKeyCode| {
+            is_held(positive) as i32 as f32 - is_held(negative) as i32 as f32
+        };
+
+        PlayerInput {
+            forward: axis(keymappings.forward, keymappings.backward),
+            strafe: axis(keymappings.right, keymappings.left),
+            jump: is_held(keymappings.jump),
+            sprint: is_held(keymappings.sprint),
+            sneak: is_held(keymappings.sneak)
+        }
+    }
+
+    /// Keys acted on once per press rather than while held
+    fn key_pressed(&mut self, key: VirtualKeyCode, keymappings: &KeyMappings) {
+        if key == keymappings.swap_offhand {
+            self.player.inventory.swap_offhand();
+        } else if let Some(slot) = keymappings.hotbar.iter().position(|&hotbar_key| hotbar_key == key) {
+            self.player.inventory.select(slot);
+        }
+    }
+
+    fn update(&mut self, keymappings: &KeyMappings) -> EngineResult<()> {
+        let now = Instant::now();
+        if now.duration_since(self.last_title_update) >= TITLE_UPDATE_INTERVAL {
+            let paused = if self.clock.is_paused() { " - paused" } else { "" };
+            self.window.set_title(&format!("{} - {}{}", WINDOW_TITLE, self.culling_stats, paused));
+            self.last_title_update = now;
+        }
+
+        while let Some(line) = self.console.poll() {
+            match Command::parse(&line) {
+                Ok(command) => println!("{}", command.execute(&mut self.world, Some(&mut self.player.inventory))),
+                Err(message) => println!("{}", message)
+            }
+        }
+
+        let input = self.player_input(keymappings);
+        let interaction_input = InteractionInput {
+            attack: self.held_buttons.contains(&MouseButton::Left),
+            use_item: self.held_buttons.contains(&MouseButton::Right)
+        };
+        for _ in 0..self.clock.advance(now) {
+            self.player.yaw = self.camera.yaw;
+            self.player.tick(&self.world, &input);
+            let target = raycast::raycast(&self.world, self.player.eye_position(1.0), self.camera.forward(), self.player.reach);
+            self.interaction.tick(&mut self.world, &mut self.player, target.as_ref(), &interaction_input);
+            self.world.tick();
+        }
+        self.camera.position = self.player.eye_position(self.clock.partial_tick());
+        self.target = raycast::raycast(&self.world, self.camera.position, self.camera.forward(), self.player.reach);
+        self.uploads.collect(&self.device, &mut self.allocator)?;
+
+        if self.shaders.reload_changed() {
+            self.rebuild_pipelines()?;
+        }
+
+        let camera_position = self.camera.position;
+        self.world.stream_around(
+            &self.jobs,
+            camera_position.x.floor() as i32,
+            camera_position.z.floor() as i32,
+            self.chunk_renderer.view_distance() as i32
+        );
+        self.world.receive_chunks();
+
+        let mut dirty_chunks = self.world.take_dirty_chunks();
+        for position in self.chunk_renderer.track_camera(&self.jobs, &self.world, camera_position) {
+            if !dirty_chunks.contains(&position) {
+                dirty_chunks.push(position);
+            }
+        }
+        self.chunk_renderer.queue_meshes(&self.jobs, &self.world, &dirty_chunks);
+        self.chunk_renderer.receive_meshes(
+            &self.device,
+            &mut self.allocator,
+            &mut self.uploads,
+            self.submitted_frames
+        )?;
+        self.chunk_renderer.sort_translucency(
+            &self.device,
+            &mut self.allocator,
+            &mut self.uploads,
+            self.submitted_frames,
+            camera_position
+        )?;
+        self.uploads.flush(&self.device)?;
+
+        Ok(())
+    }
+
+    /// Recreates every pipeline from the current shaders, keeping the swapchain
+    fn rebuild_pipelines(&mut self) -> EngineResult<()> {
+        unsafe {
+            self.device.device_wait_idle()?;
+        }
+        self.destroy_pipelines();
+        self.create_pipelines()
+    }
+
+    fn create_pipelines(&mut self) -> EngineResult<()> {
+        let (terrain_pipelines, pipeline_layout) = GraphicsPipeline::create_graphics_pipeline(
+            &self.device,
+            self.render_pass,
+            self.descriptor_set_layout,
+            &self.shaders,
+            self.pipeline_cache
+        )?;
+        self.terrain_pipelines = terrain_pipelines;
+        self.pipeline_layout = pipeline_layout;
+        self.sky_renderer.create_pipeline(&self.device, self.render_pass, &self.shaders, self.pipeline_cache)?;
+        self.weather_renderer.create_pipeline(&self.device, self.render_pass, &self.shaders, self.pipeline_cache)?;
+        self.outline_renderer.create_pipeline(&self.device, self.render_pass, &self.shaders, self.pipeline_cache)?;
+        self.crack_renderer.create_pipeline(&self.device, self.render_pass, &self.shaders, self.pipeline_cache)?;
+        self.hud_renderer.create_pipeline(&self.device, self.render_pass, &self.shaders, self.pipeline_cache)?;
 
-         unsafe {
-            self.device
-                .wait_for_fences(&wait_fences, true, std::u64::MAX)
-                .expect("Failed to wait for Fence!");
+        Ok(())
+    }
 
+    fn destroy_pipelines(&self) {
+        self.terrain_pipelines.destroy(&self.device);
+        unsafe {
+            self.device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.sky_renderer.destroy_pipeline(&self.device);
+        self.weather_renderer.destroy_pipeline(&self.device);
+        self.outline_renderer.destroy_pipeline(&self.device);
+        self.crack_renderer.destroy_pipeline(&self.device);
+        self.hud_renderer.destroy_pipeline(&self.device);
+    }
 
+    /// Collects this frame's draws from every renderer and records them
+    fn record_frame(&mut self, image_index: usize) -> EngineResult<()> {
+        self.draw_list.clear();
+        self.sky_renderer.draw(&mut self.draw_list, self.weather_renderer.descriptor_sets[image_index]);
+        self.culling_stats = self.chunk_renderer.draw(
+            &mut self.draw_list,
+            image_index,
+            &self.terrain_pipelines,
+            self.pipeline_layout,
+            &self.camera
+        );
+        let crack = self.interaction.crack_stage();
+        let cracked_block = crack.map_or(Block::Air, |(position, _)| self.world.get_block(position.x, position.y, position.z));
+        self.crack_renderer.draw(&mut self.draw_list, image_index, crack, cracked_block);
+        self.outline_renderer.draw(
+            &mut self.draw_list,
+            self.weather_renderer.descriptor_sets[image_index],
+            self.target.as_ref()
+        );
+        self.weather_renderer.draw(&mut self.draw_list, image_index);
+        self.hud_renderer.draw(&mut self.draw_list, image_index, self.swapchain_extent, &self.player.inventory);
+
+        Buffers::record_command_buffer(
+            &self.device,
+            self.command_pools[self.current_frame],
+            self.command_buffers[self.current_frame],
+            self.swapchain_framebuffers[image_index],
+            self.render_pass,
+            self.swapchain_extent,
+            &self.draw_list
+        )
+    }
+
+    fn update_uniform_buffer(&self, image_index: usize) {
+        let time = self.clock.game_time();
+        let partial_tick = self.clock.partial_tick();
+        let weather = &self.world.weather;
+        let sky_color = weather.sky_color(SKY_COLOR, partial_tick);
+
+        let ubos = [UniformBufferObject {
+            model: Matrix4::identity(),
+            view: self.camera.view_matrix(),
+            proj: self.camera.projection_matrix(),
+            sky_color: [sky_color[0], sky_color[1], sky_color[2], 1.0],
+            weather: [time, weather.lightning_flash(partial_tick), weather.rain_level(partial_tick), weather.thunder_level(partial_tick)],
+            lighting: [1.0 - weather.sky_darkening(partial_tick) * 0.5, weather.lightning_flash(partial_tick), 0.0, 0.0],
+        }];
+
+        self.uniform_buffer_allocations[image_index].write(&ubos);
+
+        self.weather_renderer.update(image_index, &self.world, self.camera.position, time, partial_tick);
+    }
+
+    fn draw_frame(&mut self) -> EngineResult<()> {
+        let window_size = self.window.inner_size();
+        if window_size.width == 0 || window_size.height == 0 {
+            // minimized, the swapchain can't have a zero extent
+            return Ok(());
+        }
+        let wait_fences = [self.in_flight_fences[self.current_frame]];
+
+        unsafe {
+            self.device.wait_for_fences(&wait_fences, true, std::u64::MAX)?;
         };
 
         let (image_index, _is_sub_optimal) = unsafe {
@@ -179,16 +627,24 @@ impl VulkanEngine {
 
             match result {
                 Ok(image_index) => image_index,
-                Err(vk_result) => match vk_result {
-                    vk::Result::ERROR_OUT_OF_DATE_KHR => {
-                        self.recreate_swapchain();
-                        return;
-                    }
-                    _ => panic!("Failed to acquire Swap Chain Image!"),
-                }
+                Err(vk::Result::ERROR_OUT_OF_DATE_KHR) => return self.recreate_swapchain(),
+                Err(vk_result) => return Err(EngineError::Vulkan(vk_result))
             }
         };
 
+        // the image may come up before the frame that last drew to it, under another
+        // frame slot, has finished reading its buffers
+        let image_fence = self.images_in_flight[image_index as usize];
+        if image_fence != vk::Fence::null() {
+            unsafe {
+                self.device.wait_for_fences(&[image_fence], true, u64::MAX)?;
+            }
+        }
+        self.images_in_flight[image_index as usize] = self.in_flight_fences[self.current_frame];
+
+        self.update_uniform_buffer(image_index as usize);
+        self.record_frame(image_index as usize)?;
+
         let wait_semaphores = [self.image_available_semaphores[self.current_frame]];
         let wait_stages = [vk::PipelineStageFlags::COLOR_ATTACHMENT_OUTPUT];
         let signal_semaphores = [self.render_finished_semaphores[self.current_frame]];
@@ -200,24 +656,21 @@ impl VulkanEngine {
             p_wait_semaphores: wait_semaphores.as_ptr(),
             p_wait_dst_stage_mask: wait_stages.as_ptr(),
             command_buffer_count: 1,
-            p_command_buffers: &self.command_buffers[image_index as usize],
+            p_command_buffers: &self.command_buffers[self.current_frame],
             signal_semaphore_count: signal_semaphores.len() as u32,
             p_signal_semaphores: signal_semaphores.as_ptr(),
         }];
 
         unsafe {
-            self.device
-                .reset_fences(&wait_fences)
-                .expect("Failed to reset Fence!");
-
-            self.device
-                .queue_submit(
-                    self.graphics_queue,
-                    &submit_infos,
-                    self.in_flight_fences[self.current_frame],
-                )
-                .expect("Failed to execute queue submit.");
+            self.device.reset_fences(&wait_fences)?;
+
+            self.device.queue_submit(
+                self.graphics_queue,
+                &submit_infos,
+                self.in_flight_fences[self.current_frame],
+            )?;
         }
+        self.submitted_frames += 1;
 
         let swapchains = [self.swapchain];
 
@@ -239,33 +692,40 @@ impl VulkanEngine {
 
         let is_resized = match result {
             Ok(_) => self.is_framebuffer_resized,
-            Err(vk_result) => match vk_result {
-                vk::Result::ERROR_OUT_OF_DATE_KHR | vk::Result::SUBOPTIMAL_KHR => true,
-                _ => panic!("Failed to execute queue present."),
-            },
+            Err(vk::Result::ERROR_OUT_OF_DATE_KHR) | Err(vk::Result::SUBOPTIMAL_KHR) => true,
+            Err(vk_result) => return Err(EngineError::Vulkan(vk_result)),
         };
         if is_resized {
             self.is_framebuffer_resized = false;
-            self.recreate_swapchain();
+            self.recreate_swapchain()?;
         }
 
         self.current_frame = (self.current_frame + 1) % MAX_FRAMES_IN_FLIGHT;
+
+        Ok(())
     }
 
-    fn recreate_swapchain(&mut self) {
+    /// Rebuilds everything sized after the window. The render pass and pipelines are only
+    /// rebuilt when the surface format changes, viewport and scissor are dynamic.
+    fn recreate_swapchain(&mut self) -> EngineResult<()> {
+        let window_size = self.window.inner_size();
+        if window_size.width == 0 || window_size.height == 0 {
+            // minimized, retried once the window has a size again
+            self.is_framebuffer_resized = true;
+            return Ok(());
+        }
+
         // parameters -------------
         let surface_stuff = SurfaceStruct {
             surface_loader: self.surface_loader.clone(),
             surface: self.surface,
-            screen_width: WINDOW_WIDTH,
-            screen_height: WINDOW_HEIGHT,
+            screen_width: window_size.width,
+            screen_height: window_size.height,
         };
         // ------------------------
 
         unsafe {
-            self.device
-                .device_wait_idle()
-                .expect("Failed to wait device idle!")
+            self.device.device_wait_idle()?
         };
         self.cleanup_swapchain();
 
@@ -276,54 +736,99 @@ impl VulkanEngine {
             &self.window,
             &surface_stuff,
             &self.queue_family,
-        );
+        )?;
+        let is_format_changed = swapchain_stuff.swapchain_format != self.swapchain_format;
         self.swapchain_loader = swapchain_stuff.swapchain_loader;
         self.swapchain = swapchain_stuff.swapchain;
         self.swapchain_images = swapchain_stuff.swapchain_images;
+        self.images_in_flight = vec![vk::Fence::null(); self.swapchain_images.len()];
         self.swapchain_format = swapchain_stuff.swapchain_format;
         self.swapchain_extent = swapchain_stuff.swapchain_extent;
 
+        if is_format_changed {
+            self.destroy_pipelines();
+            unsafe {
+                self.device.destroy_render_pass(self.render_pass, None);
+            }
+            self.render_pass = GraphicsPipeline::create_render_pass(&self.device, self.swapchain_format, self.depth_format)?;
+            self.create_pipelines()?;
+        }
+
         self.swapchain_imageviews = Presentation::create_image_views(
             &self.device,
             self.swapchain_format,
             &self.swapchain_images,
-        );
-        self.render_pass = GraphicsPipeline::create_render_pass(&self.device, self.swapchain_format);
-        let (graphics_pipeline, pipeline_layout) = GraphicsPipeline::create_graphics_pipeline(
+        )?;
+        self.depth_buffer = DepthBuffer::new(
             &self.device,
-            self.render_pass,
-            swapchain_stuff.swapchain_extent,
-        );
-        self.graphics_pipeline = graphics_pipeline;
-        self.pipeline_layout = pipeline_layout;
+            &mut self.allocator,
+            self.depth_format,
+            self.swapchain_extent
+        )?;
 
         self.swapchain_framebuffers = Buffers::create_frame_buffers(
             &self.device,
             self.render_pass,
             &self.swapchain_imageviews,
+            self.depth_buffer.image_view,
             &self.swapchain_extent,
-        );
-        self.command_buffers = Buffers::create_command_buffers(
+        )?;
+
+        let (uniform_buffers, uniform_buffer_allocations) = Buffers::create_uniform_buffers::<UniformBufferObject>(
             &self.device,
-            self.command_pool,
-            self.graphics_pipeline,
-            &self.swapchain_framebuffers,
-            self.render_pass,
-            self.swapchain_extent,
-        );
+            &mut self.allocator,
+            self.swapchain_images.len()
+        )?;
+        self.uniform_buffers = uniform_buffers;
+        self.uniform_buffer_allocations = uniform_buffer_allocations;
+        self.descriptor_pool = Descriptors::create_descriptor_pool(&self.device, self.swapchain_images.len())?;
+
+        self.weather_renderer.create_swapchain_resources(
+            &self.device,
+            &mut self.allocator,
+            self.descriptor_pool,
+            self.descriptor_set_layout,
+            &self.uniform_buffers
+        )?;
+        self.chunk_renderer.create_swapchain_resources(
+            &self.device,
+            &mut self.allocator,
+            self.descriptor_pool,
+            self.descriptor_set_layout,
+            &self.uniform_buffers
+        )?;
+        self.crack_renderer.create_swapchain_resources(
+            &self.device,
+            self.descriptor_pool,
+            self.descriptor_set_layout,
+            &self.uniform_buffers
+        )?;
+        self.hud_renderer.create_swapchain_resources(
+            &self.device,
+            self.descriptor_pool,
+            self.descriptor_set_layout,
+            &self.uniform_buffers
+        )?;
+
+        self.camera.aspect = self.swapchain_extent.width as f32 / self.swapchain_extent.height as f32;
+
+        Ok(())
     }
 
-    fn cleanup_swapchain(&self) {
+    fn cleanup_swapchain(&mut self) {
         unsafe {
-            self.device
-                .free_command_buffers(self.command_pool, &self.command_buffers);
             for &framebuffer in self.swapchain_framebuffers.iter() {
                 self.device.destroy_framebuffer(framebuffer, None);
             }
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
-            self.device
-                .destroy_pipeline_layout(self.pipeline_layout, None);
-            self.device.destroy_render_pass(self.render_pass, None);
+            self.depth_buffer.destroy(&self.device, &mut self.allocator);
+
+            self.weather_renderer.destroy_swapchain_resources(&self.device, &mut self.allocator);
+            self.chunk_renderer.destroy_swapchain_resources(&self.device, &mut self.allocator);
+            self.device.destroy_descriptor_pool(self.descriptor_pool, None);
+            for (&buffer, allocation) in self.uniform_buffers.iter().zip(self.uniform_buffer_allocations.iter()) {
+                Buffers::destroy_buffer(&self.device, &mut self.allocator, buffer, allocation);
+            }
+
             for &image_view in self.swapchain_imageviews.iter() {
                 self.device.destroy_image_view(image_view, None);
             }
@@ -332,7 +837,7 @@ impl VulkanEngine {
         }
     }
 
-    fn create_sync_objects(device: &ash::Device) -> SyncObjects {
+    fn create_sync_objects(device: &ash::Device) -> EngineResult<SyncObjects> {
         let mut sync_objects = SyncObjects {
             image_available_semaphores: vec![],
             render_finished_semaphores: vec![],
@@ -353,15 +858,9 @@ impl VulkanEngine {
 
         for _ in 0..MAX_FRAMES_IN_FLIGHT {
             unsafe {
-                let image_available_semaphore = device
-                    .create_semaphore(&semaphore_create_info, None)
-                    .expect("Failed to create Semaphore Object!");
-                let render_finished_semaphore = device
-                    .create_semaphore(&semaphore_create_info, None)
-                    .expect("Failed to create Semaphore Object!");
-                let inflight_fence = device
-                    .create_fence(&fence_create_info, None)
-                    .expect("Failed to create Fence Object!");
+                let image_available_semaphore = device.create_semaphore(&semaphore_create_info, None)?;
+                let render_finished_semaphore = device.create_semaphore(&semaphore_create_info, None)?;
+                let inflight_fence = device.create_fence(&fence_create_info, None)?;
 
                 sync_objects
                     .image_available_semaphores
@@ -373,7 +872,7 @@ impl VulkanEngine {
             }
         }
 
-        sync_objects
+        Ok(sync_objects)
     }
 
     fn main_loop(mut self, event_loop: EventLoop<()>, keymappings : KeyMappings){
@@ -388,37 +887,101 @@ impl VulkanEngine {
                         | WindowEvent::CloseRequested => {
                             *control_flow = ControlFlow::Exit
                         },
+                        | WindowEvent::Resized(_) => {
+                            self.is_framebuffer_resized = true
+                        },
+                        | WindowEvent::Focused(is_focused) => {
+                            self.is_focused = is_focused;
+                            if !is_focused {
+                                self.held_keys.clear();
+                                self.held_buttons.clear();
+                            }
+                            // not every platform can grab the cursor, the camera turns anyway
+                            let _ = self.window.set_cursor_grab(is_focused);
+                            self.window.set_cursor_visible(!is_focused);
+                        },
                         | WindowEvent::KeyboardInput { input, .. } => {
                             match input {
                                 | KeyboardInput { virtual_keycode, state, .. } => {
                                     match (virtual_keycode, state) {
-                                        | (Some(escape), ElementState::Pressed) => {
+                                        | (Some(key), ElementState::Pressed) if key == keymappings.menu => {
                                             *control_flow = ControlFlow::Exit
                                         },
+                                        | (Some(key), ElementState::Pressed) if key == keymappings.pause => {
+                                            let is_paused = !self.clock.is_paused();
+                                            self.clock.set_paused(is_paused);
+                                        },
+                                        | (Some(key), ElementState::Pressed) => {
+                                            // held keys repeat their presses
+                                            if self.held_keys.insert(key) {
+                                                self.key_pressed(key, &keymappings);
+                                            }
+                                        },
+                                        | (Some(key), ElementState::Released) => {
+                                            self.held_keys.remove(&key);
+                                        },
                                         | _ => {},
                                     }
                                 },
                             }
                         },
+                        | WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Middle, .. } => {
+                            if let Some(target) = self.target {
+                                self.player.inventory.pick(target.block.name());
+                            }
+                        },
+                        | WindowEvent::MouseWheel { delta, .. } => {
+                            let steps = match delta {
+                                MouseScrollDelta::LineDelta(_, y) => y,
+                                MouseScrollDelta::PixelDelta(position) => position.y as f32
+                            };
+                            // scrolling down moves the selection right, like vanilla
+                            if steps != 0.0 {
+                                self.player.inventory.scroll(-steps.signum() as i32);
+                            }
+                        },
+                        | WindowEvent::MouseInput { state: ElementState::Pressed, button, .. } => {
+                            self.held_buttons.insert(button);
+                        },
+                        | WindowEvent::MouseInput { state: ElementState::Released, button, .. } => {
+                            self.held_buttons.remove(&button);
+                        },
                         | _ => {},
                     }
                 },
+                | Event::DeviceEvent { event: DeviceEvent::MouseMotion { delta: (x, y) }, .. }
+                    if self.is_focused && !self.clock.is_paused() => {
+                    self.camera.yaw += x as f32 * MOUSE_SENSITIVITY;
+                    self.camera.pitch = (self.camera.pitch - y as f32 * MOUSE_SENSITIVITY).clamp(-89.0, 89.0);
+                },
                 | Event::MainEventsCleared => {
-                   self.window.request_redraw();
+                    match self.update(&keymappings) {
+                        Ok(()) => self.window.request_redraw(),
+                        Err(error) => VulkanEngine::stop(control_flow, error)
+                    }
                 },
                 | Event::RedrawRequested(_window_id) => {
-                    self.draw_frame();
+                    if let Err(error) = self.draw_frame() {
+                        VulkanEngine::stop(control_flow, error);
+                    }
                 },
                 | Event::LoopDestroyed => {
+                    self.world.save_and_flush();
                     unsafe {
-                        self.device.device_wait_idle()
-                            .expect("failed to wait device idle")
+                        // a lost device has nothing left to wait for
+                        let _ = self.device.device_wait_idle();
                     };
                 }
                 _ => (),
             }
         })
     }
+
+    /// Leaves the event loop after an error while running, the world still gets saved
+    fn stop(control_flow: &mut ControlFlow, error: EngineError) {
+        eprintln!("Stopping: {}", error);
+        *control_flow = ControlFlow::Exit;
+    }
 }
 
 impl Drop for VulkanEngine {
@@ -437,21 +1000,28 @@ impl Drop for VulkanEngine {
                 self.device.destroy_fence(fence, None)
             }
 
-            self.device.destroy_command_pool(self.command_pool, None);
-
-            for &framebuffer in self.swapchain_framebuffers.iter(){
-                self.device.destroy_framebuffer(framebuffer, None);
-            }
-
-            self.device.destroy_pipeline(self.graphics_pipeline, None);
+            self.cleanup_swapchain();
+            self.terrain_pipelines.destroy(&self.device);
             self.device.destroy_pipeline_layout(self.pipeline_layout, None);
             self.device.destroy_render_pass(self.render_pass, None);
 
-            for &imageview in self.swapchain_imageviews.iter(){
-                self.device.destroy_image_view(imageview, None)
+            self.weather_renderer.destroy(&self.device, &mut self.allocator);
+            self.chunk_renderer.destroy(&self.device, &mut self.allocator);
+            self.sky_renderer.destroy(&self.device);
+            self.outline_renderer.destroy(&self.device);
+            self.crack_renderer.destroy(&self.device, &mut self.allocator);
+            self.hud_renderer.destroy(&self.device, &mut self.allocator);
+            self.device.destroy_descriptor_set_layout(self.descriptor_set_layout, None);
+
+            for &command_pool in self.command_pools.iter() {
+                self.device.destroy_command_pool(command_pool, None);
             }
 
-            self.swapchain_loader.destroy_swapchain(self.swapchain, None);
+            self.uploads.destroy(&self.device, &mut self.allocator);
+            PipelineCache::save(&self.device, self.pipeline_cache);
+            self.device.destroy_pipeline_cache(self.pipeline_cache, None);
+
+            self.allocator.destroy();
 
             self.device.destroy_device(None);
 
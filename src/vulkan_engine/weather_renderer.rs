@@ -0,0 +1,301 @@
+use ash::vk;
+use ash::version::DeviceV1_0;
+use cgmath::Point3;
+use std::path::Path;
+
+use crate::vulkan_engine::buffers::Buffers;
+use crate::vulkan_engine::descriptors::Descriptors;
+use crate::vulkan_engine::draw_list::{DrawList, Material, Mesh};
+use crate::vulkan_engine::engine_error::EngineResult;
+use crate::vulkan_engine::memory_allocator::{Allocation, MemoryAllocator, MemoryUsage};
+use crate::vulkan_engine::graphics_pipeline::{GraphicsPipeline, PipelineDescription};
+use crate::vulkan_engine::shader_library::ShaderLibrary;
+use crate::vulkan_engine::texture::Texture;
+use crate::vulkan_engine::upload_manager::UploadManager;
+use crate::vulkan_engine::utilities::constants::TEXTURE_DIRECTORY;
+use crate::vulkan_engine::utilities::structures::VertexV3;
+use crate::world::biome::Precipitation;
+use crate::world::random::Random;
+use crate::world::world::World;
+
+// columns are drawn in a circle of this many blocks around the camera
+const PRECIPITATION_RADIUS: i32 = 10;
+const MAX_COLUMNS: usize = ((PRECIPITATION_RADIUS * 2 + 1) * (PRECIPITATION_RADIUS * 2 + 1)) as usize;
+const VERTICES_PER_COLUMN: usize = 6;
+// the vertex buffer holds a rain half followed by a snow half
+const MAX_VERTICES: usize = MAX_COLUMNS * VERTICES_PER_COLUMN;
+
+const RAIN_LAYER: u32 = 0;
+const SNOW_LAYER: u32 = 1;
+
+/// Draws rain and snow as camera facing columns that stop at the highest opaque block
+pub struct WeatherRenderer {
+    texture: Texture,
+    pipeline_layout: vk::PipelineLayout,
+    pipeline: vk::Pipeline,
+
+    vertex_buffers: Vec<vk::Buffer>,
+    vertex_allocations: Vec<Allocation>,
+    pub descriptor_sets: Vec<vk::DescriptorSet>
+}
+
+impl WeatherRenderer {
+    pub fn new(
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        uploads: &mut UploadManager,
+        descriptor_set_layout: vk::DescriptorSetLayout
+    ) -> EngineResult<WeatherRenderer> {
+        let texture_directory = Path::new(TEXTURE_DIRECTORY).join("environment");
+        let rain_path = texture_directory.join("rain.png");
+        let snow_path = texture_directory.join("snow.png");
+
+        let texture = Texture::new_array(
+            device,
+            allocator,
+            uploads,
+            &[rain_path.as_path(), snow_path.as_path()],
+            vk::SamplerAddressMode::REPEAT
+        )?;
+
+        let push_constant_ranges = [vk::PushConstantRange {
+            stage_flags: vk::ShaderStageFlags::FRAGMENT,
+            offset: 0,
+            size: std::mem::size_of::<u32>() as u32,
+        }];
+        let pipeline_layout = GraphicsPipeline::create_pipeline_layout(
+            device,
+            &[descriptor_set_layout],
+            &push_constant_ranges
+        )?;
+
+        Ok(WeatherRenderer {
+            texture,
+            pipeline_layout,
+            pipeline: vk::Pipeline::null(),
+            vertex_buffers: vec![],
+            vertex_allocations: vec![],
+            descriptor_sets: vec![]
+        })
+    }
+
+    /// (Re)creates the per swapchain image vertex buffers and descriptor sets
+    pub fn create_swapchain_resources(
+        &mut self,
+        device: &ash::Device,
+        allocator: &mut MemoryAllocator,
+        descriptor_pool: vk::DescriptorPool,
+        descriptor_set_layout: vk::DescriptorSetLayout,
+        uniform_buffers: &Vec<vk::Buffer>
+    ) -> EngineResult<()> {
+        self.vertex_buffers.clear();
+        self.vertex_allocations.clear();
+        for _ in 0..uniform_buffers.len() {
+            let (vertex_buffer, vertex_allocation) = Buffers::create_buffer(
+                device,
+                allocator,
+                (std::mem::size_of::<VertexV3>() * MAX_VERTICES * 2) as vk::DeviceSize,
+                vk::BufferUsageFlags::VERTEX_BUFFER,
+                MemoryUsage::CpuToGpu
+            )?;
+            self.vertex_buffers.push(vertex_buffer);
+            self.vertex_allocations.push(vertex_allocation);
+        }
+
+        self.descriptor_sets = Descriptors::create_descriptor_sets(
+            device,
+            descriptor_pool,
+            descriptor_set_layout,
+            uniform_buffers,
+            &self.texture
+        )?;
+
+        Ok(())
+    }
+
+    pub fn create_pipeline(
+        &mut self,
+        device: &ash::Device,
+        render_pass: vk::RenderPass,
+        shaders: &ShaderLibrary,
+        pipeline_cache: vk::PipelineCache
+    ) -> EngineResult<()> {
+        let binding_descriptions = VertexV3::get_binding_descriptions();
+        let attribute_descriptions = VertexV3::get_attribute_descriptions();
+
+        let description = PipelineDescription {
+            vertex_bindings: &binding_descriptions,
+            vertex_attributes: &attribute_descriptions,
+            cull_mode: vk::CullModeFlags::NONE,
+            blend_enable: true,
+            depth_test: true,
+            ..PipelineDescription::new("weather.vert.spv", "weather.frag.spv")
+        };
+        self.pipeline = GraphicsPipeline::create_pipeline(
+            device,
+            render_pass,
+            self.pipeline_layout,
+            &description,
+            shaders,
+            pipeline_cache
+        )?;
+
+        Ok(())
+    }
+
+    pub fn destroy_pipeline(&self, device: &ash::Device) {
+        unsafe {
+            device.destroy_pipeline(self.pipeline, None);
+        }
+    }
+
+    /// Descriptor sets are freed together with the pool
+    pub fn destroy_swapchain_resources(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        for (&buffer, allocation) in self.vertex_buffers.iter().zip(self.vertex_allocations.iter()) {
+            Buffers::destroy_buffer(device, allocator, buffer, allocation);
+        }
+    }
+
+    pub fn destroy(&self, device: &ash::Device, allocator: &mut MemoryAllocator) {
+        self.destroy_pipeline(device);
+        unsafe {
+            device.destroy_pipeline_layout(self.pipeline_layout, None);
+        }
+        self.texture.destroy(device, allocator);
+    }
+
+    pub fn draw(&self, draw_list: &mut DrawList, image_index: usize) {
+        let material = Material {
+            pipeline: self.pipeline,
+            pipeline_layout: self.pipeline_layout,
+            descriptor_set: self.descriptor_sets[image_index]
+        };
+
+        for &layer in [RAIN_LAYER, SNOW_LAYER].iter() {
+            // unused vertices are zeroed, so they collapse into degenerate triangles
+            let mesh = Mesh {
+                vertex_buffer: self.vertex_buffers[image_index],
+                first_vertex: layer * MAX_VERTICES as u32,
+                vertex_count: MAX_VERTICES as u32
+            };
+            draw_list.push_with_constants(material, mesh, vk::ShaderStageFlags::FRAGMENT, &layer.to_ne_bytes());
+        }
+    }
+
+    /// Rebuilds the precipitation columns of one swapchain image
+    pub fn update(
+        &self,
+        image_index: usize,
+        world: &World,
+        camera_position: Point3<f32>,
+        time: f32,
+        partial_tick: f32
+    ) {
+        let (mut rain, snow) = WeatherRenderer::build_columns(world, camera_position, time, partial_tick);
+
+        rain.resize(MAX_VERTICES, WeatherRenderer::empty_vertex());
+        rain.extend(snow);
+        rain.resize(MAX_VERTICES * 2, WeatherRenderer::empty_vertex());
+
+        self.vertex_allocations[image_index].write(&rain);
+    }
+
+    fn empty_vertex() -> VertexV3 {
+        VertexV3 {
+            pos: [0.0; 4],
+            color: [0.0; 4],
+            tex_coord: [0.0; 2]
+        }
+    }
+
+    /// Returns the rain and snow vertices around the camera
+    pub fn build_columns(
+        world: &World,
+        camera_position: Point3<f32>,
+        time: f32,
+        partial_tick: f32
+    ) -> (Vec<VertexV3>, Vec<VertexV3>) {
+        let mut rain = vec![];
+        let mut snow = vec![];
+
+        let rain_level = world.weather.rain_level(partial_tick);
+        if rain_level <= 0.0 {
+            return (rain, snow);
+        }
+
+        // precipitation gets darker with the sky and lights up with lightning
+        let brightness = (1.0 - world.weather.sky_darkening(partial_tick) * 0.5 + world.weather.lightning_flash(partial_tick)).min(1.0);
+
+        let camera_x = camera_position.x.floor() as i32;
+        let camera_y = camera_position.y.floor() as i32;
+        let camera_z = camera_position.z.floor() as i32;
+        let radius = PRECIPITATION_RADIUS as f32;
+
+        for x in camera_x - PRECIPITATION_RADIUS..=camera_x + PRECIPITATION_RADIUS {
+            for z in camera_z - PRECIPITATION_RADIUS..=camera_z + PRECIPITATION_RADIUS {
+                let dx = x as f32 + 0.5 - camera_position.x;
+                let dz = z as f32 + 0.5 - camera_position.z;
+                let distance = (dx * dx + dz * dz).sqrt();
+                if distance > radius {
+                    continue;
+                }
+
+                let ground = world.highest_opaque_block(x, z).map_or(0, |height| height + 1);
+                let bottom = ground.max(camera_y - PRECIPITATION_RADIUS);
+                let top = ground.max(camera_y + PRECIPITATION_RADIUS);
+                if bottom >= top {
+                    continue;
+                }
+
+                let precipitation = world.precipitation_at(x, ground, z);
+                if precipitation == Precipitation::None {
+                    continue;
+                }
+
+                // half width vector perpendicular to the view direction, so the column faces the camera
+                let (side_x, side_z) = if distance < 0.01 {
+                    (0.5, 0.0)
+                } else {
+                    (-dz / distance * 0.5, dx / distance * 0.5)
+                };
+
+                let alpha = ((1.0 - distance * distance / (radius * radius)) * 0.5 + 0.5) * rain_level;
+                let color = [brightness, brightness, brightness, alpha];
+
+                let offset = (Random::hash_2d(0, x, z) & 0xFFFF) as f32 / 65536.0;
+                let (u_offset, scroll) = match precipitation {
+                    Precipitation::Snow => (time * 0.05 * (offset - 0.5), time * 0.2 + offset),
+                    _ => (0.0, time * 1.5 + offset)
+                };
+
+                let (u0, u1) = (u_offset, u_offset + 1.0);
+                let v0 = bottom as f32 * 0.25 + scroll;
+                let v1 = top as f32 * 0.25 + scroll;
+
+                let center_x = x as f32 + 0.5;
+                let center_z = z as f32 + 0.5;
+                let corner = |sign: f32, y: i32, u: f32, v: f32| VertexV3 {
+                    pos: [center_x + side_x * sign, y as f32, center_z + side_z * sign, 1.0],
+                    color,
+                    tex_coord: [u, v]
+                };
+
+                let quad = [
+                    corner(-1.0, bottom, u0, v0),
+                    corner(1.0, bottom, u1, v0),
+                    corner(1.0, top, u1, v1),
+                    corner(1.0, top, u1, v1),
+                    corner(-1.0, top, u0, v1),
+                    corner(-1.0, bottom, u0, v0),
+                ];
+
+                match precipitation {
+                    Precipitation::Snow => snow.extend_from_slice(&quad),
+                    _ => rain.extend_from_slice(&quad)
+                }
+            }
+        }
+
+        (rain, snow)
+    }
+}
@@ -0,0 +1,94 @@
+use crate::world::generator::SEA_LEVEL;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Precipitation {
+    None,
+    Rain,
+    Snow
+}
+
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Biome {
+    Ocean,
+    Plains,
+    Desert,
+    Forest,
+    Taiga,
+    SnowyTundra,
+    Swamp,
+    Jungle,
+    Savanna
+}
+
+const SNOW_TEMPERATURE: f32 = 0.15;
+
+impl Biome {
+    /// Every biome in id order, the id is the index
+    pub const ALL: [Biome; 9] = [
+        Biome::Ocean,
+        Biome::Plains,
+        Biome::Desert,
+        Biome::Forest,
+        Biome::Taiga,
+        Biome::SnowyTundra,
+        Biome::Swamp,
+        Biome::Jungle,
+        Biome::Savanna
+    ];
+
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<Biome> {
+        Biome::ALL.get(id as usize).copied()
+    }
+
+    pub fn temperature(&self) -> f32 {
+        match self {
+            Biome::Ocean => 0.5,
+            Biome::Plains => 0.8,
+            Biome::Desert => 2.0,
+            Biome::Forest => 0.7,
+            Biome::Taiga => 0.25,
+            Biome::SnowyTundra => 0.0,
+            Biome::Swamp => 0.8,
+            Biome::Jungle => 0.95,
+            Biome::Savanna => 1.2
+        }
+    }
+
+    pub fn downfall(&self) -> f32 {
+        match self {
+            Biome::Ocean => 0.5,
+            Biome::Plains => 0.4,
+            Biome::Desert => 0.0,
+            Biome::Forest => 0.8,
+            Biome::Taiga => 0.8,
+            Biome::SnowyTundra => 0.5,
+            Biome::Swamp => 0.9,
+            Biome::Jungle => 0.9,
+            Biome::Savanna => 0.0
+        }
+    }
+
+    /// Temperature adjusted for height, so mountain tops in warm biomes still get snow
+    pub fn temperature_at(&self, y: i32) -> f32 {
+        if y > SEA_LEVEL {
+            self.temperature() - (y - SEA_LEVEL) as f32 * 0.05 / 30.0
+        } else {
+            self.temperature()
+        }
+    }
+
+    pub fn precipitation_at(&self, y: i32) -> Precipitation {
+        if self.downfall() <= 0.0 {
+            Precipitation::None
+        } else if self.temperature_at(y) < SNOW_TEMPERATURE {
+            Precipitation::Snow
+        } else {
+            Precipitation::Rain
+        }
+    }
+}
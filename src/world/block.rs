@@ -0,0 +1,248 @@
+/// Which terrain pass draws a block, in drawing order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RenderLayer {
+    Opaque,
+    // fully opaque or fully transparent texels, drawn with alpha testing
+    Cutout,
+    // blended, sorted back to front
+    Translucent
+}
+
+impl RenderLayer {
+    pub const ALL: [RenderLayer; 3] = [RenderLayer::Opaque, RenderLayer::Cutout, RenderLayer::Translucent];
+}
+
+#[repr(u16)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Block {
+    Air,
+    Stone,
+    Dirt,
+    Grass,
+    Sand,
+    Gravel,
+    Snow,
+    Water,
+    Bedrock,
+    OakLog,
+    OakLeaves,
+    Vine,
+    Torch,
+    Furnace,
+    Lava,
+    Cobblestone,
+    Obsidian
+}
+
+impl Block {
+    /// Every block in id order, the id is the index
+    pub const ALL: [Block; 17] = [
+        Block::Air,
+        Block::Stone,
+        Block::Dirt,
+        Block::Grass,
+        Block::Sand,
+        Block::Gravel,
+        Block::Snow,
+        Block::Water,
+        Block::Bedrock,
+        Block::OakLog,
+        Block::OakLeaves,
+        Block::Vine,
+        Block::Torch,
+        Block::Furnace,
+        Block::Lava,
+        Block::Cobblestone,
+        Block::Obsidian
+    ];
+
+    pub fn id(&self) -> u16 {
+        *self as u16
+    }
+
+    /// Vanilla name without the namespace, e.g. `oak_leaves`
+    pub fn from_name(name: &str) -> Option<Block> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        match name {
+            "air" => Some(Block::Air),
+            "stone" => Some(Block::Stone),
+            "dirt" => Some(Block::Dirt),
+            "grass_block" => Some(Block::Grass),
+            "sand" => Some(Block::Sand),
+            "gravel" => Some(Block::Gravel),
+            "snow_block" => Some(Block::Snow),
+            "water" => Some(Block::Water),
+            "bedrock" => Some(Block::Bedrock),
+            "oak_log" => Some(Block::OakLog),
+            "oak_leaves" => Some(Block::OakLeaves),
+            "vine" => Some(Block::Vine),
+            "torch" => Some(Block::Torch),
+            "furnace" => Some(Block::Furnace),
+            "lava" => Some(Block::Lava),
+            "cobblestone" => Some(Block::Cobblestone),
+            "obsidian" => Some(Block::Obsidian),
+            _ => None
+        }
+    }
+
+    /// Inverse of `from_name`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Block::Air => "air",
+            Block::Stone => "stone",
+            Block::Dirt => "dirt",
+            Block::Grass => "grass_block",
+            Block::Sand => "sand",
+            Block::Gravel => "gravel",
+            Block::Snow => "snow_block",
+            Block::Water => "water",
+            Block::Bedrock => "bedrock",
+            Block::OakLog => "oak_log",
+            Block::OakLeaves => "oak_leaves",
+            Block::Vine => "vine",
+            Block::Torch => "torch",
+            Block::Furnace => "furnace",
+            Block::Lava => "lava",
+            Block::Cobblestone => "cobblestone",
+            Block::Obsidian => "obsidian"
+        }
+    }
+
+    /// Opaque blocks stop precipitation and hide the faces of their neighbours
+    pub fn is_opaque(&self) -> bool {
+        !matches!(self, Block::Air | Block::Water | Block::Lava | Block::OakLeaves | Block::Vine | Block::Torch)
+    }
+
+    pub fn render_layer(&self) -> RenderLayer {
+        match self {
+            Block::Water => RenderLayer::Translucent,
+            Block::OakLeaves | Block::Vine | Block::Torch => RenderLayer::Cutout,
+            _ => RenderLayer::Opaque
+        }
+    }
+
+    /// How much light is lost passing through the block, 15 stops it completely
+    pub fn light_opacity(&self) -> u8 {
+        match self {
+            Block::Air | Block::Vine | Block::Torch => 0,
+            Block::OakLeaves => 1,
+            Block::Water => 3,
+            _ => 15
+        }
+    }
+
+    /// Height of the collision box standing on the bottom of the block, None when
+    /// entities pass through it
+    pub fn collision_height(&self) -> Option<f32> {
+        match self {
+            Block::Air | Block::Water | Block::Lava | Block::Vine | Block::Torch => None,
+            _ => Some(1.0)
+        }
+    }
+
+    pub fn is_fluid(&self) -> bool {
+        *self == Block::Water || *self == Block::Lava
+    }
+
+    /// Blocks that placing another block simply overwrites
+    pub fn is_replaceable(&self) -> bool {
+        *self == Block::Air || self.is_fluid()
+    }
+
+    /// How long the block resists breaking, vanilla values. None can't be broken.
+    pub fn hardness(&self) -> Option<f32> {
+        match self {
+            Block::Air | Block::Water | Block::Lava | Block::Bedrock => None,
+            Block::Torch => Some(0.0),
+            Block::Snow | Block::OakLeaves | Block::Vine => Some(0.2),
+            Block::Dirt | Block::Sand => Some(0.5),
+            Block::Grass | Block::Gravel => Some(0.6),
+            Block::Stone => Some(1.5),
+            Block::OakLog | Block::Cobblestone => Some(2.0),
+            Block::Furnace => Some(3.5),
+            Block::Obsidian => Some(50.0)
+        }
+    }
+
+    /// The block a broken one leaves as an item, stone crumbles to cobblestone and grass
+    /// loses its sod like in vanilla. Leaves and vines need shears.
+    pub fn dropped_block(&self) -> Option<Block> {
+        match self {
+            Block::Stone => Some(Block::Cobblestone),
+            Block::Grass => Some(Block::Dirt),
+            Block::Air | Block::Water | Block::Lava | Block::OakLeaves | Block::Vine => None,
+            _ => Some(*self)
+        }
+    }
+
+    /// Which way a newly placed block faces, from the face it was placed against and the
+    /// horizontal direction the player looks. Logs lie along the clicked axis, furnaces
+    /// turn their front towards the player like the `facing` property of vanilla blockstates.
+    pub fn placement_facing(&self, clicked_face: Face, player_facing: Face) -> Option<Face> {
+        match self {
+            Block::OakLog if clicked_face != Face::Up && clicked_face != Face::Down => Some(clicked_face),
+            Block::Furnace => Some(player_facing.opposite()),
+            _ => None
+        }
+    }
+
+    pub fn light_emission(&self) -> u8 {
+        match self {
+            Block::Torch => 14,
+            Block::Lava => 15,
+            _ => 0
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Face {
+    Up,
+    Down,
+    North,
+    South,
+    East,
+    West
+}
+
+impl Face {
+    pub const ALL: [Face; 6] = [Face::Up, Face::Down, Face::North, Face::South, Face::East, Face::West];
+
+    /// The horizontal face a yaw in degrees looks towards, yaw 0 looks north
+    pub fn from_yaw(yaw: f32) -> Face {
+        match (yaw.rem_euclid(360.0) / 90.0).round() as i32 % 4 {
+            0 => Face::North,
+            1 => Face::East,
+            2 => Face::South,
+            _ => Face::West
+        }
+    }
+
+    /// Logs lying east-west or north-south share their end textures on both opposite faces
+    pub fn is_same_axis(&self, other: Face) -> bool {
+        *self == other || *self == other.opposite()
+    }
+
+    /// Offset to the neighbouring block this face looks at, north is -z and east is +x
+    pub fn offset(&self) -> (i32, i32, i32) {
+        match self {
+            Face::Up => (0, 1, 0),
+            Face::Down => (0, -1, 0),
+            Face::North => (0, 0, -1),
+            Face::South => (0, 0, 1),
+            Face::East => (1, 0, 0),
+            Face::West => (-1, 0, 0)
+        }
+    }
+
+    pub fn opposite(&self) -> Face {
+        match self {
+            Face::Up => Face::Down,
+            Face::Down => Face::Up,
+            Face::North => Face::South,
+            Face::South => Face::North,
+            Face::East => Face::West,
+            Face::West => Face::East
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+
+use crate::world::block::{Block, Face};
+use crate::world::block_registry::BlockRegistry;
+
+/// A block as a chunk keeps it: the block, its facing and its fluid level
+pub type PlacedBlock = (Block, Option<Face>, u8);
+
+// fluid levels the registry has states for
+const FLUID_LEVELS: u8 = 16;
+
+/// The ids chunks are saved with. A state gets the next free id the first time the world
+/// can store it and keeps it for good. The names are saved with the level, so saved
+/// chunks stay valid when the registry numbers its states differently.
+pub struct BlockPalette {
+    // registry state name by saved id
+    names: Vec<String>,
+    // None for saved states this version can't place
+    blocks: Vec<Option<PlacedBlock>>,
+    // saved ids of blocks with a facing or fluid level, see `plain_ids` for the rest
+    ids: HashMap<PlacedBlock, u16>,
+    // saved id by block id, for blocks without a facing and fluid level
+    plain_ids: Vec<u16>
+}
+
+impl BlockPalette {
+    /// Continues the palette `names` of a saved world, empty for a new one, and adds the
+    /// states it is missing. Fails when the registry has no state for a block.
+    pub fn new(names: Vec<String>, registry: &BlockRegistry) -> std::io::Result<BlockPalette> {
+        let mut palette = BlockPalette {
+            blocks: names.iter()
+                .map(|name| registry.parse_state(name).and_then(|state| registry.world_block(state)))
+                .collect(),
+            names,
+            ids: HashMap::new(),
+            plain_ids: vec![]
+        };
+        let mut saved_ids: HashMap<String, u16> = palette.names.iter()
+            .enumerate()
+            .map(|(id, name)| (name.clone(), id as u16))
+            .collect();
+
+        for &block in Block::ALL.iter() {
+            let levels = if block.is_fluid() { FLUID_LEVELS } else { 1 };
+            for facing in [None].into_iter().chain(Face::ALL.iter().copied().map(Some)) {
+                for fluid_level in 0..levels {
+                    let state = match registry.state_of(block, facing, fluid_level) {
+                        Some(state) => state,
+                        None if facing.is_none() && fluid_level == 0 => {
+                            let message = format!("no block state for {}", block.name());
+                            return Err(Error::new(ErrorKind::InvalidData, message));
+                        }
+                        // a facing the block can't have
+                        None => continue
+                    };
+
+                    let name = registry.state_name(state).unwrap();
+                    let id = match saved_ids.get(&name) {
+                        Some(&id) => id,
+                        None => {
+                            if palette.names.len() > u16::MAX as usize {
+                                return Err(Error::new(ErrorKind::InvalidData, "too many block states for a saved id"));
+                            }
+                            let id = palette.names.len() as u16;
+                            palette.names.push(name.clone());
+                            palette.blocks.push(registry.world_block(state));
+                            saved_ids.insert(name, id);
+                            id
+                        }
+                    };
+
+                    if facing.is_none() && fluid_level == 0 {
+                        palette.plain_ids.push(id);
+                    } else {
+                        palette.ids.insert((block, facing, fluid_level), id);
+                    }
+                }
+            }
+        }
+
+        Ok(palette)
+    }
+
+    /// State names by saved id, for the level data
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Saved ids are below this
+    pub fn state_count(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The saved id of a block, a facing the block can't have is left out
+    pub fn id(&self, block: Block, facing: Option<Face>, fluid_level: u8) -> u16 {
+        if facing.is_none() && fluid_level == 0 {
+            return self.plain_ids[block.id() as usize];
+        }
+        self.ids
+            .get(&(block, facing, fluid_level))
+            .copied()
+            .unwrap_or(self.plain_ids[block.id() as usize])
+    }
+
+    /// None for ids the palette doesn't have or states this version can't place
+    pub fn block(&self, id: u16) -> Option<PlacedBlock> {
+        self.blocks.get(id as usize).copied().flatten()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_registry::{world_block_definitions, world_block_registry};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn every_block_reads_back_as_itself() {
+        let palette = BlockPalette::new(vec![], &world_block_registry()).unwrap();
+
+        assert_eq!(palette.id(Block::Air, None, 0), 0);
+        for &block in Block::ALL.iter() {
+            let (read, _, _) = palette.block(palette.id(block, None, 0)).unwrap();
+            assert_eq!(read, block);
+        }
+        let furnace = palette.id(Block::Furnace, Some(Face::West), 0);
+        assert_eq!(palette.block(furnace), Some((Block::Furnace, Some(Face::West), 0)));
+        let water = palette.id(Block::Water, None, 5);
+        assert_eq!(palette.block(water), Some((Block::Water, None, 5)));
+        // furnaces can't face up, stone has no facing at all
+        assert_eq!(palette.id(Block::Furnace, Some(Face::Up), 0), palette.id(Block::Furnace, None, 0));
+        assert_eq!(palette.id(Block::Stone, Some(Face::East), 0), palette.id(Block::Stone, None, 0));
+    }
+
+    #[test]
+    fn saved_ids_survive_a_renumbered_registry() {
+        let palette = BlockPalette::new(vec![], &world_block_registry()).unwrap();
+        let furnace = palette.id(Block::Furnace, Some(Face::South), 0);
+
+        // a new blockstate file sorted before the others shifts every registry state after air
+        let mut blocks = world_block_definitions();
+        blocks.push(("acacia_log".to_string(), BTreeMap::new()));
+        let registry = BlockRegistry::new(blocks).unwrap();
+        let old_state = world_block_registry().state_of(Block::Furnace, Some(Face::South), 0);
+        assert_ne!(registry.state_of(Block::Furnace, Some(Face::South), 0), old_state);
+
+        let reloaded = BlockPalette::new(palette.names().to_vec(), &registry).unwrap();
+        assert_eq!(reloaded.names(), palette.names());
+        assert_eq!(reloaded.id(Block::Furnace, Some(Face::South), 0), furnace);
+        assert_eq!(reloaded.block(furnace), Some((Block::Furnace, Some(Face::South), 0)));
+    }
+
+    #[test]
+    fn states_the_registry_lost_read_as_unknown() {
+        let mut names = vec!["air".to_string(), "granite".to_string()];
+        names.extend(BlockPalette::new(vec![], &world_block_registry()).unwrap().names()[1..].iter().cloned());
+
+        let palette = BlockPalette::new(names, &world_block_registry()).unwrap();
+        assert_eq!(palette.block(1), None);
+        assert_eq!(palette.block(palette.state_count() as u16), None);
+        assert_eq!(palette.id(Block::Stone, None, 0), 2);
+    }
+
+    #[test]
+    fn registry_without_a_block_is_an_error() {
+        assert!(BlockPalette::new(vec![], &BlockRegistry::new(vec![]).unwrap()).is_err());
+    }
+}
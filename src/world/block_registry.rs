@@ -0,0 +1,551 @@
+use serde_json::Value;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use crate::world::block::{Block, Face, RenderLayer};
+
+pub const BLOCKSTATE_DIRECTORY: &str = "TextureData/assets/minecraft/blockstates";
+
+/// Dense id of one combination of a block and its property values. Ids follow the
+/// blockstate files, adding or removing one renumbers the states after it, so saves
+/// store `BlockPalette` ids instead.
+pub type StateId = u16;
+
+// properties the game keeps that no model depends on, so the blockstate files don't list them
+const EXTRA_PROPERTIES: [(&str, &str, &[&str]); 2] = [
+    ("water", "level", &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15"]),
+    ("lava", "level", &["0", "1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11", "12", "13", "14", "15"])
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BlockMetadata {
+    // light lost passing through, 15 stops it completely
+    pub light_opacity: u8,
+    // a full cube entities collide with
+    pub is_solid: bool,
+    pub light_emission: u8,
+    // None can't be broken
+    pub hardness: Option<f32>,
+    pub render_layer: RenderLayer
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+    pub name: String,
+    // the first value is the default
+    pub values: Vec<String>
+}
+
+pub struct BlockDefinition {
+    pub name: String,
+    // sorted by name, the last one changes fastest between consecutive state ids
+    pub properties: Vec<Property>,
+    pub metadata: BlockMetadata,
+    pub first_state: StateId
+}
+
+impl BlockDefinition {
+    fn property_index(&self, name: &str) -> Option<usize> {
+        self.properties.iter().position(|property| property.name == name)
+    }
+
+    /// Index of every property's value in a state of this block
+    fn value_indices(&self, state: StateId) -> Vec<usize> {
+        let mut remaining = (state - self.first_state) as usize;
+        let mut indices = vec![0; self.properties.len()];
+        for (i, property) in self.properties.iter().enumerate().rev() {
+            indices[i] = remaining % property.values.len();
+            remaining /= property.values.len();
+        }
+        indices
+    }
+
+    /// The value a state of this block has for a property, None when the block has no such property
+    fn value(&self, state: StateId, property: &str) -> Option<&str> {
+        let i = self.property_index(property)?;
+        Some(&self.properties[i].values[self.value_indices(state)[i]])
+    }
+
+    fn state_from_indices(&self, indices: &[usize]) -> StateId {
+        let offset = self.properties.iter()
+            .zip(indices.iter())
+            .fold(0, |offset, (property, &index)| offset * property.values.len() + index);
+        self.first_state + offset as StateId
+    }
+}
+
+/// Every vanilla block with its properties and metadata. Blocks are ordered by name
+/// with air first, so state 0 is always air.
+pub struct BlockRegistry {
+    blocks: Vec<BlockDefinition>,
+    block_indices: HashMap<String, usize>,
+    // index into blocks of every state
+    state_blocks: Vec<u16>
+}
+
+impl BlockRegistry {
+    /// Derives the properties of every block from the variant keys and multipart
+    /// conditions of the blockstate files in `directory`
+    pub fn load(directory: &Path) -> std::io::Result<BlockRegistry> {
+        let mut blocks = vec![];
+        for entry in fs::read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |extension| extension != "json") {
+                continue;
+            }
+            let name = path.file_stem().unwrap().to_string_lossy().to_string();
+            let content: Value = serde_json::from_slice(&fs::read(&path)?)
+                .map_err(|error| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))?;
+            blocks.push((name, read_properties(&content)));
+        }
+
+        BlockRegistry::new(blocks)
+    }
+
+    /// Builds the registry from each block's property values in any order
+    pub fn new(blocks: Vec<(String, BTreeMap<String, BTreeSet<String>>)>) -> std::io::Result<BlockRegistry> {
+        let mut blocks = blocks;
+        for (name, properties) in blocks.iter_mut() {
+            for &(block, property, values) in EXTRA_PROPERTIES.iter() {
+                if name == block {
+                    let entry = properties.entry(property.to_string()).or_default();
+                    entry.extend(values.iter().map(|value| value.to_string()));
+                }
+            }
+        }
+        blocks.sort_by(|(a, _), (b, _)| (a != "air", a).cmp(&(b != "air", b)));
+
+        let mut definitions = Vec::with_capacity(blocks.len());
+        let mut block_indices = HashMap::new();
+        let mut state_blocks = vec![];
+        for (name, properties) in blocks {
+            let properties: Vec<Property> = properties.into_iter()
+                .map(|(name, values)| Property { name, values: sort_values(values) })
+                .collect();
+            let state_count: u32 = properties.iter().map(|property| property.values.len() as u32).product();
+
+            if state_blocks.len() + state_count as usize > StateId::MAX as usize + 1 {
+                return Err(Error::new(ErrorKind::InvalidData, "too many block states for a state id"));
+            }
+            let first_state = state_blocks.len() as StateId;
+            state_blocks.extend(std::iter::repeat(definitions.len() as u16).take(state_count as usize));
+
+            block_indices.insert(name.clone(), definitions.len());
+            definitions.push(BlockDefinition {
+                metadata: block_metadata(&name),
+                name,
+                properties,
+                first_state
+            });
+        }
+
+        Ok(BlockRegistry {
+            blocks: definitions,
+            block_indices,
+            state_blocks
+        })
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.state_blocks.len()
+    }
+
+    pub fn blocks(&self) -> &[BlockDefinition] {
+        &self.blocks
+    }
+
+    /// Vanilla name without the namespace, e.g. `oak_log`
+    pub fn block(&self, name: &str) -> Option<&BlockDefinition> {
+        let name = name.strip_prefix("minecraft:").unwrap_or(name);
+        self.block_indices.get(name).map(|&index| &self.blocks[index])
+    }
+
+    pub fn block_of(&self, state: StateId) -> Option<&BlockDefinition> {
+        self.state_blocks.get(state as usize).map(|&index| &self.blocks[index as usize])
+    }
+
+    pub fn metadata(&self, state: StateId) -> Option<BlockMetadata> {
+        self.block_of(state).map(|block| block.metadata)
+    }
+
+    pub fn default_state(&self, name: &str) -> Option<StateId> {
+        self.block(name).map(|block| block.first_state)
+    }
+
+    /// The state of a block with the given property values, properties left out keep their default
+    pub fn state_id(&self, name: &str, properties: &[(&str, &str)]) -> Option<StateId> {
+        let block = self.block(name)?;
+        let mut indices = vec![0; block.properties.len()];
+        for &(property, value) in properties.iter() {
+            let i = block.property_index(property)?;
+            indices[i] = block.properties[i].values.iter().position(|candidate| candidate == value)?;
+        }
+        Some(block.state_from_indices(&indices))
+    }
+
+    /// Vanilla notation such as `furnace[facing=north,lit=false]`
+    pub fn state_name(&self, state: StateId) -> Option<String> {
+        let block = self.block_of(state)?;
+        if block.properties.is_empty() {
+            return Some(block.name.clone());
+        }
+
+        let values: Vec<String> = block.properties.iter()
+            .zip(block.value_indices(state))
+            .map(|(property, index)| format!("{}={}", property.name, property.values[index]))
+            .collect();
+        Some(format!("{}[{}]", block.name, values.join(",")))
+    }
+
+    /// Reads the notation of `state_name`, the brackets are optional
+    pub fn parse_state(&self, text: &str) -> Option<StateId> {
+        let (name, properties) = match text.find('[') {
+            Some(start) => (&text[..start], text[start + 1..].strip_suffix(']')?),
+            None => (text, "")
+        };
+
+        let mut pairs = vec![];
+        for pair in properties.split(',').filter(|pair| !pair.is_empty()) {
+            let (property, value) = pair.split_once('=')?;
+            pairs.push((property.trim(), value.trim()));
+        }
+        self.state_id(name, &pairs)
+    }
+
+    /// The state a block the world stores with its facing and fluid level corresponds to
+    pub fn state_of(&self, block: Block, facing: Option<Face>, fluid_level: u8) -> Option<StateId> {
+        match (block, facing) {
+            (Block::OakLog, Some(Face::East)) | (Block::OakLog, Some(Face::West)) => {
+                self.state_id(block.name(), &[("axis", "x")])
+            }
+            (Block::OakLog, Some(Face::North)) | (Block::OakLog, Some(Face::South)) => {
+                self.state_id(block.name(), &[("axis", "z")])
+            }
+            (Block::OakLog, _) => self.state_id(block.name(), &[("axis", "y")]),
+            (Block::Furnace, facing) => {
+                self.state_id(block.name(), &[("facing", face_name(facing.unwrap_or(Face::North)))])
+            }
+            (Block::Water, _) | (Block::Lava, _) => {
+                self.state_id(block.name(), &[("level", &fluid_level.to_string())])
+            }
+            _ => self.default_state(block.name())
+        }
+    }
+
+    /// The block, facing and fluid level the world stores for a state, the inverse of
+    /// `state_of`. None for blocks the world doesn't implement.
+    pub fn world_block(&self, state: StateId) -> Option<(Block, Option<Face>, u8)> {
+        let definition = self.block_of(state)?;
+        let block = Block::from_name(&definition.name)?;
+
+        let facing = match (block, definition.value(state, "axis"), definition.value(state, "facing")) {
+            (Block::OakLog, Some("x"), _) => Some(Face::East),
+            (Block::OakLog, Some("z"), _) => Some(Face::North),
+            (Block::Furnace, _, Some(facing)) => Face::ALL.iter().copied().find(|&face| face_name(face) == facing),
+            _ => None
+        };
+        let fluid_level = match definition.value(state, "level") {
+            Some(level) if block.is_fluid() => level.parse().ok()?,
+            _ => 0
+        };
+        Some((block, facing, fluid_level))
+    }
+}
+
+fn face_name(face: Face) -> &'static str {
+    match face {
+        Face::Up => "up",
+        Face::Down => "down",
+        Face::North => "north",
+        Face::South => "south",
+        Face::East => "east",
+        Face::West => "west"
+    }
+}
+
+/// Collects the property values a blockstate file mentions. Multipart files only name the
+/// values a part is drawn for, the values that draw nothing are filled in by `sort_values`.
+fn read_properties(content: &Value) -> BTreeMap<String, BTreeSet<String>> {
+    let mut properties: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+    if let Some(variants) = content.get("variants").and_then(Value::as_object) {
+        for key in variants.keys() {
+            for pair in key.split(',').filter(|pair| !pair.is_empty()) {
+                if let Some((property, value)) = pair.split_once('=') {
+                    properties.entry(property.to_string()).or_default().insert(value.to_string());
+                }
+            }
+        }
+    }
+
+    if let Some(parts) = content.get("multipart").and_then(Value::as_array) {
+        for part in parts {
+            if let Some(condition) = part.get("when") {
+                read_condition(condition, &mut properties);
+            }
+        }
+        for values in properties.values_mut() {
+            complete_values(values);
+        }
+    }
+
+    properties
+}
+
+fn read_condition(condition: &Value, properties: &mut BTreeMap<String, BTreeSet<String>>) {
+    let condition = match condition.as_object() {
+        Some(condition) => condition,
+        None => return
+    };
+
+    for (key, value) in condition.iter() {
+        match (key.as_str(), value) {
+            ("OR", Value::Array(terms)) | ("AND", Value::Array(terms)) => {
+                for term in terms {
+                    read_condition(term, properties);
+                }
+            }
+            (property, value) => {
+                let value = match value {
+                    Value::String(value) => value.clone(),
+                    other => other.to_string()
+                };
+                let values = properties.entry(property.to_string()).or_default();
+                values.extend(value.split('|').map(|value| value.to_string()));
+            }
+        }
+    }
+}
+
+/// Adds the values a multipart condition implies without naming them: `false` for flags,
+/// every level below the highest for numbers and `none` for connections
+fn complete_values(values: &mut BTreeSet<String>) {
+    if values.iter().all(|value| value == "true" || value == "false") {
+        values.insert("true".to_string());
+        values.insert("false".to_string());
+    } else if values.iter().all(|value| value.parse::<u32>().is_ok()) {
+        let highest = values.iter().filter_map(|value| value.parse::<u32>().ok()).max().unwrap_or(0);
+        values.extend((0..=highest).map(|value| value.to_string()));
+    } else {
+        values.insert("none".to_string());
+    }
+}
+
+/// Numbers count up, everything else is alphabetical, so `false` comes before `true`
+fn sort_values(values: BTreeSet<String>) -> Vec<String> {
+    let mut values: Vec<String> = values.into_iter().collect();
+    if values.iter().all(|value| value.parse::<u32>().is_ok()) {
+        values.sort_by_key(|value| value.parse::<u32>().unwrap());
+    }
+    values
+}
+
+const TRANSLUCENT_BLOCKS: [&str; 7] = ["water", "ice", "frosted_ice", "slime_block", "honey_block", "nether_portal", "bubble_column"];
+
+// blocks with holes in their textures or shapes smaller than a cube, matched against the end of the name
+const CUTOUT_SUFFIXES: [&str; 46] = [
+    "leaves", "redstone_wire", "tripwire", "glass", "glass_pane", "sapling", "torch", "rail", "door", "trapdoor", "ladder", "vine", "vines",
+    "vines_plant", "grass", "fern", "flower", "tulip", "orchid", "allium", "bluet", "daisy", "dandelion", "poppy",
+    "lily_of_the_valley", "wither_rose", "rose_bush", "lilac", "peony", "sunflower", "dead_bush", "cobweb", "fire",
+    "wheat", "carrots", "potatoes", "beetroots", "sugar_cane", "kelp", "kelp_plant", "seagrass", "lantern",
+    "bamboo", "coral", "coral_fan", "sweet_berry_bush"
+];
+
+// smaller than a full cube, matched against the end of the name
+const PARTIAL_SUFFIXES: [&str; 24] = [
+    "redstone_wire", "tripwire", "slab", "stairs", "wall", "fence", "fence_gate", "button", "pressure_plate", "sign", "banner", "bed",
+    "carpet", "head", "skull", "chest", "anvil", "cake", "lectern", "campfire", "bell", "composter",
+    "cauldron", "stonecutter"
+];
+
+const NOT_SOLID_BLOCKS: [&str; 9] = ["air", "cave_air", "void_air", "water", "lava", "snow", "farmland", "grass_path", "structure_void"];
+
+const LIGHT_EMISSIONS: [(&str, u8); 28] = [
+    ("beacon", 15), ("campfire", 15), ("conduit", 15), ("end_gateway", 15), ("end_portal", 15), ("fire", 15),
+    ("glowstone", 15), ("jack_o_lantern", 15), ("lantern", 15), ("lava", 15), ("sea_lantern", 15),
+    ("shroomlight", 15), ("end_rod", 14), ("torch", 14), ("wall_torch", 14), ("nether_portal", 11),
+    ("crying_obsidian", 10), ("soul_campfire", 10), ("soul_fire", 10), ("soul_lantern", 10), ("soul_torch", 10),
+    ("soul_wall_torch", 10), ("redstone_torch", 7), ("redstone_wall_torch", 7), ("magma_block", 3),
+    ("brewing_stand", 1), ("brown_mushroom", 1), ("dragon_egg", 1)
+];
+
+const UNBREAKABLE_BLOCKS: [&str; 15] = [
+    "air", "cave_air", "void_air", "water", "lava", "bubble_column", "bedrock", "barrier", "end_portal",
+    "end_portal_frame", "end_gateway", "nether_portal", "command_block", "chain_command_block",
+    "repeating_command_block"
+];
+
+// vanilla hardness, matched against the end of the name, the first match wins
+const HARDNESS_SUFFIXES: [(&str, f32); 30] = [
+    ("obsidian", 50.0), ("ancient_debris", 30.0), ("netherite_block", 50.0), ("ender_chest", 22.5),
+    ("anvil", 5.0), ("diamond_block", 5.0), ("emerald_block", 5.0), ("iron_block", 5.0), ("spawner", 5.0),
+    ("furnace", 3.5), ("ore", 3.0), ("door", 3.0), ("planks", 2.0), ("log", 2.0), ("wood", 2.0),
+    ("stem", 2.0), ("hyphae", 2.0), ("fence", 2.0), ("fence_gate", 2.0), ("cobblestone", 2.0),
+    ("stone", 1.5), ("bricks", 1.5), ("wool", 0.8), ("sandstone", 0.8), ("glass", 0.3), ("glass_pane", 0.3),
+    ("leaves", 0.2), ("snow", 0.1), ("sapling", 0.0), ("torch", 0.0)
+];
+
+fn has_suffix(name: &str, suffixes: &[&str]) -> bool {
+    suffixes.iter().any(|suffix| name == *suffix || name.ends_with(&format!("_{}", suffix)))
+}
+
+/// Metadata of blocks the world implements comes from `Block`, everything else is
+/// estimated from vanilla naming
+fn block_metadata(name: &str) -> BlockMetadata {
+    if let Some(block) = Block::from_name(name) {
+        return BlockMetadata {
+            light_opacity: block.light_opacity(),
+            is_solid: block.collision_height() == Some(1.0),
+            light_emission: block.light_emission(),
+            hardness: block.hardness(),
+            render_layer: block.render_layer()
+        };
+    }
+
+    let is_stained_glass = name.contains("stained_glass");
+    let render_layer = if TRANSLUCENT_BLOCKS.contains(&name) || is_stained_glass {
+        RenderLayer::Translucent
+    } else if has_suffix(name, &CUTOUT_SUFFIXES) {
+        RenderLayer::Cutout
+    } else {
+        RenderLayer::Opaque
+    };
+
+    let is_full_cube = !has_suffix(name, &PARTIAL_SUFFIXES) && !NOT_SOLID_BLOCKS.contains(&name);
+    let is_solid = is_full_cube && (render_layer == RenderLayer::Opaque || name.ends_with("glass") || name.ends_with("leaves"));
+
+    let light_opacity = match render_layer {
+        RenderLayer::Opaque if is_full_cube => 15,
+        _ if name.ends_with("leaves") || name == "cobweb" => 1,
+        _ if name == "water" || name.ends_with("ice") => 3,
+        _ => 0
+    };
+
+    let light_emission = LIGHT_EMISSIONS.iter()
+        .find(|(emitter, _)| *emitter == name)
+        .map_or(0, |&(_, emission)| emission);
+
+    let hardness = if UNBREAKABLE_BLOCKS.contains(&name) {
+        None
+    } else {
+        let hardness = HARDNESS_SUFFIXES.iter()
+            .find(|(suffix, _)| has_suffix(name, &[suffix]))
+            .map_or(1.0, |&(_, hardness)| hardness);
+        Some(hardness)
+    };
+
+    BlockMetadata {
+        light_opacity,
+        is_solid,
+        light_emission,
+        hardness,
+        render_layer
+    }
+}
+
+/// The blocks the world implements with the properties their blockstate files give them
+#[cfg(test)]
+pub fn world_block_definitions() -> Vec<(String, BTreeMap<String, BTreeSet<String>>)> {
+    let property = |name: &str, values: &[&str]| {
+        (name.to_string(), values.iter().map(|value| value.to_string()).collect::<BTreeSet<String>>())
+    };
+
+    Block::ALL.iter()
+        .map(|block| {
+            let properties = match block {
+                Block::OakLog => vec![property("axis", &["x", "y", "z"])],
+                Block::Furnace => vec![property("facing", &["east", "north", "south", "west"]), property("lit", &["false", "true"])],
+                _ => vec![]
+            };
+            (block.name().to_string(), properties.into_iter().collect())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub fn world_block_registry() -> BlockRegistry {
+    BlockRegistry::new(world_block_definitions()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions() -> Vec<(String, BTreeMap<String, BTreeSet<String>>)> {
+        let mut lever = BTreeMap::new();
+        lever.insert("powered".to_string(), ["false", "true"].iter().map(|value| value.to_string()).collect());
+        lever.insert("face".to_string(), ["floor", "wall", "ceiling"].iter().map(|value| value.to_string()).collect());
+
+        vec![
+            ("stone".to_string(), BTreeMap::new()),
+            ("lever".to_string(), lever),
+            ("water".to_string(), BTreeMap::new()),
+            ("air".to_string(), BTreeMap::new())
+        ]
+    }
+
+    #[test]
+    fn same_definitions_give_the_same_ids_in_any_order() {
+        let registry = BlockRegistry::new(definitions()).unwrap();
+        let mut reversed = definitions();
+        reversed.reverse();
+        let reversed = BlockRegistry::new(reversed).unwrap();
+
+        assert_eq!(registry.state_count(), 1 + 6 + 1 + 16);
+        assert_eq!(registry.default_state("air"), Some(0));
+        for state in 0..registry.state_count() as StateId {
+            assert_eq!(registry.state_name(state), reversed.state_name(state));
+        }
+    }
+
+    #[test]
+    fn state_names_round_trip() {
+        let registry = BlockRegistry::new(definitions()).unwrap();
+
+        for state in 0..registry.state_count() as StateId {
+            let name = registry.state_name(state).unwrap();
+            assert_eq!(registry.parse_state(&name), Some(state), "{}", name);
+        }
+
+        let state = registry.state_id("lever", &[("powered", "true"), ("face", "wall")]).unwrap();
+        assert_eq!(registry.state_name(state).unwrap(), "lever[face=wall,powered=true]");
+        assert_eq!(registry.parse_state("minecraft:lever[powered=true, face=wall]"), Some(state));
+        // left out properties keep their default
+        assert_eq!(registry.parse_state("lever"), registry.default_state("lever"));
+    }
+
+    #[test]
+    fn parse_state_rejects_unknown_blocks_properties_and_values() {
+        let registry = BlockRegistry::new(definitions()).unwrap();
+
+        assert_eq!(registry.parse_state("lever[color=red]"), None);
+        assert_eq!(registry.parse_state("lever[face=side]"), None);
+        assert_eq!(registry.parse_state("lever[face]"), None);
+        assert_eq!(registry.parse_state("lever[face=wall"), None);
+        assert_eq!(registry.parse_state("stone[face=wall]"), None);
+        assert_eq!(registry.parse_state("granite"), None);
+    }
+
+    #[test]
+    fn world_block_inverts_state_of() {
+        let registry = world_block_registry();
+
+        for &block in Block::ALL.iter() {
+            for facing in Face::ALL.iter().copied().map(Some).chain([None]) {
+                for fluid_level in 0..16 {
+                    let state = match registry.state_of(block, facing, fluid_level) {
+                        Some(state) => state,
+                        None => continue
+                    };
+                    let (read_block, read_facing, read_level) = registry.world_block(state).unwrap();
+                    assert_eq!(read_block, block);
+                    assert_eq!(registry.state_of(read_block, read_facing, read_level), Some(state));
+                }
+            }
+        }
+        assert_eq!(registry.state_of(Block::Furnace, Some(Face::Up), 0), None);
+    }
+}
@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use crate::world::biome::Biome;
+use crate::world::block::Block;
+use crate::world::chunk::{Chunk, ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::world::generator::TerrainGenerator;
+use crate::world::lighting::{LightChannel, MAX_LIGHT};
+
+/// Read access to blocks, light and biomes in world coordinates
+pub trait BlockView {
+    fn chunk(&self, position: ChunkPos) -> Option<&Chunk>;
+
+    /// Biome of a column whose chunk is not loaded
+    fn unloaded_biome(&self, x: i32, z: i32) -> Biome;
+
+    fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return Block::Air;
+        }
+        let (position, local_x, local_z) = local(x, z);
+        match self.chunk(position) {
+            Some(chunk) => chunk.get_block(local_x, y as usize, local_z),
+            None => Block::Air
+        }
+    }
+
+    /// Level of the fluid at a position, 0 for sources and anything that is no fluid
+    fn get_fluid_level(&self, x: i32, y: i32, z: i32) -> u8 {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return 0;
+        }
+        let (position, local_x, local_z) = local(x, z);
+        match self.chunk(position) {
+            Some(chunk) => chunk.get_fluid_level(local_x, y as usize, local_z),
+            None => 0
+        }
+    }
+
+    /// Above the world is open sky, unloaded chunks are dark
+    fn get_light(&self, channel: LightChannel, x: i32, y: i32, z: i32) -> u8 {
+        if y >= CHUNK_HEIGHT as i32 {
+            return if channel == LightChannel::Sky { MAX_LIGHT } else { 0 };
+        }
+        if y < 0 {
+            return 0;
+        }
+
+        let (position, local_x, local_z) = local(x, z);
+        match (self.chunk(position), channel) {
+            (Some(chunk), LightChannel::Sky) => chunk.get_sky_light(local_x, y as usize, local_z),
+            (Some(chunk), LightChannel::Block) => chunk.get_block_light(local_x, y as usize, local_z),
+            (None, _) => 0
+        }
+    }
+
+    fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let (position, local_x, local_z) = local(x, z);
+        match self.chunk(position) {
+            Some(chunk) => chunk.get_biome(local_x, local_z),
+            None => self.unloaded_biome(x, z)
+        }
+    }
+}
+
+/// The chunk of a block column and the column inside it
+pub fn local(x: i32, z: i32) -> (ChunkPos, usize, usize) {
+    (
+        ChunkPos::from_block(x, z),
+        x.rem_euclid(CHUNK_SIZE as i32) as usize,
+        z.rem_euclid(CHUNK_SIZE as i32) as usize
+    )
+}
+
+/// A chunk and the eight around it, shared with the world so worker threads can mesh
+/// while the world keeps changing. Changed chunks are copied on write by the world.
+pub struct ChunkNeighbourhood {
+    center: ChunkPos,
+    // 3x3, x major
+    chunks: Vec<Option<Arc<Chunk>>>,
+    generator: TerrainGenerator
+}
+
+impl ChunkNeighbourhood {
+    pub fn new<F>(center: ChunkPos, generator: TerrainGenerator, chunk_at: F) -> ChunkNeighbourhood
+    where F: Fn(ChunkPos) -> Option<Arc<Chunk>> {
+        let mut chunks = Vec::with_capacity(9);
+        for offset_x in -1..=1 {
+            for offset_z in -1..=1 {
+                chunks.push(chunk_at(ChunkPos::new(center.x + offset_x, center.z + offset_z)));
+            }
+        }
+
+        ChunkNeighbourhood { center, chunks, generator }
+    }
+
+    /// The chunk the neighbourhood was taken around, None when it was not loaded
+    pub fn center(&self) -> Option<&Chunk> {
+        self.chunk(self.center)
+    }
+}
+
+impl BlockView for ChunkNeighbourhood {
+    fn chunk(&self, position: ChunkPos) -> Option<&Chunk> {
+        let offset_x = position.x - self.center.x + 1;
+        let offset_z = position.z - self.center.z + 1;
+        if !(0..3).contains(&offset_x) || !(0..3).contains(&offset_z) {
+            return None;
+        }
+        self.chunks[(offset_x * 3 + offset_z) as usize].as_deref()
+    }
+
+    fn unloaded_biome(&self, x: i32, z: i32) -> Biome {
+        self.generator.biome_at(x, z)
+    }
+}
+
+/// Blocks listed by position for tests, everywhere else is air
+#[cfg(test)]
+pub struct BlockMap(pub std::collections::HashMap<(i32, i32, i32), Block>);
+
+#[cfg(test)]
+impl BlockMap {
+    /// `block` at every position
+    pub fn filled<I: IntoIterator<Item = (i32, i32, i32)>>(positions: I, block: Block) -> BlockMap {
+        BlockMap(positions.into_iter().map(|position| (position, block)).collect())
+    }
+}
+
+#[cfg(test)]
+impl BlockView for BlockMap {
+    fn chunk(&self, _position: ChunkPos) -> Option<&Chunk> {
+        None
+    }
+
+    fn unloaded_biome(&self, _x: i32, _z: i32) -> Biome {
+        Biome::Plains
+    }
+
+    fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+        self.0.get(&(x, y, z)).copied().unwrap_or(Block::Air)
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use crate::world::block::{Block, Face};
+use crate::world::block_palette::BlockPalette;
+use crate::world::biome::Biome;
+
+pub const CHUNK_SIZE: usize = 16;
+pub const CHUNK_HEIGHT: usize = 256;
+// chunks are meshed and culled in 16^3 sections
+pub const SECTION_HEIGHT: usize = 16;
+pub const SECTIONS_PER_CHUNK: usize = CHUNK_HEIGHT / SECTION_HEIGHT;
+
+// bumped whenever the layout written by `Chunk::to_bytes` changes
+const CHUNK_FORMAT_VERSION: u8 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPos {
+    pub x: i32,
+    pub z: i32
+}
+
+impl ChunkPos {
+    pub fn new(x: i32, z: i32) -> ChunkPos {
+        ChunkPos { x, z }
+    }
+
+    pub fn from_block(x: i32, z: i32) -> ChunkPos {
+        ChunkPos {
+            x: x.div_euclid(CHUNK_SIZE as i32),
+            z: z.div_euclid(CHUNK_SIZE as i32)
+        }
+    }
+}
+
+/// A full height column of blocks. Blocks are stored y-major so a vertical scan stays
+/// inside one contiguous slice.
+#[derive(Clone)]
+pub struct Chunk {
+    pub position: ChunkPos,
+    blocks: Vec<Block>,
+    biomes: Vec<Biome>,
+    // sky light in the high nibble, block light in the low nibble
+    light: Vec<u8>,
+    // y of the highest opaque block per column, -1 when the column is empty
+    heightmap: Vec<i32>,
+    // direction of the few blocks that face one, by block index
+    facings: HashMap<u32, Face>,
+    // level of the fluids that are not sources, by block index
+    fluid_levels: HashMap<u32, u8>
+}
+
+impl Chunk {
+    pub fn new(position: ChunkPos) -> Chunk {
+        Chunk {
+            position,
+            blocks: vec![Block::Air; CHUNK_SIZE * CHUNK_HEIGHT * CHUNK_SIZE],
+            biomes: vec![Biome::Plains; CHUNK_SIZE * CHUNK_SIZE],
+            light: vec![0; CHUNK_SIZE * CHUNK_HEIGHT * CHUNK_SIZE],
+            heightmap: vec![-1; CHUNK_SIZE * CHUNK_SIZE],
+            facings: HashMap::new(),
+            fluid_levels: HashMap::new()
+        }
+    }
+
+    fn index(x: usize, y: usize, z: usize) -> usize {
+        (y * CHUNK_SIZE + z) * CHUNK_SIZE + x
+    }
+
+    fn column_index(x: usize, z: usize) -> usize {
+        z * CHUNK_SIZE + x
+    }
+
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> Block {
+        self.blocks[Chunk::index(x, y, z)]
+    }
+
+    /// Replacing a block drops its facing and fluid level
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: Block) {
+        self.blocks[Chunk::index(x, y, z)] = block;
+        self.facings.remove(&(Chunk::index(x, y, z) as u32));
+        self.fluid_levels.remove(&(Chunk::index(x, y, z) as u32));
+
+        let column = Chunk::column_index(x, z);
+        let height = self.heightmap[column];
+        if block.is_opaque() {
+            if y as i32 > height {
+                self.heightmap[column] = y as i32;
+            }
+        } else if y as i32 == height {
+            self.heightmap[column] = self.scan_height(x, z, y);
+        }
+    }
+
+    pub fn get_facing(&self, x: usize, y: usize, z: usize) -> Option<Face> {
+        self.facings.get(&(Chunk::index(x, y, z) as u32)).copied()
+    }
+
+    pub fn set_facing(&mut self, x: usize, y: usize, z: usize, facing: Option<Face>) {
+        let index = Chunk::index(x, y, z) as u32;
+        match facing {
+            Some(facing) => self.facings.insert(index, facing),
+            None => self.facings.remove(&index)
+        };
+    }
+
+    /// See `fluid::FALLING` for what the levels mean, 0 is a source and any other block
+    pub fn get_fluid_level(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.fluid_levels.get(&(Chunk::index(x, y, z) as u32)).copied().unwrap_or(0)
+    }
+
+    pub fn set_fluid_level(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = Chunk::index(x, y, z) as u32;
+        if level == 0 {
+            self.fluid_levels.remove(&index);
+        } else {
+            self.fluid_levels.insert(index, level);
+        }
+    }
+
+    pub fn get_sky_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.light[Chunk::index(x, y, z)] >> 4
+    }
+
+    pub fn set_sky_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = Chunk::index(x, y, z);
+        self.light[index] = (self.light[index] & 0x0F) | (level << 4);
+    }
+
+    pub fn get_block_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.light[Chunk::index(x, y, z)] & 0x0F
+    }
+
+    pub fn set_block_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        let index = Chunk::index(x, y, z);
+        self.light[index] = (self.light[index] & 0xF0) | (level & 0x0F);
+    }
+
+    pub fn get_biome(&self, x: usize, z: usize) -> Biome {
+        self.biomes[Chunk::column_index(x, z)]
+    }
+
+    pub fn set_biome(&mut self, x: usize, z: usize, biome: Biome) {
+        self.biomes[Chunk::column_index(x, z)] = biome;
+    }
+
+    /// Highest opaque block in the column, or None when the column is open to the void
+    pub fn highest_opaque_block(&self, x: usize, z: usize) -> Option<i32> {
+        let height = self.heightmap[Chunk::column_index(x, z)];
+        if height < 0 { None } else { Some(height) }
+    }
+
+    /// Uncompressed save format: version, then per block its id in `palette` (u16 little
+    /// endian), which includes its facing and fluid level, then biome ids and light
+    pub fn to_bytes(&self, palette: &BlockPalette) -> Vec<u8> {
+        let mut ids: Vec<u16> = self.blocks.iter().map(|&block| palette.id(block, None, 0)).collect();
+        for &index in self.facings.keys().chain(self.fluid_levels.keys()) {
+            let facing = self.facings.get(&index).copied();
+            let fluid_level = self.fluid_levels.get(&index).copied().unwrap_or(0);
+            ids[index as usize] = palette.id(self.blocks[index as usize], facing, fluid_level);
+        }
+
+        let mut bytes = Vec::with_capacity(1 + self.blocks.len() * 3 + self.biomes.len());
+        bytes.push(CHUNK_FORMAT_VERSION);
+        for id in ids {
+            bytes.extend_from_slice(&id.to_le_bytes());
+        }
+        bytes.extend(self.biomes.iter().map(|biome| biome.id()));
+        bytes.extend_from_slice(&self.light);
+        bytes
+    }
+
+    /// None when the data has the wrong length, is from another version or holds ids the
+    /// palette doesn't have
+    pub fn from_bytes(position: ChunkPos, bytes: &[u8], palette: &BlockPalette) -> Option<Chunk> {
+        let block_count = CHUNK_SIZE * CHUNK_HEIGHT * CHUNK_SIZE;
+        let column_count = CHUNK_SIZE * CHUNK_SIZE;
+        if bytes.len() != 1 + block_count * 2 + column_count + block_count || bytes[0] != CHUNK_FORMAT_VERSION {
+            return None;
+        }
+
+        let mut chunk = Chunk::new(position);
+        let (block_bytes, rest) = bytes[1..].split_at(block_count * 2);
+        let (biome_bytes, light_bytes) = rest.split_at(column_count);
+
+        for (i, id) in block_bytes.chunks_exact(2).enumerate() {
+            let id = u16::from_le_bytes([id[0], id[1]]);
+            if id as usize >= palette.state_count() {
+                return None;
+            }
+            // states this version can't place are dropped
+            let (block, facing, fluid_level) = palette.block(id).unwrap_or((Block::Air, None, 0));
+            chunk.blocks[i] = block;
+            if let Some(facing) = facing {
+                chunk.facings.insert(i as u32, facing);
+            }
+            if fluid_level != 0 {
+                chunk.fluid_levels.insert(i as u32, fluid_level);
+            }
+        }
+        for (i, &id) in biome_bytes.iter().enumerate() {
+            chunk.biomes[i] = Biome::from_id(id)?;
+        }
+        chunk.light.copy_from_slice(light_bytes);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                chunk.heightmap[Chunk::column_index(x, z)] = chunk.scan_height(x, z, CHUNK_HEIGHT);
+            }
+        }
+
+        Some(chunk)
+    }
+
+    fn scan_height(&self, x: usize, z: usize, below: usize) -> i32 {
+        for y in (0..below).rev() {
+            if self.get_block(x, y, z).is_opaque() {
+                return y as i32;
+            }
+        }
+        -1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_registry::world_block_registry;
+
+    fn palette() -> BlockPalette {
+        BlockPalette::new(vec![], &world_block_registry()).unwrap()
+    }
+
+    fn sample_chunk() -> Chunk {
+        let mut chunk = Chunk::new(ChunkPos::new(2, -3));
+        chunk.set_block(1, 64, 2, Block::Furnace);
+        chunk.set_facing(1, 64, 2, Some(Face::East));
+        chunk.set_block(6, 64, 2, Block::OakLog);
+        chunk.set_facing(6, 64, 2, Some(Face::North));
+        chunk.set_block(3, 70, 4, Block::Water);
+        chunk.set_fluid_level(3, 70, 4, 5);
+        chunk.set_block(0, 0, 0, Block::Bedrock);
+        chunk.set_biome(15, 15, Biome::Desert);
+        chunk.set_sky_light(5, 100, 5, 12);
+        chunk.set_block_light(5, 100, 5, 7);
+        chunk
+    }
+
+    #[test]
+    fn chunk_reads_back_what_it_wrote() {
+        let palette = palette();
+        let chunk = sample_chunk();
+        let read = Chunk::from_bytes(chunk.position, &chunk.to_bytes(&palette), &palette).unwrap();
+
+        assert_eq!(read.blocks, chunk.blocks);
+        assert_eq!(read.biomes, chunk.biomes);
+        assert_eq!(read.light, chunk.light);
+        assert_eq!(read.get_facing(1, 64, 2), Some(Face::East));
+        assert_eq!(read.get_facing(6, 64, 2), Some(Face::North));
+        assert_eq!(read.get_fluid_level(3, 70, 4), 5);
+        assert_eq!(read.highest_opaque_block(1, 2), Some(64));
+        assert_eq!(read.highest_opaque_block(0, 0), Some(0));
+        assert_eq!(read.highest_opaque_block(3, 4), None);
+    }
+
+    #[test]
+    fn cut_short_or_trailing_data_is_rejected() {
+        let palette = palette();
+        let bytes = sample_chunk().to_bytes(&palette);
+
+        assert!(Chunk::from_bytes(ChunkPos::new(0, 0), &bytes[..bytes.len() - 1], &palette).is_none());
+        assert!(Chunk::from_bytes(ChunkPos::new(0, 0), &bytes[..100], &palette).is_none());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert!(Chunk::from_bytes(ChunkPos::new(0, 0), &trailing, &palette).is_none());
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let palette = palette();
+        let mut bytes = sample_chunk().to_bytes(&palette);
+        bytes[0] = CHUNK_FORMAT_VERSION - 1;
+
+        assert!(Chunk::from_bytes(ChunkPos::new(0, 0), &bytes, &palette).is_none());
+    }
+
+    #[test]
+    fn id_outside_the_palette_is_rejected() {
+        let palette = palette();
+        let mut bytes = Chunk::new(ChunkPos::new(0, 0)).to_bytes(&palette);
+        bytes[1..3].copy_from_slice(&(palette.state_count() as u16).to_le_bytes());
+
+        assert!(Chunk::from_bytes(ChunkPos::new(0, 0), &bytes, &palette).is_none());
+    }
+
+    #[test]
+    fn states_this_version_can_not_place_read_as_air() {
+        let mut names = vec!["air".to_string(), "granite".to_string()];
+        names.extend(palette().names()[1..].iter().cloned());
+        let palette = BlockPalette::new(names, &world_block_registry()).unwrap();
+
+        let mut chunk = Chunk::new(ChunkPos::new(0, 0));
+        chunk.set_block(1, 0, 0, Block::Stone);
+        let mut bytes = chunk.to_bytes(&palette);
+        bytes[1..3].copy_from_slice(&1_u16.to_le_bytes());
+
+        let read = Chunk::from_bytes(chunk.position, &bytes, &palette).unwrap();
+        assert_eq!(read.get_block(0, 0, 0), Block::Air);
+        assert_eq!(read.get_block(1, 0, 0), Block::Stone);
+    }
+}
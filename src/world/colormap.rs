@@ -0,0 +1,109 @@
+use std::path::Path;
+
+use crate::world::biome::Biome;
+use crate::world::block_view::BlockView;
+
+/// A 256x256 lookup texture indexed by biome temperature and downfall
+pub struct ColorMap {
+    pixels: Vec<[f32; 3]>,
+    width: usize,
+    height: usize
+}
+
+// magenta, same fallback vanilla uses for lookups outside the map
+const FALLBACK_COLOR: [f32; 3] = [1.0, 0.0, 1.0];
+
+impl ColorMap {
+    pub fn load(path: &Path) -> image::ImageResult<ColorMap> {
+        let image = image::open(path)?.to_rgb();
+        let (width, height) = image.dimensions();
+
+        let pixels = image
+            .pixels()
+            .map(|pixel| [
+                pixel[0] as f32 / 255.0,
+                pixel[1] as f32 / 255.0,
+                pixel[2] as f32 / 255.0
+            ])
+            .collect();
+
+        Ok(ColorMap {
+            pixels,
+            width: width as usize,
+            height: height as usize
+        })
+    }
+
+    /// Downfall is scaled by temperature, so only the lower left triangle of the map is used
+    pub fn sample(&self, temperature: f32, downfall: f32) -> [f32; 3] {
+        let temperature = temperature.clamp(0.0, 1.0);
+        let downfall = downfall.clamp(0.0, 1.0) * temperature;
+
+        let x = ((1.0 - temperature) * (self.width - 1) as f32) as usize;
+        let y = ((1.0 - downfall) * (self.height - 1) as f32) as usize;
+
+        match self.pixels.get(y * self.width + x) {
+            Some(color) => *color,
+            None => FALLBACK_COLOR
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tint {
+    None,
+    Grass,
+    Foliage,
+    Water
+}
+
+const WATER_COLOR: [f32; 3] = [0.247, 0.463, 0.894];
+
+pub struct BiomeColors {
+    grass: ColorMap,
+    foliage: ColorMap,
+    pub blend_radius: u32
+}
+
+impl BiomeColors {
+    pub fn load(texture_directory: &Path, blend_radius: u32) -> image::ImageResult<BiomeColors> {
+        let colormap_directory = texture_directory.join("colormap");
+
+        Ok(BiomeColors {
+            grass: ColorMap::load(&colormap_directory.join("grass.png"))?,
+            foliage: ColorMap::load(&colormap_directory.join("foliage.png"))?,
+            blend_radius
+        })
+    }
+
+    pub fn biome_color(&self, biome: Biome, tint: Tint) -> [f32; 3] {
+        match tint {
+            Tint::None => [1.0, 1.0, 1.0],
+            Tint::Grass => self.grass.sample(biome.temperature(), biome.downfall()),
+            Tint::Foliage => self.foliage.sample(biome.temperature(), biome.downfall()),
+            Tint::Water => WATER_COLOR
+        }
+    }
+
+    /// Average colour of every column within the blend radius of the given column
+    pub fn blended_color<W: BlockView>(&self, world: &W, x: i32, z: i32, tint: Tint) -> [f32; 3] {
+        if tint == Tint::None || tint == Tint::Water {
+            return self.biome_color(world.biome_at(x, z), tint);
+        }
+
+        let radius = self.blend_radius as i32;
+        let mut total = [0.0; 3];
+        let mut count = 0.0;
+        for offset_x in -radius..=radius {
+            for offset_z in -radius..=radius {
+                let color = self.biome_color(world.biome_at(x + offset_x, z + offset_z), tint);
+                for i in 0..3 {
+                    total[i] += color[i];
+                }
+                count += 1.0;
+            }
+        }
+
+        [total[0] / count, total[1] / count, total[2] / count]
+    }
+}
@@ -0,0 +1,513 @@
+use std::collections::{BTreeMap, HashMap};
+
+use crate::world::block::{Block, Face};
+use crate::world::chunk::{ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::world::world::World;
+
+/// Level of a fluid fed from above, it reaches the full height of its block. Sources are
+/// level 0 and flowing fluids 1-7, thinner the further they are from what feeds them.
+pub const FALLING: u8 = 8;
+
+const HORIZONTAL_FACES: [Face; 4] = [Face::North, Face::South, Face::East, Face::West];
+// how far flowing fluids look for a way down before spreading evenly
+const WATER_SLOPE_DISTANCE: u32 = 4;
+const LAVA_SLOPE_DISTANCE: u32 = 2;
+// further than any slope search reaches
+const NO_SLOPE: u32 = 1000;
+
+type Position = (i32, i32, i32);
+
+/// Fluids waiting for their next flow step. A position is queued once, at the earliest
+/// tick it was asked for.
+pub struct FluidTicks {
+    due: BTreeMap<u64, Vec<Position>>,
+    scheduled: HashMap<Position, u64>
+}
+
+impl FluidTicks {
+    pub fn new() -> FluidTicks {
+        FluidTicks {
+            due: BTreeMap::new(),
+            scheduled: HashMap::new()
+        }
+    }
+
+    pub fn schedule(&mut self, position: Position, tick: u64) {
+        if self.scheduled.get(&position).is_some_and(|&scheduled| scheduled <= tick) {
+            return;
+        }
+        self.scheduled.insert(position, tick);
+        self.due.entry(tick).or_default().push(position);
+    }
+
+    /// Positions due at or before `tick`, in the order they were scheduled
+    pub fn take_due(&mut self, tick: u64) -> Vec<Position> {
+        let later = self.due.split_off(&(tick + 1));
+        let due = std::mem::replace(&mut self.due, later);
+
+        let mut positions = vec![];
+        for (due_tick, due_positions) in due {
+            for position in due_positions {
+                // moved to an earlier tick since
+                if self.scheduled.get(&position) == Some(&due_tick) {
+                    self.scheduled.remove(&position);
+                    positions.push(position);
+                }
+            }
+        }
+        positions
+    }
+}
+
+/// Height of the surface of a fluid inside its block, falling fluids are drawn full height
+/// by the mesher when the same fluid lies above
+pub fn surface_height(level: u8) -> f32 {
+    if level >= FALLING {
+        8.0 / 9.0
+    } else {
+        (8 - level) as f32 / 9.0
+    }
+}
+
+/// Ticks between two flow steps, lava next to water hardens on the next tick
+fn update_delay(world: &World, fluid: Block, x: i32, y: i32, z: i32) -> u64 {
+    match fluid {
+        Block::Lava if touches_water(world, x, y, z) => 1,
+        Block::Lava => 30,
+        _ => 5
+    }
+}
+
+// how much a fluid thins out per block it flows sideways
+fn level_drop(fluid: Block) -> u8 {
+    if fluid == Block::Lava { 2 } else { 1 }
+}
+
+fn slope_distance_limit(fluid: Block) -> u32 {
+    if fluid == Block::Lava { LAVA_SLOPE_DISTANCE } else { WATER_SLOPE_DISTANCE }
+}
+
+/// Blocks fluids flow into, washing away what is there
+fn is_open(block: Block) -> bool {
+    !block.is_fluid() && block.collision_height().is_none()
+}
+
+fn is_solid(block: Block) -> bool {
+    block.collision_height().is_some()
+}
+
+fn neighbour(x: i32, y: i32, z: i32, face: Face) -> Position {
+    let (offset_x, offset_y, offset_z) = face.offset();
+    (x + offset_x, y + offset_y, z + offset_z)
+}
+
+/// The bottom of the world holds fluids like a floor
+fn block_below(world: &World, x: i32, y: i32, z: i32) -> Block {
+    if y > 0 { world.get_block(x, y - 1, z) } else { Block::Bedrock }
+}
+
+/// Wakes the fluids at and next to a block that changed
+pub fn wake_around(world: &mut World, x: i32, y: i32, z: i32) {
+    let mut positions = vec![(x, y, z)];
+    positions.extend(Face::ALL.iter().map(|&face| neighbour(x, y, z, face)));
+
+    for (x, y, z) in positions {
+        let block = world.get_block(x, y, z);
+        if block.is_fluid() {
+            let delay = update_delay(world, block, x, y, z);
+            world.schedule_fluid_update(x, y, z, delay);
+        }
+    }
+}
+
+/// Wakes the fluids of a chunk read from disk that may still flow, flow steps that were
+/// pending when it was saved are not kept
+pub fn wake_chunk(world: &mut World, position: ChunkPos) {
+    let chunk = match world.chunks.get(&position) {
+        Some(chunk) => chunk.clone(),
+        None => return
+    };
+    let origin_x = position.x * CHUNK_SIZE as i32;
+    let origin_z = position.z * CHUNK_SIZE as i32;
+
+    for y in 0..CHUNK_HEIGHT {
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let fluid = chunk.get_block(x, y, z);
+                if !fluid.is_fluid() {
+                    continue;
+                }
+                let (x, y, z) = (origin_x + x as i32, y as i32, origin_z + z as i32);
+                if world.get_fluid_level(x, y, z) != 0 || can_flow(world, fluid, x, y, z) {
+                    let delay = update_delay(world, fluid, x, y, z);
+                    world.schedule_fluid_update(x, y, z, delay);
+                }
+            }
+        }
+    }
+}
+
+/// Whether a source has somewhere to flow or something to harden against
+fn can_flow(world: &World, fluid: Block, x: i32, y: i32, z: i32) -> bool {
+    Face::ALL.iter()
+        .filter(|&&face| face != Face::Up)
+        .map(|&face| neighbour(x, y, z, face))
+        .any(|(x, y, z)| {
+            let block = world.get_block(x, y, z);
+            is_open(block) || (block.is_fluid() && block != fluid)
+        })
+        || (fluid == Block::Lava && touches_water(world, x, y, z))
+}
+
+/// One flow step of the fluid at a position: lava touching water hardens, flowing fluids
+/// take the level of what feeds them or dry up, then the fluid spreads
+pub fn update(world: &mut World, x: i32, y: i32, z: i32) {
+    let fluid = world.get_block(x, y, z);
+    if !fluid.is_fluid() {
+        return;
+    }
+
+    // what feeds it may not be loaded yet, it would dry up
+    let next_to_unloaded = HORIZONTAL_FACES.iter()
+        .map(|&face| neighbour(x, y, z, face))
+        .any(|(x, _, z)| !world.is_loaded(x, z));
+    if next_to_unloaded {
+        let delay = update_delay(world, fluid, x, y, z);
+        world.schedule_fluid_update(x, y, z, delay);
+        return;
+    }
+
+    let mut level = world.get_fluid_level(x, y, z);
+    if fluid == Block::Lava && touches_water(world, x, y, z) {
+        world.set_block(x, y, z, if level == 0 { Block::Obsidian } else { Block::Cobblestone });
+        return;
+    }
+
+    if level != 0 {
+        match supported_level(world, fluid, x, y, z) {
+            None => {
+                world.set_block(x, y, z, Block::Air);
+                return;
+            }
+            Some(supported) if supported != level => {
+                world.place_fluid(x, y, z, fluid, supported);
+                level = supported;
+            }
+            Some(_) => {}
+        }
+    }
+
+    spread(world, fluid, level, x, y, z);
+}
+
+/// Water on any side but below
+fn touches_water(world: &World, x: i32, y: i32, z: i32) -> bool {
+    Face::ALL.iter()
+        .filter(|&&face| face != Face::Down)
+        .map(|&face| neighbour(x, y, z, face))
+        .any(|(x, y, z)| world.get_block(x, y, z) == Block::Water)
+}
+
+/// The level a flowing fluid gets from the fluid around it, None when nothing feeds it.
+/// Water between two sources on top of something that holds it becomes a source itself.
+fn supported_level(world: &World, fluid: Block, x: i32, y: i32, z: i32) -> Option<u8> {
+    if world.get_block(x, y + 1, z) == fluid {
+        return Some(FALLING);
+    }
+
+    let mut sources = 0;
+    let mut nearest: Option<u8> = None;
+    for &face in HORIZONTAL_FACES.iter() {
+        let (x, y, z) = neighbour(x, y, z, face);
+        if world.get_block(x, y, z) != fluid {
+            continue;
+        }
+
+        let level = world.get_fluid_level(x, y, z);
+        if level == 0 {
+            sources += 1;
+        }
+        // falling fluid spreads like a source
+        let distance = if level >= FALLING { 0 } else { level };
+        nearest = Some(nearest.map_or(distance, |nearest| nearest.min(distance)));
+    }
+
+    if fluid == Block::Water && sources >= 2 {
+        let below = block_below(world, x, y, z);
+        if is_solid(below) || (below == Block::Water && world.get_fluid_level(x, y - 1, z) == 0) {
+            return Some(0);
+        }
+    }
+
+    nearest
+        .map(|distance| distance + level_drop(fluid))
+        .filter(|&level| level < FALLING)
+}
+
+/// Falls into the block below when it can, otherwise flows sideways towards the nearest
+/// way down. Flowing fluid on top of other fluid only falls.
+fn spread(world: &mut World, fluid: Block, level: u8, x: i32, y: i32, z: i32) {
+    let below = block_below(world, x, y, z);
+    if is_open(below) {
+        world.place_fluid(x, y - 1, z, fluid, FALLING);
+        return;
+    }
+    if fluid == Block::Lava && below == Block::Water {
+        world.set_block(x, y - 1, z, Block::Stone);
+        return;
+    }
+    if level != 0 && !is_solid(below) {
+        return;
+    }
+
+    let spread_level = if level >= FALLING { 1 } else { level + level_drop(fluid) };
+    if spread_level >= FALLING {
+        return;
+    }
+
+    for face in flow_faces(world, fluid, x, y, z) {
+        let (x, y, z) = neighbour(x, y, z, face);
+        if is_open(world.get_block(x, y, z)) {
+            world.place_fluid(x, y, z, fluid, spread_level);
+        }
+    }
+}
+
+/// The sides a fluid flows out of: those with the shortest way to somewhere it can fall,
+/// or every side when there is none within reach
+fn flow_faces(world: &World, fluid: Block, x: i32, y: i32, z: i32) -> Vec<Face> {
+    let mut distances = vec![];
+    for &face in HORIZONTAL_FACES.iter() {
+        let (next_x, next_y, next_z) = neighbour(x, y, z, face);
+        if !can_pass(world, fluid, next_x, next_y, next_z) {
+            continue;
+        }
+
+        let distance = if can_fall(world, next_x, next_y, next_z) {
+            0
+        } else {
+            slope_distance(world, fluid, (next_x, next_y, next_z), 1, face.opposite())
+        };
+        distances.push((face, distance));
+    }
+
+    let shortest = distances.iter().map(|&(_, distance)| distance).min().unwrap_or(NO_SLOPE);
+    distances
+        .into_iter()
+        .filter(|&(_, distance)| distance == shortest)
+        .map(|(face, _)| face)
+        .collect()
+}
+
+/// Open blocks and flowing fluid of the same kind
+fn can_pass(world: &World, fluid: Block, x: i32, y: i32, z: i32) -> bool {
+    let block = world.get_block(x, y, z);
+    is_open(block) || (block == fluid && world.get_fluid_level(x, y, z) != 0)
+}
+
+fn can_fall(world: &World, x: i32, y: i32, z: i32) -> bool {
+    !is_solid(block_below(world, x, y, z))
+}
+
+/// Blocks to walk from `position` to somewhere the fluid can fall, never turning back
+/// the way it came
+fn slope_distance(world: &World, fluid: Block, position: Position, distance: u32, came_from: Face) -> u32 {
+    let mut shortest = NO_SLOPE;
+    for &face in HORIZONTAL_FACES.iter() {
+        if face == came_from {
+            continue;
+        }
+
+        let (x, y, z) = neighbour(position.0, position.1, position.2, face);
+        if !can_pass(world, fluid, x, y, z) {
+            continue;
+        }
+        if can_fall(world, x, y, z) {
+            return distance;
+        }
+        if distance < slope_distance_limit(fluid) {
+            shortest = shortest.min(slope_distance(world, fluid, (x, y, z), distance + 1, face.opposite()));
+        }
+    }
+    shortest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_registry::world_block_registry;
+    use crate::world::world::test_directory;
+    use std::sync::Arc;
+
+    fn run(world: &mut World, ticks: u32) {
+        for _ in 0..ticks {
+            world.tick();
+        }
+    }
+
+    fn fluid_at(world: &World, x: i32, y: i32, z: i32) -> (Block, u8) {
+        (world.get_block(x, y, z), world.get_fluid_level(x, y, z))
+    }
+
+    #[test]
+    fn source_spreads_thinner_over_a_flat_floor() {
+        let mut world = World::flat("fluid_spread", 1);
+        world.set_block(0, 64, 0, Block::Water);
+        run(&mut world, 200);
+
+        assert_eq!(fluid_at(&world, 0, 64, 0), (Block::Water, 0));
+        assert_eq!(fluid_at(&world, 3, 64, 0), (Block::Water, 3));
+        assert_eq!(fluid_at(&world, -2, 64, 2), (Block::Water, 4));
+        assert_eq!(fluid_at(&world, 7, 64, 0), (Block::Water, 7));
+        assert_eq!(world.get_block(8, 64, 0), Block::Air);
+        assert_eq!(world.get_block(0, 65, 0), Block::Air);
+    }
+
+    #[test]
+    fn water_dries_up_once_its_source_is_gone() {
+        let mut world = World::flat("fluid_drying", 1);
+        world.set_block(0, 64, 0, Block::Water);
+        run(&mut world, 200);
+        world.set_block(0, 64, 0, Block::Air);
+        run(&mut world, 400);
+
+        for x in -8..=8 {
+            for z in -8..=8 {
+                assert_eq!(world.get_block(x, 64, z), Block::Air, "{} {}", x, z);
+            }
+        }
+    }
+
+    #[test]
+    fn water_between_two_sources_becomes_a_source() {
+        let mut world = World::flat("fluid_infinite", 1);
+        world.set_block(0, 64, 0, Block::Water);
+        world.set_block(2, 64, 0, Block::Water);
+        run(&mut world, 100);
+        assert_eq!(fluid_at(&world, 1, 64, 0), (Block::Water, 0));
+
+        world.set_block(0, 64, 0, Block::Air);
+        world.set_block(2, 64, 0, Block::Air);
+        run(&mut world, 100);
+        assert_eq!(fluid_at(&world, 1, 64, 0), (Block::Water, 0));
+    }
+
+    #[test]
+    fn lava_does_not_become_a_source_between_two_sources() {
+        let mut world = World::flat("fluid_lava_sources", 1);
+        world.set_block(0, 64, 0, Block::Lava);
+        world.set_block(2, 64, 0, Block::Lava);
+        run(&mut world, 200);
+
+        assert_eq!(fluid_at(&world, 1, 64, 0), (Block::Lava, 2));
+        assert_eq!(fluid_at(&world, 4, 64, 0), (Block::Lava, 4));
+    }
+
+    #[test]
+    fn falling_fluid_fills_its_column_and_spreads_at_the_bottom() {
+        let mut world = World::flat("fluid_falling", 1);
+        world.set_block(0, 70, 0, Block::Water);
+        run(&mut world, 200);
+
+        for y in 64..70 {
+            assert_eq!(fluid_at(&world, 0, y, 0), (Block::Water, FALLING), "{}", y);
+        }
+        // the source spreads around itself too, that water falls beside the column
+        assert_eq!(fluid_at(&world, 1, 70, 0), (Block::Water, 1));
+        assert_eq!(fluid_at(&world, 1, 64, 0), (Block::Water, FALLING));
+        assert_eq!(fluid_at(&world, 2, 64, 0), (Block::Water, 1));
+        assert_eq!(fluid_at(&world, 0, 64, -3), (Block::Water, 2));
+        assert_eq!(world.get_block(2, 70, 0), Block::Air);
+    }
+
+    #[test]
+    fn flowing_fluid_heads_for_the_nearest_way_down() {
+        let mut world = World::flat("fluid_slope", 1);
+        world.set_block(3, 63, 0, Block::Air);
+        world.set_block(0, 64, 0, Block::Water);
+        run(&mut world, 200);
+
+        assert_eq!(fluid_at(&world, 2, 64, 0), (Block::Water, 2));
+        assert_eq!(fluid_at(&world, 3, 63, 0), (Block::Water, FALLING));
+        assert_eq!(world.get_block(-1, 64, 0), Block::Air);
+        assert_eq!(world.get_block(0, 64, 1), Block::Air);
+        assert_eq!(world.get_block(4, 64, 0), Block::Air);
+    }
+
+    #[test]
+    fn lava_touching_water_hardens() {
+        let mut world = World::flat("fluid_hardening", 1);
+        // a source turns to obsidian
+        world.set_block(0, 64, 0, Block::Lava);
+        world.set_block(0, 65, 0, Block::Water);
+        run(&mut world, 10);
+        assert_eq!(world.get_block(0, 64, 0), Block::Obsidian);
+
+        // flowing lava to cobblestone
+        world.set_block(10, 64, 0, Block::Lava);
+        run(&mut world, 100);
+        assert_eq!(fluid_at(&world, 11, 64, 0), (Block::Lava, 2));
+        world.set_block(11, 65, 0, Block::Water);
+        run(&mut world, 10);
+        assert_eq!(world.get_block(11, 64, 0), Block::Cobblestone);
+
+        // lava falling onto water turns the water to stone
+        world.set_block(-10, 63, 0, Block::Water);
+        world.set_block(-10, 66, 0, Block::Lava);
+        run(&mut world, 100);
+        assert_eq!(world.get_block(-10, 63, 0), Block::Stone);
+    }
+
+    #[test]
+    fn fluid_flowing_when_saved_flows_on_after_loading() {
+        let mut world = World::flat("fluid_reload", 1);
+        world.set_block(0, 64, 0, Block::Water);
+        run(&mut world, 6);
+        assert_eq!(fluid_at(&world, 1, 64, 0), (Block::Water, 1));
+        assert_eq!(world.get_block(2, 64, 0), Block::Air);
+        world.save_and_flush();
+        drop(world);
+
+        let mut world = World::load(&test_directory("fluid_reload"), 0, Arc::new(world_block_registry())).unwrap();
+        world.generate_around(0, 0, 1);
+        run(&mut world, 200);
+        assert_eq!(fluid_at(&world, 3, 64, 0), (Block::Water, 3));
+    }
+
+    #[test]
+    fn position_scheduled_twice_fires_once_at_the_earlier_tick() {
+        let mut ticks = FluidTicks::new();
+        ticks.schedule((0, 64, 0), 10);
+        ticks.schedule((0, 64, 0), 15);
+
+        assert_eq!(ticks.take_due(9), vec![]);
+        assert_eq!(ticks.take_due(10), vec![(0, 64, 0)]);
+        assert_eq!(ticks.take_due(20), vec![]);
+    }
+
+    #[test]
+    fn due_positions_come_in_scheduled_order() {
+        let mut ticks = FluidTicks::new();
+        ticks.schedule((3, 64, 0), 7);
+        ticks.schedule((1, 64, 0), 5);
+        ticks.schedule((2, 64, 0), 5);
+        ticks.schedule((4, 64, 0), 12);
+
+        assert_eq!(ticks.take_due(8), vec![(1, 64, 0), (2, 64, 0), (3, 64, 0)]);
+        assert_eq!(ticks.take_due(12), vec![(4, 64, 0)]);
+    }
+
+    #[test]
+    fn position_moved_earlier_does_not_fire_again_later() {
+        let mut ticks = FluidTicks::new();
+        ticks.schedule((0, 64, 0), 20);
+        ticks.schedule((0, 64, 0), 5);
+
+        assert_eq!(ticks.take_due(5), vec![(0, 64, 0)]);
+        assert_eq!(ticks.take_due(20), vec![]);
+
+        // scheduling it again afterwards still works, once
+        ticks.schedule((0, 64, 0), 20);
+        assert_eq!(ticks.take_due(30), vec![(0, 64, 0)]);
+    }
+}
@@ -0,0 +1,198 @@
+use cgmath::Point3;
+
+use crate::world::biome::{Biome, Precipitation};
+use crate::world::block::Block;
+use crate::world::chunk::{Chunk, ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::world::random::Random;
+
+pub const SEA_LEVEL: i32 = 62;
+// furthest a canopy reaches from its trunk
+const TREE_RADIUS: i32 = 2;
+
+#[derive(Clone)]
+pub struct TerrainGenerator {
+    seed: u64
+}
+
+impl TerrainGenerator {
+    pub fn new(seed: u64) -> TerrainGenerator {
+        TerrainGenerator { seed }
+    }
+
+    pub fn generate_chunk(&self, position: ChunkPos) -> Chunk {
+        let mut chunk = Chunk::new(position);
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let world_x = position.x * CHUNK_SIZE as i32 + x as i32;
+                let world_z = position.z * CHUNK_SIZE as i32 + z as i32;
+
+                let biome = self.biome_at(world_x, world_z);
+                let height = self.height_at(world_x, world_z, biome);
+                chunk.set_biome(x, z, biome);
+
+                for y in 0..=height.max(SEA_LEVEL) {
+                    let block = if y == 0 {
+                        Block::Bedrock
+                    } else if y > height {
+                        Block::Water
+                    } else if y < height - 3 {
+                        Block::Stone
+                    } else {
+                        TerrainGenerator::surface_block(biome, y, height)
+                    };
+                    chunk.set_block(x, y as usize, z, block);
+                }
+
+                if biome.precipitation_at(height + 1) == Precipitation::Snow
+                    && height >= SEA_LEVEL
+                {
+                    chunk.set_block(x, height as usize + 1, z, Block::Snow);
+                }
+            }
+        }
+
+        self.place_trees(&mut chunk);
+
+        chunk
+    }
+
+    /// Trees are rolled per column, including columns of neighbouring chunks whose
+    /// canopy reaches into this one, so they line up across chunk borders
+    fn place_trees(&self, chunk: &mut Chunk) {
+        let min_x = chunk.position.x * CHUNK_SIZE as i32;
+        let min_z = chunk.position.z * CHUNK_SIZE as i32;
+
+        for x in min_x - TREE_RADIUS..min_x + CHUNK_SIZE as i32 + TREE_RADIUS {
+            for z in min_z - TREE_RADIUS..min_z + CHUNK_SIZE as i32 + TREE_RADIUS {
+                let biome = self.biome_at(x, z);
+                let chance = match biome {
+                    Biome::Forest | Biome::Jungle => 0.03,
+                    Biome::Swamp | Biome::Taiga => 0.015,
+                    Biome::Plains | Biome::Savanna => 0.002,
+                    _ => 0.0
+                };
+                let roll = Random::hash_2d(self.seed ^ 0x7472_6565, x, z);
+                if (roll & 0xFFFF) as f32 / 65536.0 >= chance {
+                    continue;
+                }
+
+                let ground = self.height_at(x, z, biome);
+                if ground <= SEA_LEVEL {
+                    continue;
+                }
+                let trunk_height = 4 + (roll >> 16) as i32 % 3;
+                let has_vines = biome == Biome::Swamp || biome == Biome::Jungle;
+
+                self.place_tree(chunk, Point3::new(x, ground + 1, z), trunk_height, has_vines, roll >> 24);
+            }
+        }
+    }
+
+    /// A tree whose trunk starts at `base`
+    fn place_tree(&self, chunk: &mut Chunk, base: Point3<i32>, trunk_height: i32, has_vines: bool, roll: u64) {
+        let Point3 { x, y, z } = base;
+        let top = y + trunk_height;
+
+        for leaf_y in top - 3..=top {
+            let radius: i32 = if leaf_y >= top - 1 { 1 } else { 2 };
+            for offset_x in -radius..=radius {
+                for offset_z in -radius..=radius {
+                    // trim the corners so the canopy is rounder
+                    if offset_x.abs() == radius && offset_z.abs() == radius && (leaf_y == top || radius == 2) {
+                        continue;
+                    }
+                    TerrainGenerator::set_if_inside(chunk, x + offset_x, leaf_y, z + offset_z, Block::OakLeaves);
+
+                    let is_edge = offset_x.abs() == radius || offset_z.abs() == radius;
+                    if has_vines && is_edge && leaf_y == top - 3 {
+                        let vine_roll = Random::hash_2d(roll, x + offset_x, z + offset_z);
+                        let length = (vine_roll % 4) as i32;
+                        for vine_y in leaf_y - length..leaf_y {
+                            TerrainGenerator::set_if_inside(chunk, x + offset_x, vine_y, z + offset_z, Block::Vine);
+                        }
+                    }
+                }
+            }
+        }
+
+        for trunk_y in y..top {
+            TerrainGenerator::set_if_inside(chunk, x, trunk_y, z, Block::OakLog);
+        }
+    }
+
+    fn set_if_inside(chunk: &mut Chunk, x: i32, y: i32, z: i32, block: Block) {
+        let local_x = x - chunk.position.x * CHUNK_SIZE as i32;
+        let local_z = z - chunk.position.z * CHUNK_SIZE as i32;
+        let inside = local_x >= 0 && local_x < CHUNK_SIZE as i32
+            && local_z >= 0 && local_z < CHUNK_SIZE as i32
+            && y >= 0 && y < CHUNK_HEIGHT as i32;
+
+        if inside && !chunk.get_block(local_x as usize, y as usize, local_z as usize).is_opaque() {
+            chunk.set_block(local_x as usize, y as usize, local_z as usize, block);
+        }
+    }
+
+    fn surface_block(biome: Biome, y: i32, height: i32) -> Block {
+        if height < SEA_LEVEL + 2 && biome != Biome::SnowyTundra {
+            return if height < SEA_LEVEL - 4 { Block::Gravel } else { Block::Sand };
+        }
+        match biome {
+            Biome::Desert => Block::Sand,
+            _ if y == height => Block::Grass,
+            _ => Block::Dirt
+        }
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        let temperature = self.noise(x, z, 256.0, 1);
+        let humidity = self.noise(x, z, 256.0, 2);
+        let continent = self.noise(x, z, 512.0, 3);
+
+        if continent < 0.3 {
+            return Biome::Ocean;
+        }
+
+        match (temperature, humidity) {
+            (t, _) if t < 0.25 => Biome::SnowyTundra,
+            (t, h) if t < 0.4 => if h > 0.5 { Biome::Taiga } else { Biome::Plains },
+            (t, h) if t < 0.65 => if h > 0.6 { Biome::Swamp } else if h > 0.4 { Biome::Forest } else { Biome::Plains },
+            (_, h) if h > 0.6 => Biome::Jungle,
+            (_, h) if h > 0.35 => Biome::Savanna,
+            _ => Biome::Desert
+        }
+    }
+
+    fn height_at(&self, x: i32, z: i32, biome: Biome) -> i32 {
+        let base = self.noise(x, z, 128.0, 4) * 0.7 + self.noise(x, z, 32.0, 5) * 0.3;
+        let amplitude = match biome {
+            Biome::Ocean => 12.0,
+            Biome::Plains | Biome::Desert | Biome::Swamp => 8.0,
+            Biome::SnowyTundra | Biome::Taiga => 28.0,
+            _ => 16.0
+        };
+        let offset = if biome == Biome::Ocean { -14 } else { 2 };
+
+        SEA_LEVEL + offset + (base * amplitude) as i32
+    }
+
+    /// Bilinear value noise in [0, 1) with smoothstep interpolation
+    fn noise(&self, x: i32, z: i32, scale: f32, octave: u64) -> f32 {
+        let fx = x as f32 / scale;
+        let fz = z as f32 / scale;
+        let x0 = fx.floor() as i32;
+        let z0 = fz.floor() as i32;
+        let tx = fx - x0 as f32;
+        let tz = fz - z0 as f32;
+
+        let seed = self.seed.wrapping_add(octave.wrapping_mul(0x1000_0001));
+        let corner = |cx: i32, cz: i32| (Random::hash_2d(seed, cx, cz) >> 40) as f32 / (1u64 << 24) as f32;
+
+        let sx = tx * tx * (3.0 - 2.0 * tx);
+        let sz = tz * tz * (3.0 - 2.0 * tz);
+        let top = corner(x0, z0) + (corner(x0 + 1, z0) - corner(x0, z0)) * sx;
+        let bottom = corner(x0, z0 + 1) + (corner(x0 + 1, z0 + 1) - corner(x0, z0 + 1)) * sx;
+
+        top + (bottom - top) * sz
+    }
+}
@@ -0,0 +1,77 @@
+use bincode;
+use serde::{Serialize, Deserialize};
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+pub const LEVEL_FILE_NAME: &str = "level.dat";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct GameRules {
+    pub do_weather_cycle: bool,
+    pub do_daylight_cycle: bool,
+    pub keep_inventory: bool,
+    pub random_tick_speed: u32
+}
+
+impl GameRules {
+    pub fn default() -> GameRules {
+        GameRules {
+            do_weather_cycle: true,
+            do_daylight_cycle: true,
+            keep_inventory: false,
+            random_tick_speed: 3
+        }
+    }
+}
+
+/// Everything about a world that isn't stored per chunk
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LevelData {
+    pub seed: u64,
+    pub spawn: [i32; 3],
+    // world age in ticks, 20 per second like vanilla
+    pub time: u64,
+    pub game_rules: GameRules,
+    // block state names by the id chunks are saved with, see `BlockPalette`
+    pub block_states: Vec<String>
+}
+
+impl LevelData {
+    pub fn new(seed: u64) -> LevelData {
+        LevelData {
+            seed,
+            spawn: [0, 64, 0],
+            time: 0,
+            game_rules: GameRules::default(),
+            block_states: vec![]
+        }
+    }
+
+    /// Writes to a temporary file first, so a crash never leaves a half written header
+    pub fn write_to_file(&self, directory: &Path) -> std::io::Result<()> {
+        let content = bincode::serialize(&self)
+            .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
+
+        let temporary_path = directory.join(format!("{}_new", LEVEL_FILE_NAME));
+        fs::write(&temporary_path, &content[..])?;
+        fs::rename(&temporary_path, directory.join(LEVEL_FILE_NAME))
+    }
+
+    /// None when there is no world in the directory yet. A header that can't be read is
+    /// an error, a world opened without it would lose its seed and overwrite it.
+    pub fn read_from_file(directory: &Path) -> std::io::Result<Option<LevelData>> {
+        let path = directory.join(LEVEL_FILE_NAME);
+
+        let content = match fs::read(&path) {
+            Ok(content) => content,
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(Error::new(error.kind(), format!("{}: {}", path.display(), error)))
+        };
+
+        bincode::deserialize(&content[..])
+            .map(Some)
+            .map_err(|error| Error::new(ErrorKind::InvalidData, format!("{}: {}", path.display(), error)))
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+use crate::world::block::Face;
+use crate::world::chunk::{ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::world::world::World;
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block
+}
+
+impl LightChannel {
+    pub const ALL: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+}
+
+type Position = (i32, i32, i32);
+
+/// Level reaching `face`'s neighbour from a block at `level`. Full sky light travels
+/// straight down through transparent blocks without fading.
+fn propagated_level(channel: LightChannel, level: u8, face: Face, opacity: u8) -> u8 {
+    if channel == LightChannel::Sky && face == Face::Down && level == MAX_LIGHT && opacity == 0 {
+        return MAX_LIGHT;
+    }
+    level.saturating_sub(opacity.max(1))
+}
+
+fn neighbour(position: Position, face: Face) -> Position {
+    let (offset_x, offset_y, offset_z) = face.offset();
+    (position.0 + offset_x, position.1 + offset_y, position.2 + offset_z)
+}
+
+/// Spreads light outwards from every queued position until it fades out
+fn propagate_increase(world: &mut World, channel: LightChannel, queue: &mut VecDeque<Position>) {
+    while let Some(position) = queue.pop_front() {
+        let level = world.get_light(channel, position.0, position.1, position.2);
+        if level <= 1 {
+            continue;
+        }
+
+        for &face in Face::ALL.iter() {
+            let next = neighbour(position, face);
+            if next.1 < 0 || next.1 >= CHUNK_HEIGHT as i32 || !world.is_loaded(next.0, next.2) {
+                continue;
+            }
+
+            let opacity = world.get_block(next.0, next.1, next.2).light_opacity();
+            if opacity >= MAX_LIGHT {
+                continue;
+            }
+
+            let new_level = propagated_level(channel, level, face, opacity);
+            if new_level > world.get_light(channel, next.0, next.1, next.2) {
+                world.set_light(channel, next.0, next.1, next.2, new_level);
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Darkens everything that was lit by the queued positions. Neighbours that are lit
+/// from elsewhere end up in `relight` so their light can flow back in.
+fn propagate_decrease(
+    world: &mut World,
+    channel: LightChannel,
+    queue: &mut VecDeque<(Position, u8)>,
+    relight: &mut VecDeque<Position>
+) {
+    while let Some((position, level)) = queue.pop_front() {
+        for &face in Face::ALL.iter() {
+            let next = neighbour(position, face);
+            if next.1 < 0 || next.1 >= CHUNK_HEIGHT as i32 || !world.is_loaded(next.0, next.2) {
+                continue;
+            }
+
+            let next_level = world.get_light(channel, next.0, next.1, next.2);
+            if next_level == 0 {
+                continue;
+            }
+
+            let was_lit_by_us = next_level < level
+                || (channel == LightChannel::Sky && face == Face::Down && level == MAX_LIGHT);
+            if was_lit_by_us {
+                // a weaker light source keeps its own light and spreads it again
+                let emission = match channel {
+                    LightChannel::Sky => 0,
+                    LightChannel::Block => world.get_block(next.0, next.1, next.2).light_emission()
+                };
+                world.set_light(channel, next.0, next.1, next.2, emission);
+                queue.push_back((next, next_level));
+                if emission > 0 {
+                    relight.push_back(next);
+                }
+            } else {
+                relight.push_back(next);
+            }
+        }
+    }
+}
+
+/// Lights a freshly generated chunk and lets light flow across its borders in both directions
+pub fn light_chunk(world: &mut World, position: ChunkPos) {
+    let origin_x = position.x * CHUNK_SIZE as i32;
+    let origin_z = position.z * CHUNK_SIZE as i32;
+
+    let mut sky_queue = VecDeque::new();
+    let mut block_queue = VecDeque::new();
+
+    // straight down from the top of the world, every column starts at full sky light
+    let mut column_tops = [[0_i32; CHUNK_SIZE]; CHUNK_SIZE];
+    if let Some(chunk) = world.chunks.get_mut(&position).map(Arc::make_mut) {
+        for (x, tops) in column_tops.iter_mut().enumerate() {
+            for (z, column_top) in tops.iter_mut().enumerate() {
+                let mut level = MAX_LIGHT;
+                let mut top = -1;
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    let block = chunk.get_block(x, y, z);
+                    let opacity = block.light_opacity();
+                    if opacity > 0 && top < 0 {
+                        top = y as i32;
+                    }
+                    level = level.saturating_sub(opacity);
+                    chunk.set_sky_light(x, y, z, level);
+
+                    let emission = block.light_emission();
+                    if emission > 0 {
+                        chunk.set_block_light(x, y, z, emission);
+                        block_queue.push_back((origin_x + x as i32, y as i32, origin_z + z as i32));
+                    }
+                }
+                *column_top = top;
+            }
+        }
+    }
+
+    // sideways spreading only matters below the tallest neighbouring column, above it
+    // every block already has full sky light
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            let is_border = x == 0 || z == 0 || x == CHUNK_SIZE - 1 || z == CHUNK_SIZE - 1;
+            let highest = if is_border {
+                CHUNK_HEIGHT as i32 - 1
+            } else {
+                column_tops[x][z]
+                    .max(column_tops[x - 1][z])
+                    .max(column_tops[x + 1][z])
+                    .max(column_tops[x][z - 1])
+                    .max(column_tops[x][z + 1])
+                    + 1
+            };
+
+            for y in 0..=highest.min(CHUNK_HEIGHT as i32 - 1) {
+                sky_queue.push_back((origin_x + x as i32, y, origin_z + z as i32));
+            }
+        }
+    }
+
+    // light already in the neighbouring chunks flows in across the border
+    for &(offset_x, offset_z) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+        if !world.chunks.contains_key(&ChunkPos::new(position.x + offset_x, position.z + offset_z)) {
+            continue;
+        }
+
+        for i in 0..CHUNK_SIZE as i32 {
+            let (x, z) = match (offset_x, offset_z) {
+                (-1, _) => (origin_x - 1, origin_z + i),
+                (1, _) => (origin_x + CHUNK_SIZE as i32, origin_z + i),
+                (_, -1) => (origin_x + i, origin_z - 1),
+                _ => (origin_x + i, origin_z + CHUNK_SIZE as i32)
+            };
+            for y in 0..CHUNK_HEIGHT as i32 {
+                sky_queue.push_back((x, y, z));
+                if world.get_light(LightChannel::Block, x, y, z) > 0 {
+                    block_queue.push_back((x, y, z));
+                }
+            }
+        }
+    }
+
+    propagate_increase(world, LightChannel::Sky, &mut sky_queue);
+    propagate_increase(world, LightChannel::Block, &mut block_queue);
+}
+
+/// Fixes up both light channels after the block at the given position changed
+pub fn update_block(world: &mut World, x: i32, y: i32, z: i32) {
+    let block = world.get_block(x, y, z);
+
+    for &channel in LightChannel::ALL.iter() {
+        let mut decrease_queue = VecDeque::new();
+        let mut increase_queue = VecDeque::new();
+
+        let old_level = world.get_light(channel, x, y, z);
+        world.set_light(channel, x, y, z, 0);
+        decrease_queue.push_back(((x, y, z), old_level));
+        propagate_decrease(world, channel, &mut decrease_queue, &mut increase_queue);
+
+        let source = match channel {
+            // nothing above the top layer, so it sees the sky directly
+            LightChannel::Sky if y == CHUNK_HEIGHT as i32 - 1 => MAX_LIGHT.saturating_sub(block.light_opacity()),
+            LightChannel::Sky => 0,
+            LightChannel::Block => block.light_emission()
+        };
+        if source > 0 {
+            world.set_light(channel, x, y, z, source);
+            increase_queue.push_back((x, y, z));
+        }
+
+        propagate_increase(world, channel, &mut increase_queue);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block::Block;
+
+    #[test]
+    fn placed_torch_lights_its_surroundings_and_removing_it_darkens_them() {
+        let mut world = World::flat("light_torch", 1);
+        world.set_block(0, 64, 0, Block::Torch);
+
+        assert_eq!(world.get_light(LightChannel::Block, 0, 64, 0), 14);
+        assert_eq!(world.get_light(LightChannel::Block, 3, 64, 0), 11);
+        assert_eq!(world.get_light(LightChannel::Block, 0, 63, 0), 0);
+
+        world.set_block(0, 64, 0, Block::Air);
+        assert_eq!(world.get_light(LightChannel::Block, 0, 64, 0), 0);
+        assert_eq!(world.get_light(LightChannel::Block, 3, 64, 0), 0);
+    }
+
+    #[test]
+    fn removing_the_bright_source_leaves_the_weaker_emitter_its_light() {
+        let mut world = World::flat("light_weaker_emitter", 1);
+        world.set_block(0, 64, 0, Block::Lava);
+        world.set_block(1, 64, 0, Block::Torch);
+        assert_eq!(world.get_light(LightChannel::Block, 1, 64, 0), 14);
+        assert_eq!(world.get_light(LightChannel::Block, -3, 64, 0), 12);
+
+        world.set_block(0, 64, 0, Block::Air);
+        assert_eq!(world.get_light(LightChannel::Block, 1, 64, 0), 14);
+        assert_eq!(world.get_light(LightChannel::Block, 0, 64, 0), 13);
+        assert_eq!(world.get_light(LightChannel::Block, 4, 64, 0), 11);
+        assert_eq!(world.get_light(LightChannel::Block, -3, 64, 0), 10);
+    }
+
+    #[test]
+    fn covering_a_column_shades_it_and_uncovering_brings_the_sky_back() {
+        let mut world = World::flat("light_sky", 1);
+        assert_eq!(world.get_light(LightChannel::Sky, 5, 64, 5), MAX_LIGHT);
+
+        world.set_block(5, 70, 5, Block::Stone);
+        assert_eq!(world.get_light(LightChannel::Sky, 5, 69, 5), MAX_LIGHT - 1);
+        assert_eq!(world.get_light(LightChannel::Sky, 5, 64, 5), MAX_LIGHT - 1);
+
+        world.set_block(5, 70, 5, Block::Air);
+        assert_eq!(world.get_light(LightChannel::Sky, 5, 64, 5), MAX_LIGHT);
+    }
+}
@@ -0,0 +1,19 @@
+pub mod world;
+pub mod block;
+pub mod biome;
+pub mod chunk;
+pub mod generator;
+pub mod random;
+pub mod weather;
+
+pub mod colormap;
+pub mod lighting;
+pub mod region;
+pub mod level_data;
+pub mod world_saver;
+pub mod block_view;
+pub mod raycast;
+pub mod block_registry;
+pub mod block_palette;
+
+pub mod fluid;
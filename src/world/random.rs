@@ -0,0 +1,46 @@
+/// Small deterministic xorshift generator, so world generation and weather
+/// rolls are reproducible from a seed without pulling in an rng crate.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u64
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            // xorshift gets stuck on zero, so mix the seed first
+            state: Random::mix(seed) | 1
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    /// Uniform float in [0, 1)
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// Uniform float in [min, max)
+    pub fn range_f32(&mut self, min: f32, max: f32) -> f32 {
+        min + self.next_f32() * (max - min)
+    }
+
+    /// Stateless hash used for per-position noise
+    pub fn mix(value: u64) -> u64 {
+        let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    pub fn hash_2d(seed: u64, x: i32, z: i32) -> u64 {
+        Random::mix(seed ^ Random::mix(((x as u32 as u64) << 32) | z as u32 as u64))
+    }
+}
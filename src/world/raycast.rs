@@ -0,0 +1,207 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::world::block::{Block, Face};
+use crate::world::block_view::BlockView;
+
+/// The block a ray hit first
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub block_position: Point3<i32>,
+    pub block: Block,
+    // the face the ray entered through, its normal points back towards the ray origin
+    pub face: Face,
+    pub point: Point3<f32>,
+    pub distance: f32
+}
+
+/// Fluids and air are looked through
+fn is_targetable(block: Block) -> bool {
+    block != Block::Air && !block.is_fluid()
+}
+
+/// Box a block can be targeted by, in block local coordinates
+pub fn outline_box(block: Block) -> (Point3<f32>, Point3<f32>) {
+    let height = block.collision_height().unwrap_or(1.0);
+    (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, height, 1.0))
+}
+
+/// Face of a box whose outward normal points along `axis`, towards positive or negative
+fn face_along(axis: usize, is_positive: bool) -> Face {
+    match (axis, is_positive) {
+        (0, true) => Face::East,
+        (0, false) => Face::West,
+        (1, true) => Face::Up,
+        (1, false) => Face::Down,
+        (2, true) => Face::South,
+        _ => Face::North
+    }
+}
+
+/// The face turned towards a ray along `direction`, for rays that start inside a block
+fn face_against(direction: Vector3<f32>) -> Face {
+    let axis = (0..3)
+        .max_by(|&a, &b| direction[a].abs().total_cmp(&direction[b].abs()))
+        .unwrap();
+    face_along(axis, direction[axis] < 0.0)
+}
+
+/// Distance along the ray to where it enters the box and the face it enters through,
+/// None when it misses. A ray starting inside the box hits it at distance 0.
+fn intersect_box(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    min: Point3<f32>,
+    max: Point3<f32>
+) -> Option<(f32, Option<Face>)> {
+    let mut enter = f32::NEG_INFINITY;
+    let mut exit = f32::INFINITY;
+    let mut enter_face = None;
+
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            if origin[axis] < min[axis] || origin[axis] > max[axis] {
+                return None;
+            }
+            continue;
+        }
+
+        let to_min = (min[axis] - origin[axis]) / direction[axis];
+        let to_max = (max[axis] - origin[axis]) / direction[axis];
+        let (near, far) = if to_min < to_max { (to_min, to_max) } else { (to_max, to_min) };
+        if near > enter {
+            enter = near;
+            // entering through the min side means the face points towards negative
+            enter_face = Some(face_along(axis, direction[axis] < 0.0));
+        }
+        exit = exit.min(far);
+    }
+
+    if enter > exit || exit < 0.0 {
+        return None;
+    }
+    if enter < 0.0 {
+        return Some((0.0, None));
+    }
+    Some((enter, enter_face))
+}
+
+/// Walks the grid cell by cell along the ray (a voxel DDA) and returns the first
+/// targetable block within `reach` blocks of `origin`. A ray without a direction hits nothing.
+pub fn raycast<W: BlockView>(
+    world: &W,
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    reach: f32
+) -> Option<RaycastHit> {
+    if direction.magnitude2() == 0.0 {
+        return None;
+    }
+    let direction = direction.normalize();
+    let mut cell = Point3::new(origin.x.floor() as i32, origin.y.floor() as i32, origin.z.floor() as i32);
+
+    let mut step = [0; 3];
+    // distance along the ray to the next cell boundary per axis, and between two boundaries
+    let mut to_boundary = [f32::INFINITY; 3];
+    let mut between_boundaries = [f32::INFINITY; 3];
+    for axis in 0..3 {
+        if direction[axis] > 0.0 {
+            step[axis] = 1;
+            to_boundary[axis] = (cell[axis] as f32 + 1.0 - origin[axis]) / direction[axis];
+            between_boundaries[axis] = 1.0 / direction[axis];
+        } else if direction[axis] < 0.0 {
+            step[axis] = -1;
+            to_boundary[axis] = (cell[axis] as f32 - origin[axis]) / direction[axis];
+            between_boundaries[axis] = -1.0 / direction[axis];
+        }
+    }
+
+    // the face of the current cell the ray came in through, unknown in the first cell
+    let mut entered_through = None;
+    loop {
+        let block = world.get_block(cell.x, cell.y, cell.z);
+        if is_targetable(block) {
+            let offset = Vector3::new(cell.x as f32, cell.y as f32, cell.z as f32);
+            let (min, max) = outline_box(block);
+            if let Some((distance, face)) = intersect_box(origin, direction, min + offset, max + offset) {
+                if distance <= reach {
+                    return Some(RaycastHit {
+                        block_position: cell,
+                        block,
+                        face: face.or(entered_through).unwrap_or_else(|| face_against(direction)),
+                        point: origin + direction * distance,
+                        distance
+                    });
+                }
+            }
+        }
+
+        let axis = (0..3)
+            .min_by(|&a, &b| to_boundary[a].total_cmp(&to_boundary[b]))
+            .unwrap();
+        if to_boundary[axis] > reach {
+            return None;
+        }
+
+        cell[axis] += step[axis];
+        to_boundary[axis] += between_boundaries[axis];
+        entered_through = Some(face_along(axis, step[axis] < 0));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_view::BlockMap;
+
+    #[test]
+    fn ray_without_a_direction_hits_nothing() {
+        let world = BlockMap::filled(vec![(0, 0, 0)], Block::Stone);
+
+        assert_eq!(raycast(&world, Point3::new(0.5, 2.5, 0.5), Vector3::new(0.0, 0.0, 0.0), 5.0), None);
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let world = BlockMap::filled(vec![(0, 0, 0), (3, 3, 0)], Block::Stone);
+
+        let down = raycast(&world, Point3::new(0.5, 2.5, 0.5), Vector3::new(0.0, -1.0, 0.0), 5.0).unwrap();
+        assert_eq!(down.block_position, Point3::new(0, 0, 0));
+        assert_eq!(down.face, Face::Up);
+        assert_eq!(down.distance, 1.5);
+
+        let east = raycast(&world, Point3::new(0.5, 3.5, 0.5), Vector3::new(2.0, 0.0, 0.0), 5.0).unwrap();
+        assert_eq!(east.block_position, Point3::new(3, 3, 0));
+        assert_eq!(east.face, Face::West);
+        assert_eq!(east.point, Point3::new(3.0, 3.5, 0.5));
+
+        assert_eq!(raycast(&world, Point3::new(0.5, 3.5, 0.5), Vector3::new(1.0, 0.0, 0.0), 2.0), None);
+    }
+
+    #[test]
+    fn ray_through_the_empty_top_of_a_half_block_misses_it() {
+        // no block is half height yet, so the box is tested on its own
+        let (min, max) = (Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 0.5, 1.0));
+
+        let above = intersect_box(Point3::new(-1.0, 0.75, 0.5), Vector3::new(1.0, 0.0, 0.0), min, max);
+        assert_eq!(above, None);
+
+        let side = intersect_box(Point3::new(-1.0, 0.25, 0.5), Vector3::new(1.0, 0.0, 0.0), min, max);
+        assert_eq!(side, Some((1.0, Some(Face::West))));
+
+        let top = intersect_box(Point3::new(0.5, 2.0, 0.5), Vector3::new(0.0, -1.0, 0.0), min, max);
+        assert_eq!(top, Some((1.5, Some(Face::Up))));
+    }
+
+    #[test]
+    fn ray_starting_inside_a_block_hits_the_face_turned_towards_it() {
+        let world = BlockMap::filled(vec![(0, 0, 0)], Block::Stone);
+
+        let hit = raycast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::new(1.0, -0.2, 0.0), 5.0).unwrap();
+        assert_eq!(hit.block_position, Point3::new(0, 0, 0));
+        assert_eq!(hit.distance, 0.0);
+        assert_eq!(hit.face, Face::West);
+
+        let hit = raycast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::new(0.1, -1.0, 0.3), 5.0).unwrap();
+        assert_eq!(hit.face, Face::Up);
+    }
+}
@@ -0,0 +1,369 @@
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::world::chunk::ChunkPos;
+
+/// Chunks per region along each axis
+pub const REGION_SIZE: i32 = 32;
+const SECTOR_SIZE: usize = 4096;
+const CHUNKS_PER_REGION: usize = (REGION_SIZE * REGION_SIZE) as usize;
+// the offset table and the timestamp table take one sector each
+const HEADER_SECTORS: usize = 2;
+// same id vanilla uses for zlib
+const COMPRESSION_ZLIB: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RegionPos {
+    pub x: i32,
+    pub z: i32
+}
+
+impl RegionPos {
+    pub fn from_chunk(position: ChunkPos) -> RegionPos {
+        RegionPos {
+            x: position.x.div_euclid(REGION_SIZE),
+            z: position.z.div_euclid(REGION_SIZE)
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        format!("r.{}.{}.mca", self.x, self.z)
+    }
+}
+
+/// A file of 32x32 compressed chunks in the vanilla Anvil layout. Chunks live in 4 KiB
+/// sectors, the first sector maps every chunk to its first sector and sector count.
+pub struct RegionFile {
+    path: PathBuf,
+    file: File,
+    // (first sector, sector count) per chunk, (0, 0) when the chunk was never saved
+    offsets: Vec<(usize, usize)>,
+    used_sectors: Vec<bool>
+}
+
+impl RegionFile {
+    pub fn open(path: &Path) -> std::io::Result<RegionFile> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let mut header = vec![0_u8; HEADER_SECTORS * SECTOR_SIZE];
+        let file_length = file.metadata()?.len() as usize;
+        if file_length < header.len() {
+            // new file, or one that was cut off before its header was complete
+            if file_length > 0 {
+                println!("Region file {:?} has a truncated header, starting it over", path);
+            }
+            file.set_len(header.len() as u64)?;
+            file.seek(SeekFrom::Start(0))?;
+            file.write_all(&header)?;
+        } else {
+            file.seek(SeekFrom::Start(0))?;
+            file.read_exact(&mut header)?;
+        }
+
+        let file_sectors = (file.metadata()?.len() as usize).div_ceil(SECTOR_SIZE);
+        let mut used_sectors = vec![false; file_sectors.max(HEADER_SECTORS)];
+        for sector in used_sectors.iter_mut().take(HEADER_SECTORS) {
+            *sector = true;
+        }
+
+        let mut offsets = vec![(0, 0); CHUNKS_PER_REGION];
+        for (i, entry) in header[..CHUNKS_PER_REGION * 4].chunks_exact(4).enumerate() {
+            let first_sector = u32::from_be_bytes([0, entry[0], entry[1], entry[2]]) as usize;
+            let sector_count = entry[3] as usize;
+            if sector_count == 0 {
+                continue;
+            }
+
+            // entries pointing into the header or past the end of the file are dropped,
+            // those chunks get generated again
+            if first_sector < HEADER_SECTORS || first_sector + sector_count > file_sectors {
+                println!("Region file {:?} has a truncated chunk at index {}, dropping it", path, i);
+                continue;
+            }
+
+            offsets[i] = (first_sector, sector_count);
+            for sector in used_sectors[first_sector..first_sector + sector_count].iter_mut() {
+                *sector = true;
+            }
+        }
+
+        Ok(RegionFile {
+            path: path.to_path_buf(),
+            file,
+            offsets,
+            used_sectors
+        })
+    }
+
+    fn index(position: ChunkPos) -> usize {
+        (position.z.rem_euclid(REGION_SIZE) * REGION_SIZE + position.x.rem_euclid(REGION_SIZE)) as usize
+    }
+
+    /// The decompressed chunk data, None when it was never saved or can't be read back
+    pub fn read_chunk(&mut self, position: ChunkPos) -> Option<Vec<u8>> {
+        let (first_sector, sector_count) = self.offsets[RegionFile::index(position)];
+        if sector_count == 0 {
+            return None;
+        }
+
+        match self.read_sectors(first_sector, sector_count) {
+            Ok(data) => Some(data),
+            Err(error) => {
+                println!("Unable to read chunk {:?} from {:?}: {}", position, self.path, error);
+                None
+            }
+        }
+    }
+
+    fn read_sectors(&mut self, first_sector: usize, sector_count: usize) -> std::io::Result<Vec<u8>> {
+        let mut sectors = vec![0_u8; sector_count * SECTOR_SIZE];
+        self.file.seek(SeekFrom::Start((first_sector * SECTOR_SIZE) as u64))?;
+        self.file.read_exact(&mut sectors)?;
+
+        let length = u32::from_be_bytes([sectors[0], sectors[1], sectors[2], sectors[3]]) as usize;
+        if length == 0 || length + 4 > sectors.len() {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "chunk length exceeds its sectors"));
+        }
+        if sectors[4] != COMPRESSION_ZLIB {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "unknown compression type"));
+        }
+
+        let mut data = vec![];
+        ZlibDecoder::new(&sectors[5..4 + length]).read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    /// Compresses and writes a chunk, reusing its old sectors when it still fits
+    pub fn write_chunk(&mut self, position: ChunkPos, data: &[u8]) -> std::io::Result<()> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        let compressed = encoder.finish()?;
+
+        let mut payload = Vec::with_capacity(compressed.len() + 5);
+        payload.extend_from_slice(&(compressed.len() as u32 + 1).to_be_bytes());
+        payload.push(COMPRESSION_ZLIB);
+        payload.extend_from_slice(&compressed);
+
+        let sector_count = payload.len().div_ceil(SECTOR_SIZE);
+        if sector_count > u8::MAX as usize {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "chunk is too large for a region file"));
+        }
+        payload.resize(sector_count * SECTOR_SIZE, 0);
+
+        let index = RegionFile::index(position);
+        let (old_first, old_count) = self.offsets[index];
+        let first_sector = if sector_count <= old_count {
+            old_first
+        } else {
+            self.find_free_sectors(sector_count)
+        };
+        for sector in self.used_sectors[old_first..old_first + old_count].iter_mut() {
+            *sector = false;
+        }
+
+        self.file.seek(SeekFrom::Start((first_sector * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&payload)?;
+
+        if first_sector + sector_count > self.used_sectors.len() {
+            self.used_sectors.resize(first_sector + sector_count, false);
+        }
+        for sector in self.used_sectors[first_sector..first_sector + sector_count].iter_mut() {
+            *sector = true;
+        }
+        self.offsets[index] = (first_sector, sector_count);
+
+        // a chunk that moved keeps its old copy until the table points at the new one
+        let entry = ((first_sector as u32) << 8 | sector_count as u32).to_be_bytes();
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs() as u32)
+            .unwrap_or(0)
+            .to_be_bytes();
+
+        self.file.seek(SeekFrom::Start((index * 4) as u64))?;
+        self.file.write_all(&entry)?;
+        self.file.seek(SeekFrom::Start((SECTOR_SIZE + index * 4) as u64))?;
+        self.file.write_all(&timestamp)?;
+
+        Ok(())
+    }
+
+    /// First run of free sectors that is long enough, or the end of the file
+    fn find_free_sectors(&self, sector_count: usize) -> usize {
+        let mut run_start = 0;
+        let mut run_length = 0;
+        for (sector, &used) in self.used_sectors.iter().enumerate() {
+            if used {
+                run_length = 0;
+                continue;
+            }
+            if run_length == 0 {
+                run_start = sector;
+            }
+            run_length += 1;
+            if run_length == sector_count {
+                return run_start;
+            }
+        }
+
+        // a free run at the end of the file can be extended
+        if run_length > 0 { run_start } else { self.used_sectors.len() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::random::Random;
+    use std::fs;
+
+    fn test_directory(name: &str) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("region_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    // random bytes barely compress, so the chunk needs about `length` bytes of sectors
+    fn noise(seed: u64, length: usize) -> Vec<u8> {
+        let mut random = Random::new(seed);
+        (0..length).map(|_| random.next_u64() as u8).collect()
+    }
+
+    fn overwrite(path: &Path, offset: u64, bytes: &[u8]) {
+        let mut file = OpenOptions::new().write(true).open(path).unwrap();
+        file.seek(SeekFrom::Start(offset)).unwrap();
+        file.write_all(bytes).unwrap();
+    }
+
+    #[test]
+    fn chunks_read_back_after_reopening() {
+        let directory = test_directory("round_trip");
+        let path = directory.join("r.0.0.mca");
+        let first = ChunkPos::new(3, 7);
+        let second = ChunkPos::new(-1, -1);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(first, b"first chunk").unwrap();
+        region.write_chunk(second, &noise(1, 6000)).unwrap();
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(first), Some(b"first chunk".to_vec()));
+        assert_eq!(region.read_chunk(second), Some(noise(1, 6000)));
+        assert_eq!(region.read_chunk(ChunkPos::new(0, 0)), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn chunk_that_outgrows_its_sectors_moves() {
+        let directory = test_directory("outgrown");
+        let path = directory.join("r.0.0.mca");
+        let grown = ChunkPos::new(0, 0);
+        let neighbour = ChunkPos::new(1, 0);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(grown, b"small").unwrap();
+        region.write_chunk(neighbour, b"neighbour").unwrap();
+        let (old_first, _) = region.offsets[RegionFile::index(grown)];
+
+        region.write_chunk(grown, &noise(2, 3 * SECTOR_SIZE)).unwrap();
+        let (new_first, new_count) = region.offsets[RegionFile::index(grown)];
+        assert_ne!(new_first, old_first);
+        assert!(new_count > 1);
+        // the sector it left is free for the next small chunk
+        assert!(!region.used_sectors[old_first]);
+        drop(region);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(grown), Some(noise(2, 3 * SECTOR_SIZE)));
+        assert_eq!(region.read_chunk(neighbour), Some(b"neighbour".to_vec()));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn truncated_header_starts_the_file_over() {
+        let directory = test_directory("truncated_header");
+        let path = directory.join("r.0.0.mca");
+        let position = ChunkPos::new(0, 0);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(position, b"lost").unwrap();
+        drop(region);
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(1000).unwrap();
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(position), None);
+        region.write_chunk(position, b"new").unwrap();
+        assert_eq!(region.read_chunk(position), Some(b"new".to_vec()));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn sector_offset_past_the_end_of_the_file_is_dropped() {
+        let directory = test_directory("offset_past_end");
+        let path = directory.join("r.0.0.mca");
+        let position = ChunkPos::new(5, 5);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(position, b"moved away").unwrap();
+        drop(region);
+        let index = RegionFile::index(position) as u64;
+        overwrite(&path, index * 4, &(100_u32 << 8 | 1).to_be_bytes());
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(position), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn truncated_chunk_sectors_read_as_missing() {
+        let directory = test_directory("truncated_sectors");
+        let path = directory.join("r.0.0.mca");
+        let position = ChunkPos::new(0, 0);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(position, &noise(3, 2 * SECTOR_SIZE)).unwrap();
+        drop(region);
+        // cut off in the middle of the chunk's last sector
+        let length = fs::metadata(&path).unwrap().len();
+        OpenOptions::new().write(true).open(&path).unwrap().set_len(length - 100).unwrap();
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(position), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn length_field_larger_than_the_file_reads_as_missing() {
+        let directory = test_directory("length_too_large");
+        let path = directory.join("r.0.0.mca");
+        let position = ChunkPos::new(0, 0);
+
+        let mut region = RegionFile::open(&path).unwrap();
+        region.write_chunk(position, b"chunk").unwrap();
+        let (first_sector, _) = region.offsets[RegionFile::index(position)];
+        drop(region);
+        overwrite(&path, (first_sector * SECTOR_SIZE) as u64, &u32::MAX.to_be_bytes());
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(position), None);
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
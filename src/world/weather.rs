@@ -0,0 +1,180 @@
+use crate::world::random::Random;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeatherState {
+    Clear,
+    Rain,
+    Thunder
+}
+
+impl WeatherState {
+    pub fn from_name(name: &str) -> Option<WeatherState> {
+        match name {
+            "clear" => Some(WeatherState::Clear),
+            "rain" => Some(WeatherState::Rain),
+            "thunder" => Some(WeatherState::Thunder),
+            _ => None
+        }
+    }
+
+    // durations in seconds, roughly the vanilla tick ranges divided by 20
+    fn duration_range(&self) -> (f32, f32) {
+        match self {
+            WeatherState::Clear => (600.0, 9000.0),
+            WeatherState::Rain => (600.0, 1200.0),
+            WeatherState::Thunder => (180.0, 780.0)
+        }
+    }
+}
+
+// how fast rain and thunder fade in and out, per second
+const LEVEL_CHANGE_RATE: f32 = 0.2;
+const FLASH_DURATION: f32 = 0.35;
+
+pub struct Weather {
+    pub state: WeatherState,
+    // seconds until the next transition
+    pub time_remaining: f32,
+    rain_level: f32,
+    thunder_level: f32,
+    // levels before the last tick, rendering blends towards the current ones
+    previous_rain_level: f32,
+    previous_thunder_level: f32,
+
+    flash_time: f32,
+    previous_flash_time: f32,
+    next_lightning: f32,
+    random: Random
+}
+
+impl Weather {
+    pub fn new(seed: u64) -> Weather {
+        let mut random = Random::new(seed ^ 0x0057_4541_5448_4552);
+        let (min, max) = WeatherState::Clear.duration_range();
+        let time_remaining = random.range_f32(min, max);
+
+        Weather {
+            state: WeatherState::Clear,
+            time_remaining,
+            rain_level: 0.0,
+            thunder_level: 0.0,
+            previous_rain_level: 0.0,
+            previous_thunder_level: 0.0,
+            flash_time: 0.0,
+            previous_flash_time: 0.0,
+            next_lightning: 0.0,
+            random
+        }
+    }
+
+    pub fn tick(&mut self, delta: f32) {
+        self.previous_rain_level = self.rain_level;
+        self.previous_thunder_level = self.thunder_level;
+        self.previous_flash_time = self.flash_time;
+
+        self.time_remaining -= delta;
+        if self.time_remaining <= 0.0 {
+            let next = self.next_state();
+            self.set_state(next, None);
+        }
+
+        let (rain_target, thunder_target) = match self.state {
+            WeatherState::Clear => (0.0, 0.0),
+            WeatherState::Rain => (1.0, 0.0),
+            WeatherState::Thunder => (1.0, 1.0)
+        };
+        self.rain_level = Weather::approach(self.rain_level, rain_target, LEVEL_CHANGE_RATE * delta);
+        self.thunder_level = Weather::approach(self.thunder_level, thunder_target, LEVEL_CHANGE_RATE * delta);
+
+        self.flash_time = (self.flash_time - delta).max(0.0);
+        if self.thunder_level > 0.9 {
+            self.next_lightning -= delta;
+            if self.next_lightning <= 0.0 {
+                self.flash_time = FLASH_DURATION;
+                self.next_lightning = self.random.range_f32(5.0, 20.0);
+            }
+        }
+    }
+
+    /// Force a state, e.g. from the debug console. Without a duration a random one is rolled.
+    pub fn set_state(&mut self, state: WeatherState, duration: Option<f32>) {
+        self.state = state;
+        self.time_remaining = match duration {
+            Some(duration) => duration,
+            None => {
+                let (min, max) = state.duration_range();
+                self.random.range_f32(min, max)
+            }
+        };
+        if state == WeatherState::Thunder {
+            self.next_lightning = self.random.range_f32(2.0, 8.0);
+        }
+    }
+
+    fn next_state(&mut self) -> WeatherState {
+        match self.state {
+            WeatherState::Clear => WeatherState::Rain,
+            WeatherState::Rain => {
+                if self.random.next_f32() < 0.3 { WeatherState::Thunder } else { WeatherState::Clear }
+            }
+            WeatherState::Thunder => WeatherState::Rain
+        }
+    }
+
+    fn approach(current: f32, target: f32, step: f32) -> f32 {
+        if current < target {
+            (current + step).min(target)
+        } else {
+            (current - step).max(target)
+        }
+    }
+
+    fn lerp(previous: f32, current: f32, partial_tick: f32) -> f32 {
+        previous + (current - previous) * partial_tick
+    }
+
+    // the getters below take how far rendering is between the last tick and the next
+
+    pub fn rain_level(&self, partial_tick: f32) -> f32 {
+        Weather::lerp(self.previous_rain_level, self.rain_level, partial_tick)
+    }
+
+    pub fn thunder_level(&self, partial_tick: f32) -> f32 {
+        Weather::lerp(self.previous_thunder_level, self.thunder_level, partial_tick)
+    }
+
+    /// How much the sky colour is scaled down, 0 for a clear sky
+    pub fn sky_darkening(&self, partial_tick: f32) -> f32 {
+        self.rain_level(partial_tick) * 0.4 + self.thunder_level(partial_tick) * 0.35
+    }
+
+    /// Brightness of the current lightning flash in [0, 1], flickers twice before fading
+    pub fn lightning_flash(&self, partial_tick: f32) -> f32 {
+        // a new flash starts at the tick instead of fading in from the last one
+        let flash_time = if self.flash_time > self.previous_flash_time {
+            self.flash_time
+        } else {
+            Weather::lerp(self.previous_flash_time, self.flash_time, partial_tick)
+        };
+        if flash_time <= 0.0 {
+            return 0.0;
+        }
+        let t = 1.0 - flash_time / FLASH_DURATION;
+        let flicker = if t < 0.15 || (t > 0.3 && t < 0.45) { 1.0 } else { 0.4 };
+        flicker * (flash_time / FLASH_DURATION)
+    }
+
+    pub fn sky_color(&self, base: [f32; 3], partial_tick: f32) -> [f32; 3] {
+        let darkening = 1.0 - self.sky_darkening(partial_tick);
+        let flash = self.lightning_flash(partial_tick);
+        let rain_level = self.rain_level(partial_tick);
+        let grey = (base[0] * 0.3 + base[1] * 0.59 + base[2] * 0.11) * 0.6;
+
+        let mut color = [0.0; 3];
+        for i in 0..3 {
+            let desaturated = base[i] + (grey - base[i]) * rain_level * 0.6;
+            color[i] = desaturated * darkening + (1.0 - desaturated * darkening) * flash * 0.45;
+        }
+        color
+    }
+}
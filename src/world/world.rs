@@ -0,0 +1,538 @@
+use std::collections::{HashMap, HashSet};
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+use crate::game_loop::tick_clock::{SECONDS_PER_TICK, TICKS_PER_SECOND};
+use crate::jobs::job_system::{JobKind, JobSystem};
+use crate::world::biome::{Biome, Precipitation};
+use crate::world::block::{Block, Face};
+use crate::world::block_palette::BlockPalette;
+use crate::world::block_registry::{BlockRegistry, StateId};
+use crate::world::block_view::{self, BlockView, ChunkNeighbourhood};
+use crate::world::chunk::{Chunk, ChunkPos, CHUNK_HEIGHT, CHUNK_SIZE};
+use crate::world::fluid::{self, FluidTicks};
+use crate::world::generator::{TerrainGenerator, SEA_LEVEL};
+use crate::world::level_data::{LevelData, LEVEL_FILE_NAME};
+use crate::world::lighting::{self, LightChannel};
+use crate::world::weather::Weather;
+use crate::world::world_saver::WorldSaver;
+
+// ticks between automatic saves of changed chunks
+const AUTOSAVE_INTERVAL: u32 = 30 * TICKS_PER_SECOND;
+// streamed chunks lit and added per update, lighting runs on the main thread
+const MAX_CHUNKS_PER_UPDATE: usize = 4;
+// chunks beyond the streaming radius kept loaded, so walking back and forth over a
+// chunk border doesn't load them again
+const UNLOAD_MARGIN: i32 = 2;
+
+/// A chunk built on a worker thread, `is_new` when it was generated instead of loaded
+struct StreamedChunk {
+    position: ChunkPos,
+    chunk: Chunk,
+    is_new: bool
+}
+
+pub struct World {
+    pub level: LevelData,
+    // shared with mesh jobs, changing a chunk copies it while a job still reads it
+    pub chunks: HashMap<ChunkPos, Arc<Chunk>>,
+    pub weather: Weather,
+    // every vanilla block state, shared with whatever needs to name them
+    pub blocks: Arc<BlockRegistry>,
+    // ids chunks are saved with, shared with loading jobs
+    palette: Arc<BlockPalette>,
+    // chunks whose blocks or light changed since the renderer last meshed them
+    dirty_chunks: HashSet<ChunkPos>,
+    // dirty chunks that were unloaded, their meshes have to go
+    unloaded_chunks: HashSet<ChunkPos>,
+    // chunks that changed since they were last written to their region file
+    unsaved_chunks: HashSet<ChunkPos>,
+    ticks_since_save: u32,
+    // fluids waiting to flow, by tick of the level's clock
+    fluid_ticks: FluidTicks,
+    generator: TerrainGenerator,
+    saver: WorldSaver,
+
+    // the chunk streaming was last centred on
+    stream_center: Option<ChunkPos>,
+    // chunks queued or being built by a job
+    streaming_chunks: HashSet<ChunkPos>,
+    streamed_sender: Sender<StreamedChunk>,
+    streamed_receiver: Receiver<StreamedChunk>
+}
+
+impl World {
+    /// Opens the world saved in `directory`, or creates a new one with `default_seed`.
+    /// Fails when the directory can't be created, the registry misses a block or the
+    /// level data of saved chunks can't be read.
+    pub fn load(directory: &Path, default_seed: u64, blocks: Arc<BlockRegistry>) -> std::io::Result<World> {
+        let saver = WorldSaver::new(directory)?;
+
+        let saved_level = LevelData::read_from_file(directory)?;
+        if saved_level.is_none() && saver.has_saved_chunks()? {
+            let message = format!("{} is missing, the saved chunks can't be read without it", LEVEL_FILE_NAME);
+            return Err(Error::new(ErrorKind::NotFound, message));
+        }
+        if saved_level.as_ref().is_some_and(|level| level.block_states.is_empty()) && saver.has_saved_chunks()? {
+            let message = format!("{} has no block palette, the saved chunks can't be read without it", LEVEL_FILE_NAME);
+            return Err(Error::new(ErrorKind::InvalidData, message));
+        }
+        let is_new = saved_level.is_none();
+        let mut level = saved_level.unwrap_or_else(|| LevelData::new(default_seed));
+        let palette = BlockPalette::new(std::mem::take(&mut level.block_states), &blocks)?;
+        level.block_states = palette.names().to_vec();
+
+        let mut world = World::with_level(level, saver, blocks, Arc::new(palette));
+        if is_new {
+            world.generate_around(0, 0, 0);
+            let height = world.highest_opaque_block(0, 0).unwrap_or(SEA_LEVEL);
+            world.level.spawn = [0, height + 1, 0];
+        }
+        Ok(world)
+    }
+
+    fn with_level(level: LevelData, saver: WorldSaver, blocks: Arc<BlockRegistry>, palette: Arc<BlockPalette>) -> World {
+        let (streamed_sender, streamed_receiver) = channel();
+
+        World {
+            weather: Weather::new(level.seed),
+            generator: TerrainGenerator::new(level.seed),
+            level,
+            blocks,
+            palette,
+            chunks: HashMap::new(),
+            dirty_chunks: HashSet::new(),
+            unloaded_chunks: HashSet::new(),
+            unsaved_chunks: HashSet::new(),
+            ticks_since_save: 0,
+            fluid_ticks: FluidTicks::new(),
+            saver,
+            stream_center: None,
+            streaming_chunks: HashSet::new(),
+            streamed_sender,
+            streamed_receiver
+        }
+    }
+
+    /// Advances the simulation by one fixed tick
+    pub fn tick(&mut self) {
+        if self.level.game_rules.do_weather_cycle {
+            self.weather.tick(SECONDS_PER_TICK);
+        }
+
+        for (x, y, z) in self.fluid_ticks.take_due(self.level.time) {
+            fluid::update(self, x, y, z);
+        }
+
+        self.level.time += 1;
+
+        self.ticks_since_save += 1;
+        if self.ticks_since_save >= AUTOSAVE_INTERVAL {
+            self.save();
+        }
+    }
+
+    /// Queues every changed chunk and the level header for the background writer
+    pub fn save(&mut self) {
+        for position in self.unsaved_chunks.drain() {
+            if let Some(chunk) = self.chunks.get(&position) {
+                self.saver.save_chunk(position, chunk.to_bytes(&self.palette));
+            }
+        }
+        self.saver.save_level(self.level.clone());
+        self.ticks_since_save = 0;
+    }
+
+    /// Saves and waits until everything is written, for shutting down
+    pub fn save_and_flush(&mut self) {
+        self.save();
+        self.saver.flush();
+    }
+
+    /// Generate every missing chunk within `radius` chunks of the given block position
+    pub fn generate_around(&mut self, x: i32, z: i32, radius: i32) {
+        let center = ChunkPos::from_block(x, z);
+        for chunk_x in center.x - radius..=center.x + radius {
+            for chunk_z in center.z - radius..=center.z + radius {
+                let position = ChunkPos::new(chunk_x, chunk_z);
+                if self.chunks.contains_key(&position) {
+                    continue;
+                }
+
+                let saved = self.saver
+                    .load_chunk(position)
+                    .and_then(|data| Chunk::from_bytes(position, &data, &self.palette));
+                match saved {
+                    Some(chunk) => self.add_chunk(chunk, false),
+                    None => self.add_chunk(self.generator.generate_chunk(position), true)
+                }
+            }
+        }
+    }
+
+    /// Queues loading or generating the missing chunks within `radius` chunks of the given
+    /// block position on the job system, nearest first, and drops the queued ones that are
+    /// out of range now. Finished chunks are added by `receive_chunks`. Loaded chunks
+    /// further than `UNLOAD_MARGIN` outside the range are saved and unloaded.
+    pub fn stream_around(&mut self, jobs: &JobSystem, x: i32, z: i32, radius: i32) {
+        let center = ChunkPos::from_block(x, z);
+        if self.stream_center == Some(center) {
+            return;
+        }
+        self.stream_center = Some(center);
+
+        let distance = |position: ChunkPos| (position.x - center.x).abs().max((position.z - center.z).abs());
+        let left_behind: Vec<ChunkPos> = self.chunks
+            .keys()
+            .filter(|&&position| distance(position) > radius + UNLOAD_MARGIN)
+            .copied()
+            .collect();
+        for position in left_behind {
+            self.unload_chunk(position);
+        }
+        for position in jobs.reprioritise(JobKind::Generate, |position| {
+            let distance = distance(position);
+            if distance <= radius { Some(distance as u32) } else { None }
+        }) {
+            self.streaming_chunks.remove(&position);
+        }
+
+        for chunk_x in center.x - radius..=center.x + radius {
+            for chunk_z in center.z - radius..=center.z + radius {
+                let position = ChunkPos::new(chunk_x, chunk_z);
+                if self.chunks.contains_key(&position) || !self.streaming_chunks.insert(position) {
+                    continue;
+                }
+
+                let loader = self.saver.loader();
+                let palette = self.palette.clone();
+                let generator = self.generator.clone();
+                let sender = self.streamed_sender.clone();
+                jobs.submit(JobKind::Generate, position, distance(position) as u32, move || {
+                    let saved = loader
+                        .load_chunk(position)
+                        .and_then(|data| Chunk::from_bytes(position, &data, &palette));
+                    let (chunk, is_new) = match saved {
+                        Some(chunk) => (chunk, false),
+                        None => (generator.generate_chunk(position), true)
+                    };
+                    let _ = sender.send(StreamedChunk { position, chunk, is_new });
+                });
+            }
+        }
+    }
+
+    /// Adds a few of the chunks finished by streaming jobs
+    pub fn receive_chunks(&mut self) {
+        let streamed: Vec<StreamedChunk> = self.streamed_receiver
+            .try_iter()
+            .take(MAX_CHUNKS_PER_UPDATE)
+            .collect();
+
+        for streamed in streamed {
+            // results of jobs that ran while their chunk left the range
+            if !self.streaming_chunks.remove(&streamed.position) || self.chunks.contains_key(&streamed.position) {
+                continue;
+            }
+            self.add_chunk(streamed.chunk, streamed.is_new);
+        }
+    }
+
+    /// Saved chunks come with their light and have their fluids woken, new ones are lit
+    /// and saved later. The
+    /// neighbours are meshed again, their border faces were drawn towards the gap.
+    fn add_chunk(&mut self, chunk: Chunk, is_new: bool) {
+        let position = chunk.position;
+        self.chunks.insert(position, Arc::new(chunk));
+        if is_new {
+            lighting::light_chunk(self, position);
+            self.unsaved_chunks.insert(position);
+        } else {
+            fluid::wake_chunk(self, position);
+        }
+
+        self.dirty_chunks.insert(position);
+        for &(offset_x, offset_z) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+            self.dirty_chunks.insert(ChunkPos::new(position.x + offset_x, position.z + offset_z));
+        }
+    }
+
+    /// Saves the chunk when it changed and drops it
+    fn unload_chunk(&mut self, position: ChunkPos) {
+        if let Some(chunk) = self.chunks.remove(&position) {
+            if self.unsaved_chunks.remove(&position) {
+                self.saver.save_chunk(position, chunk.to_bytes(&self.palette));
+            }
+            self.dirty_chunks.insert(position);
+            self.unloaded_chunks.insert(position);
+        }
+    }
+
+    /// A snapshot of the chunk and its neighbours for meshing on another thread
+    pub fn neighbourhood(&self, position: ChunkPos) -> ChunkNeighbourhood {
+        ChunkNeighbourhood::new(position, self.generator.clone(), |position| self.chunks.get(&position).cloned())
+    }
+
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Block {
+        BlockView::get_block(self, x, y, z)
+    }
+
+    /// The registry state of a block, None outside loaded chunks
+    pub fn block_state(&self, x: i32, y: i32, z: i32) -> Option<StateId> {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return None;
+        }
+        let (position, local_x, local_z) = block_view::local(x, z);
+        let chunk = self.chunks.get(&position)?;
+        let (block, facing) = (chunk.get_block(local_x, y as usize, local_z), chunk.get_facing(local_x, y as usize, local_z));
+        self.blocks.state_of(block, facing, chunk.get_fluid_level(local_x, y as usize, local_z))
+    }
+
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, block: Block) {
+        self.place_block(x, y, z, block, None);
+    }
+
+    /// Sets a block facing a direction, see `Block::placement_facing`
+    pub fn place_block(&mut self, x: i32, y: i32, z: i32, block: Block, facing: Option<Face>) {
+        self.set_block_data(x, y, z, block, facing, 0);
+    }
+
+    /// Sets water or lava at a level, see `fluid::FALLING`
+    pub fn place_fluid(&mut self, x: i32, y: i32, z: i32, block: Block, level: u8) {
+        self.set_block_data(x, y, z, block, None, level);
+    }
+
+    /// Changing a block relights it and wakes the fluids around it
+    fn set_block_data(&mut self, x: i32, y: i32, z: i32, block: Block, facing: Option<Face>, fluid_level: u8) {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return;
+        }
+        let (position, local_x, local_z) = block_view::local(x, z);
+        match self.chunks.get_mut(&position).map(Arc::make_mut) {
+            Some(chunk) => {
+                chunk.set_block(local_x, y as usize, local_z, block);
+                chunk.set_facing(local_x, y as usize, local_z, facing);
+                chunk.set_fluid_level(local_x, y as usize, local_z, fluid_level);
+            }
+            None => return
+        }
+
+        self.mark_dirty(x, z);
+        lighting::update_block(self, x, y, z);
+        fluid::wake_around(self, x, y, z);
+    }
+
+    pub fn get_fluid_level(&self, x: i32, y: i32, z: i32) -> u8 {
+        BlockView::get_fluid_level(self, x, y, z)
+    }
+
+    /// Runs `fluid::update` for the position `delay` ticks from now
+    pub fn schedule_fluid_update(&mut self, x: i32, y: i32, z: i32, delay: u64) {
+        self.fluid_ticks.schedule((x, y, z), self.level.time + delay);
+    }
+
+    pub fn is_loaded(&self, x: i32, z: i32) -> bool {
+        self.chunks.contains_key(&ChunkPos::from_block(x, z))
+    }
+
+    pub fn get_light(&self, channel: LightChannel, x: i32, y: i32, z: i32) -> u8 {
+        BlockView::get_light(self, channel, x, y, z)
+    }
+
+    pub fn set_light(&mut self, channel: LightChannel, x: i32, y: i32, z: i32, level: u8) {
+        if y < 0 || y >= CHUNK_HEIGHT as i32 {
+            return;
+        }
+
+        let (position, local_x, local_z) = block_view::local(x, z);
+        match (self.chunks.get_mut(&position).map(Arc::make_mut), channel) {
+            (Some(chunk), LightChannel::Sky) => chunk.set_sky_light(local_x, y as usize, local_z, level),
+            (Some(chunk), LightChannel::Block) => chunk.set_block_light(local_x, y as usize, local_z, level),
+            (None, _) => return
+        }
+        self.mark_dirty(x, z);
+    }
+
+    /// Marks the chunk of a block for saving and meshing, and the chunks next to it for
+    /// meshing when the block is on a border, since their meshes read it for culling and light
+    fn mark_dirty(&mut self, x: i32, z: i32) {
+        let (position, local_x, local_z) = block_view::local(x, z);
+        self.dirty_chunks.insert(position);
+        self.unsaved_chunks.insert(position);
+
+        if local_x == 0 {
+            self.dirty_chunks.insert(ChunkPos::new(position.x - 1, position.z));
+        } else if local_x == CHUNK_SIZE - 1 {
+            self.dirty_chunks.insert(ChunkPos::new(position.x + 1, position.z));
+        }
+        if local_z == 0 {
+            self.dirty_chunks.insert(ChunkPos::new(position.x, position.z - 1));
+        } else if local_z == CHUNK_SIZE - 1 {
+            self.dirty_chunks.insert(ChunkPos::new(position.x, position.z + 1));
+        }
+    }
+
+    /// Loaded chunks that need a new mesh and unloaded ones that lose theirs, clearing the list
+    pub fn take_dirty_chunks(&mut self) -> Vec<ChunkPos> {
+        let (chunks, unloaded_chunks) = (&self.chunks, &self.unloaded_chunks);
+        let dirty_chunks = self.dirty_chunks
+            .drain()
+            .filter(|position| chunks.contains_key(position) || unloaded_chunks.contains(position))
+            .collect();
+        self.unloaded_chunks.clear();
+        dirty_chunks
+    }
+
+    pub fn biome_at(&self, x: i32, z: i32) -> Biome {
+        BlockView::biome_at(self, x, z)
+    }
+
+    /// y of the highest block that stops precipitation, None for unloaded or empty columns
+    pub fn highest_opaque_block(&self, x: i32, z: i32) -> Option<i32> {
+        let (position, local_x, local_z) = block_view::local(x, z);
+        self.chunks
+            .get(&position)
+            .and_then(|chunk| chunk.highest_opaque_block(local_x, local_z))
+    }
+
+    /// What falls at this position right now, taking the biome and height into account
+    pub fn precipitation_at(&self, x: i32, y: i32, z: i32) -> Precipitation {
+        if self.weather.rain_level(1.0) <= 0.0 {
+            return Precipitation::None;
+        }
+        self.biome_at(x, z).precipitation_at(y)
+    }
+}
+
+impl BlockView for World {
+    fn chunk(&self, position: ChunkPos) -> Option<&Chunk> {
+        self.chunks.get(&position).map(|chunk| chunk.as_ref())
+    }
+
+    fn unloaded_biome(&self, x: i32, z: i32) -> Biome {
+        self.generator.biome_at(x, z)
+    }
+}
+
+/// Where a test keeps its world, named after the test
+#[cfg(test)]
+pub fn test_directory(name: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!("world_{}_{}", name, std::process::id()))
+}
+
+#[cfg(test)]
+impl World {
+    /// A world of stone up to `FLAT_HEIGHT` within `radius` chunks of the origin, lit and
+    /// saved to `test_directory(name)`
+    pub fn flat(name: &str, radius: i32) -> World {
+        const FLAT_HEIGHT: usize = 64;
+
+        let directory = test_directory(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        let blocks = Arc::new(crate::world::block_registry::world_block_registry());
+        let palette = BlockPalette::new(vec![], &blocks).unwrap();
+        let mut level = LevelData::new(0);
+        level.block_states = palette.names().to_vec();
+
+        let saver = WorldSaver::new(&directory).unwrap();
+        let mut world = World::with_level(level, saver, blocks, Arc::new(palette));
+        for chunk_x in -radius..=radius {
+            for chunk_z in -radius..=radius {
+                let mut chunk = Chunk::new(ChunkPos::new(chunk_x, chunk_z));
+                for y in 0..FLAT_HEIGHT {
+                    for x in 0..CHUNK_SIZE {
+                        for z in 0..CHUNK_SIZE {
+                            chunk.set_block(x, y, z, Block::Stone);
+                        }
+                    }
+                }
+                world.add_chunk(chunk, true);
+            }
+        }
+        world
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_registry::world_block_registry;
+    use std::path::PathBuf;
+
+    fn fresh_directory(name: &str) -> PathBuf {
+        let directory = test_directory(name);
+        let _ = std::fs::remove_dir_all(&directory);
+        directory
+    }
+
+    fn load(directory: &Path, seed: u64) -> std::io::Result<World> {
+        World::load(directory, seed, Arc::new(world_block_registry()))
+    }
+
+    #[test]
+    fn saved_world_reopens_with_its_seed_and_blocks() {
+        let directory = fresh_directory("load_reopen");
+        let mut world = load(&directory, 7).unwrap();
+        world.set_block(1, 100, 2, Block::Obsidian);
+        world.save_and_flush();
+        drop(world);
+
+        let mut world = load(&directory, 8).unwrap();
+        assert_eq!(world.level.seed, 7);
+        world.generate_around(0, 0, 0);
+        assert_eq!(world.get_block(1, 100, 2), Block::Obsidian);
+    }
+
+    #[test]
+    fn unreadable_level_data_is_an_error_and_left_alone() {
+        let directory = fresh_directory("load_corrupt_level");
+        let mut world = load(&directory, 7).unwrap();
+        world.save_and_flush();
+        drop(world);
+
+        let path = directory.join(LEVEL_FILE_NAME);
+        std::fs::write(&path, b"not a level").unwrap();
+        assert!(load(&directory, 7).is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"not a level");
+    }
+
+    #[test]
+    fn saved_chunks_without_level_data_are_an_error() {
+        let directory = fresh_directory("load_missing_level");
+        let mut world = load(&directory, 7).unwrap();
+        world.save_and_flush();
+        drop(world);
+
+        std::fs::remove_file(directory.join(LEVEL_FILE_NAME)).unwrap();
+        assert!(load(&directory, 7).is_err());
+    }
+
+    #[test]
+    fn chunks_left_behind_are_saved_and_unloaded() {
+        let mut world = World::flat("unload", 1);
+        world.set_block(3, 64, 3, Block::Torch);
+        world.take_dirty_chunks();
+
+        let jobs = JobSystem::new();
+        world.stream_around(&jobs, 10 * CHUNK_SIZE as i32, 0, 0);
+        assert!(!world.chunks.contains_key(&ChunkPos::new(0, 0)));
+        assert!(world.take_dirty_chunks().contains(&ChunkPos::new(0, 0)));
+
+        world.generate_around(0, 0, 0);
+        assert_eq!(world.get_block(3, 64, 3), Block::Torch);
+        assert_eq!(world.get_block(3, 63, 3), Block::Stone);
+    }
+
+    #[test]
+    fn saved_chunks_without_a_palette_are_an_error() {
+        let directory = fresh_directory("load_missing_palette");
+        let mut world = load(&directory, 7).unwrap();
+        world.level.block_states.clear();
+        world.save_and_flush();
+        drop(world);
+
+        assert!(load(&directory, 7).is_err());
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::world::chunk::ChunkPos;
+use crate::world::level_data::LevelData;
+use crate::world::region::{RegionFile, RegionPos};
+
+enum SaveMessage {
+    Chunk(ChunkPos, Vec<u8>),
+    Level(LevelData),
+    Load(ChunkPos, Sender<Option<Vec<u8>>>),
+    Flush(Sender<()>),
+    Shutdown
+}
+
+/// Owns every file of a world on a background thread. Writes are queued so compression
+/// and disk access never stall a frame, reads are answered in order after earlier writes.
+pub struct WorldSaver {
+    sender: Sender<SaveMessage>,
+    thread: Option<JoinHandle<()>>,
+    region_directory: PathBuf
+}
+
+impl WorldSaver {
+    pub fn new(directory: &Path) -> std::io::Result<WorldSaver> {
+        let region_directory = directory.join("region");
+        fs::create_dir_all(&region_directory)?;
+
+        let (sender, receiver) = channel();
+        let directory = directory.to_path_buf();
+        let thread_region_directory = region_directory.clone();
+
+        let thread = thread::spawn(move || {
+            let mut regions = HashMap::new();
+
+            for message in receiver {
+                match message {
+                    SaveMessage::Chunk(position, data) => {
+                        let result = WorldSaver::region(&mut regions, &thread_region_directory, position)
+                            .and_then(|region| region.write_chunk(position, &data));
+                        if let Err(error) = result {
+                            println!("Unable to save chunk {:?}: {}", position, error);
+                        }
+                    }
+                    SaveMessage::Level(level) => {
+                        if let Err(error) = level.write_to_file(&directory) {
+                            println!("Unable to save level data: {}", error);
+                        }
+                    }
+                    SaveMessage::Load(position, reply) => {
+                        let data = WorldSaver::region(&mut regions, &thread_region_directory, position)
+                            .ok()
+                            .and_then(|region| region.read_chunk(position));
+                        let _ = reply.send(data);
+                    }
+                    SaveMessage::Flush(reply) => {
+                        let _ = reply.send(());
+                    }
+                    SaveMessage::Shutdown => break
+                }
+            }
+        });
+
+        Ok(WorldSaver {
+            sender,
+            thread: Some(thread),
+            region_directory
+        })
+    }
+
+    fn region<'a>(
+        regions: &'a mut HashMap<RegionPos, RegionFile>,
+        region_directory: &Path,
+        position: ChunkPos
+    ) -> std::io::Result<&'a mut RegionFile> {
+        let region_position = RegionPos::from_chunk(position);
+        match regions.entry(region_position) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let region = RegionFile::open(&region_directory.join(region_position.file_name()))?;
+                Ok(entry.insert(region))
+            }
+        }
+    }
+
+    /// Whether any region file was written, a world with chunks can't start over
+    pub fn has_saved_chunks(&self) -> std::io::Result<bool> {
+        for entry in fs::read_dir(&self.region_directory)? {
+            if entry?.path().extension().is_some_and(|extension| extension == "mca") {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Queues uncompressed chunk data, see `Chunk::to_bytes`
+    pub fn save_chunk(&self, position: ChunkPos, data: Vec<u8>) {
+        self.send(SaveMessage::Chunk(position, data));
+    }
+
+    pub fn save_level(&self, level: LevelData) {
+        self.send(SaveMessage::Level(level));
+    }
+
+    /// Blocks until the background thread has read the chunk
+    pub fn load_chunk(&self, position: ChunkPos) -> Option<Vec<u8>> {
+        self.loader().load_chunk(position)
+    }
+
+    /// A handle for reading chunks from other threads
+    pub fn loader(&self) -> ChunkLoader {
+        ChunkLoader {
+            sender: self.sender.clone()
+        }
+    }
+
+    /// Blocks until everything queued so far is on disk
+    pub fn flush(&self) {
+        let (reply, receiver) = channel();
+        self.send(SaveMessage::Flush(reply));
+        let _ = receiver.recv();
+    }
+
+    fn send(&self, message: SaveMessage) {
+        self.sender
+            .send(message)
+            .expect("World saver thread stopped");
+    }
+}
+
+/// Reads chunks through the saver thread, so reads are ordered after the writes queued
+/// before them. Reads after the saver stopped find nothing.
+pub struct ChunkLoader {
+    sender: Sender<SaveMessage>
+}
+
+impl ChunkLoader {
+    /// Blocks until the background thread has read the chunk
+    pub fn load_chunk(&self, position: ChunkPos) -> Option<Vec<u8>> {
+        let (reply, receiver) = channel();
+        self.sender.send(SaveMessage::Load(position, reply)).ok()?;
+        receiver.recv().ok().flatten()
+    }
+}
+
+impl Drop for WorldSaver {
+    fn drop(&mut self) {
+        let _ = self.sender.send(SaveMessage::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}